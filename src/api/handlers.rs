@@ -1,15 +1,19 @@
 use axum::{
-    extract::{ConnectInfo, State},
+    extract::{ConnectInfo, Path, Query, State},
     http::StatusCode,
     response::{IntoResponse, Json, Response},
 };
+use chrono::{DateTime, Utc};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::{net::SocketAddr, sync::Arc};
 use uuid::Uuid;
 
 use crate::{
+    api::middleware::current_request_id,
     config::Config,
-    session::{SessionError, SessionManager, SessionResponse},
+    session::{SessionError, SessionManager},
+    xray::log_files::LogQuery,
 };
 
 /// Состояние приложения для handlers
@@ -70,6 +74,9 @@ pub async fn disconnect_handler(
 pub struct StartRequest {
     pub session_id: Uuid,
     pub config: String,
+    /// Запустить даже если ядро уже работает с этой конфигурацией
+    #[serde(default)]
+    pub force: bool,
 }
 
 pub async fn start_handler(
@@ -77,7 +84,7 @@ pub async fn start_handler(
     Json(request): Json<StartRequest>,
 ) -> Response {
     match state.session_manager
-        .start(request.session_id, request.config, &state.config)
+        .start(request.session_id, request.config, &state.config, request.force)
         .await
     {
         Ok(response) => Json(response).into_response(),
@@ -106,6 +113,9 @@ pub async fn stop_handler(
 pub struct RestartRequest {
     pub session_id: Uuid,
     pub config: String,
+    /// Перезапустить даже если конфигурация не изменилась
+    #[serde(default)]
+    pub force: bool,
 }
 
 pub async fn restart_handler(
@@ -113,7 +123,7 @@ pub async fn restart_handler(
     Json(request): Json<RestartRequest>,
 ) -> Response {
     match state.session_manager
-        .restart(request.session_id, request.config, &state.config)
+        .restart(request.session_id, request.config, &state.config, request.force)
         .await
     {
         Ok(response) => Json(response).into_response(),
@@ -121,7 +131,188 @@ pub async fn restart_handler(
     }
 }
 
+/// Validate endpoint
+#[derive(Debug, Deserialize)]
+pub struct ValidateRequest {
+    pub session_id: Uuid,
+    pub config: String,
+}
+
+/// Проверяет конфигурацию без запуска Xray
+pub async fn validate_handler(
+    State(state): State<AppState>,
+    Json(request): Json<ValidateRequest>,
+) -> Response {
+    match state.session_manager
+        .validate(request.session_id, request.config, &state.config)
+        .await
+    {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => ApiError::Session(e).into_response(),
+    }
+}
+
+/// Rollback endpoint
+#[derive(Debug, Deserialize)]
+pub struct RollbackRequest {
+    pub session_id: Uuid,
+    pub revision: u64,
+}
+
+pub async fn rollback_handler(
+    State(state): State<AppState>,
+    Json(request): Json<RollbackRequest>,
+) -> Response {
+    match state.session_manager.rollback(request.session_id, request.revision).await {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => ApiError::Session(e).into_response(),
+    }
+}
+
+/// Успешно примененные конфигурации Xray
+pub async fn config_history_handler(
+    State(state): State<AppState>,
+    Query(params): Query<SessionQuery>,
+) -> Response {
+    match state.session_manager.config_history(params.session_id).await {
+        Ok(history) => Json(history).into_response(),
+        Err(e) => ApiError::Session(e).into_response(),
+    }
+}
+
+/// Effective config query parameters
+#[derive(Debug, Deserialize)]
+pub struct EffectiveConfigQuery {
+    pub session_id: Uuid,
+    /// Маскировать UUID, пароли и приватные ключи (по умолчанию да)
+    #[serde(default = "default_redact")]
+    pub redact: bool,
+}
+
+fn default_redact() -> bool {
+    true
+}
+
+/// Конфигурация, которую получил работающий Xray
+pub async fn effective_config_handler(
+    State(state): State<AppState>,
+    Query(params): Query<EffectiveConfigQuery>,
+) -> Response {
+    match state.session_manager.effective_config(params.session_id, params.redact).await {
+        Ok(config) => Json(config).into_response(),
+        Err(e) => ApiError::Session(e).into_response(),
+    }
+}
+
+/// Query parameters of GET endpoints without extra filters
+#[derive(Debug, Deserialize)]
+pub struct SessionQuery {
+    pub session_id: Uuid,
+}
+
+/// Состояние и потребление ресурсов процесса Xray
+pub async fn metrics_handler(
+    State(state): State<AppState>,
+    Query(params): Query<SessionQuery>,
+) -> Response {
+    match state.session_manager.metrics(params.session_id).await {
+        Ok(metrics) => Json(metrics).into_response(),
+        Err(e) => ApiError::Session(e).into_response(),
+    }
+}
+
+/// Список записей о падениях Xray
+pub async fn crashes_handler(
+    State(state): State<AppState>,
+    Query(params): Query<SessionQuery>,
+) -> Response {
+    match state.session_manager.crashes(params.session_id).await {
+        Ok(crashes) => Json(crashes).into_response(),
+        Err(e) => ApiError::Session(e).into_response(),
+    }
+}
+
+/// Запись о падении Xray с логом
+pub async fn crash_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<SessionQuery>,
+) -> Response {
+    match state.session_manager.crash(params.session_id, id).await {
+        Ok(crash) => Json(crash).into_response(),
+        Err(e) => ApiError::Session(e).into_response(),
+    }
+}
+
+/// Historical logs query parameters
+#[derive(Debug, Deserialize)]
+pub struct LogsHistoryQuery {
+    pub session_id: Uuid,
+    /// Начало окна: RFC 3339 или unix timestamp в секундах
+    pub from: Option<String>,
+    /// Конец окна: RFC 3339 или unix timestamp в секундах
+    pub to: Option<String>,
+    pub contains: Option<String>,
+    pub regex: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// Максимальное количество строк в одном ответе /logs/history
+const LOGS_HISTORY_MAX_LIMIT: usize = 10000;
+
+/// Парсит границу временного окна
+fn parse_timestamp(name: &str, value: &str) -> Result<DateTime<Utc>, ApiError> {
+    if let Ok(ts) = DateTime::parse_from_rfc3339(value) {
+        return Ok(ts.with_timezone(&Utc));
+    }
+    value
+        .parse::<i64>()
+        .ok()
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+        .ok_or_else(|| ApiError::Validation(format!("{} must be an RFC 3339 time or unix timestamp", name)))
+}
+
+/// Historical logs endpoint: строки из файлов логов за временное окно
+pub async fn logs_history_handler(
+    State(state): State<AppState>,
+    Query(params): Query<LogsHistoryQuery>,
+) -> Response {
+    let query = match build_log_query(&params) {
+        Ok(query) => query,
+        Err(e) => return e.into_response(),
+    };
+    
+    match state.session_manager.query_logs(params.session_id, query).await {
+        Ok(result) => Json(result).into_response(),
+        Err(e) => ApiError::Session(e).into_response(),
+    }
+}
+
+fn build_log_query(params: &LogsHistoryQuery) -> Result<LogQuery, ApiError> {
+    let regex = params.regex.as_deref()
+        .map(Regex::new)
+        .transpose()
+        .map_err(|e| ApiError::Validation(format!("Invalid regex: {}", e)))?;
+    
+    let limit = params.limit.unwrap_or(1000);
+    if limit == 0 || limit > LOGS_HISTORY_MAX_LIMIT {
+        return Err(ApiError::Validation(format!(
+            "limit must be between 1 and {}",
+            LOGS_HISTORY_MAX_LIMIT
+        )));
+    }
+    
+    Ok(LogQuery {
+        from: params.from.as_deref().map(|value| parse_timestamp("from", value)).transpose()?,
+        to: params.to.as_deref().map(|value| parse_timestamp("to", value)).transpose()?,
+        contains: params.contains.clone(),
+        regex,
+        limit,
+    })
+}
+
 /// Обработчик ошибок валидации (аналог validation_exception_handler в Python)
+#[allow(dead_code)]
 #[derive(Debug, Serialize)]
 pub struct ValidationErrorResponse {
     pub detail: serde_json::Map<String, serde_json::Value>,
@@ -157,7 +348,63 @@ impl From<ApiError> for (StatusCode, Json<serde_json::Value>) {
                     }
                 })),
             ),
-            ApiError::Session(SessionError::StartupFailed(msg)) |
+            ApiError::Session(SessionError::ConfigIssues(issues)) => {
+                let summary: Vec<String> = issues.iter().map(ToString::to_string).collect();
+                (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    Json(serde_json::json!({
+                        "detail": {
+                            "config": format!("Failed to decode config: {}", summary.join("; ")),
+                            "errors": issues
+                        }
+                    })),
+                )
+            }
+            ApiError::Session(SessionError::LintFailed(report)) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(serde_json::json!({
+                    "detail": format!("Config has {} lint errors (CONFIG_LINT_STRICT)", report.errors.len()),
+                    "lint": report
+                })),
+            ),
+            ApiError::Session(SessionError::NotFound(msg)) => (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({
+                    "detail": msg
+                })),
+            ),
+            ApiError::Session(SessionError::InvalidRequest(msg)) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(serde_json::json!({
+                    "detail": msg
+                })),
+            ),
+            ApiError::Session(SessionError::OperationInProgress(operation)) => (
+                StatusCode::CONFLICT,
+                Json(serde_json::json!({
+                    "detail": format!("Another operation is in progress: {}", operation),
+                    "operation": operation
+                })),
+            ),
+            ApiError::Session(SessionError::Conflict(msg)) => (
+                StatusCode::CONFLICT,
+                Json(serde_json::json!({
+                    "detail": msg
+                })),
+            ),
+            ApiError::Session(SessionError::StartupFailed(failure)) => {
+                let mut body = serde_json::json!({
+                    "detail": failure.message,
+                    "code": failure.code,
+                    "exit_code": failure.exit_code,
+                    "exit_signal": failure.exit_signal,
+                    "log_tail": failure.log_tail,
+                });
+                if let Some(ref rollback) = failure.rollback {
+                    body["rollback"] = serde_json::json!(rollback);
+                }
+                (StatusCode::SERVICE_UNAVAILABLE, Json(body))
+            }
             ApiError::Session(SessionError::CoreError(msg)) => (
                 StatusCode::SERVICE_UNAVAILABLE,
                 Json(serde_json::json!({
@@ -189,8 +436,18 @@ impl From<ApiError> for (StatusCode, Json<serde_json::Value>) {
 // Реализуем IntoResponse для ApiError
 impl axum::response::IntoResponse for ApiError {
     fn into_response(self) -> axum::response::Response {
-        let (status, json) = self.into();
-        (status, json).into_response()
+        let message = self.to_string();
+        let (status, Json(mut body)) = self.into();
+        if status.is_server_error() {
+            tracing::warn!("Request failed with {}: {}", status, message);
+        } else {
+            tracing::debug!("Request rejected with {}: {}", status, message);
+        }
+        // ID запроса позволяет найти ошибку в логах ноды
+        if let (Some(request_id), Some(body)) = (current_request_id(), body.as_object_mut()) {
+            body.insert("request_id".to_string(), serde_json::Value::String(request_id));
+        }
+        (status, Json(body)).into_response()
     }
 }
 
@@ -198,6 +455,8 @@ impl axum::response::IntoResponse for ApiError {
 mod tests {
     use super::*;
     
+    use crate::xray::model::ConfigIssue;
+    
     #[test]
     fn test_api_error_conversion() {
         let error = ApiError::Session(SessionError::SessionMismatch);
@@ -205,10 +464,48 @@ mod tests {
         assert_eq!(status, StatusCode::FORBIDDEN);
     }
     
+    #[test]
+    fn test_logs_history_query_validation() {
+        let mut params = LogsHistoryQuery {
+            session_id: Uuid::new_v4(),
+            from: Some("2024-01-01T00:00:00Z".to_string()),
+            to: Some("1704067260".to_string()),
+            contains: None,
+            regex: Some("Warning".to_string()),
+            limit: None,
+        };
+        
+        let query = build_log_query(&params).unwrap();
+        assert_eq!(query.limit, 1000);
+        assert_eq!((query.to.unwrap() - query.from.unwrap()).num_seconds(), 60);
+        
+        params.regex = Some("(".to_string());
+        assert!(build_log_query(&params).is_err());
+        
+        params.regex = None;
+        params.from = Some("yesterday".to_string());
+        assert!(build_log_query(&params).is_err());
+    }
+    
+    #[test]
+    fn test_config_issues_are_listed() {
+        let issues = vec![ConfigIssue {
+            path: "inbounds[1].port".to_string(),
+            reason: "port 70000 is out of range 0..65535".to_string(),
+        }];
+        let (status, Json(body)) = ApiError::Session(SessionError::ConfigIssues(issues)).into();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            body["detail"]["config"],
+            "Failed to decode config: inbounds[1].port: port 70000 is out of range 0..65535"
+        );
+        assert_eq!(body["detail"]["errors"][0]["path"], "inbounds[1].port");
+    }
+    
     #[test]
     fn test_validation_error() {
         let error = ApiError::Validation("Invalid input".to_string());
-        let (status, json) = error.into();
+        let (status, _json) = error.into();
         assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
     }
 } 
\ No newline at end of file
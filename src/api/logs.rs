@@ -0,0 +1,267 @@
+use axum::{
+    extract::{Query, State},
+    http::HeaderMap,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Json, Response,
+    },
+};
+use futures::stream;
+use serde::{Deserialize, Serialize};
+use std::{convert::Infallible, time::Duration};
+use tokio::time::{sleep, timeout};
+use uuid::Uuid;
+
+use crate::{
+    api::handlers::{ApiError, AppState},
+    xray::{
+        log_level::XrayLogLevel,
+        logs::{LogEvent, LogSubscription},
+    },
+};
+
+/// Ожидание новых строк в /logs/tail по умолчанию
+const TAIL_DEFAULT_TIMEOUT: f64 = 25.0;
+
+/// Максимальное ожидание новых строк в /logs/tail
+const TAIL_MAX_TIMEOUT: f64 = 60.0;
+
+/// Максимальное количество строк в одном ответе /logs/tail
+const TAIL_MAX_LINES: usize = 1000;
+
+/// Как часто SSE поток проверяет, что session_id все еще актуален
+const SESSION_CHECK_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Параметры SSE потока логов
+#[derive(Debug, Deserialize)]
+pub struct LogsStreamQuery {
+    pub session_id: Uuid,
+    /// Курсор: номер последней полученной строки (как заголовок Last-Event-ID)
+    pub since: Option<u64>,
+}
+
+/// Параметры long-poll запроса логов
+#[derive(Debug, Deserialize)]
+pub struct LogsTailQuery {
+    pub session_id: Uuid,
+    /// Курсор из предыдущего ответа; без него возвращается текущий буфер
+    pub since: Option<u64>,
+    /// Сколько секунд ждать новых строк, если их еще нет
+    pub timeout: Option<f64>,
+}
+
+/// Ответ long-poll запроса логов
+#[derive(Debug, Serialize)]
+pub struct LogsTailResponse {
+    pub lines: Vec<String>,
+    /// Курсор для следующего запроса (since)
+    pub cursor: u64,
+    /// Сколько строк пропущено после предыдущего курсора
+    pub dropped: u64,
+}
+
+/// Параметры чтения уровней логов
+#[derive(Debug, Deserialize)]
+pub struct LogLevelQuery {
+    pub session_id: Uuid,
+}
+
+/// Смена уровней логов без перезапуска Xray
+#[derive(Debug, Deserialize)]
+pub struct LogLevelRequest {
+    pub session_id: Uuid,
+    /// Уровень строк Xray: none, error, warning, info, debug
+    pub xray_level: Option<XrayLogLevel>,
+    /// Передавать ли строки access лога
+    pub access_log: Option<bool>,
+    /// Директивы фильтра логов ноды в формате RUST_LOG
+    pub node_level: Option<String>,
+}
+
+/// Подписка на конвейер логов с курсора или с начала буфера
+async fn subscribe(state: &AppState, since: Option<u64>) -> LogSubscription {
+    let logs = state.session_manager.get_logs();
+    match since {
+        Some(cursor) => logs.subscribe_after(cursor).await,
+        None => logs.subscribe().await,
+    }
+}
+
+/// SSE поток логов (альтернатива WebSocket /logs)
+/// Каждое событие несет id = курсор, поэтому EventSource продолжает поток без пропусков
+pub async fn logs_sse_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<LogsStreamQuery>,
+) -> Response {
+    // Та же проверка session_id, что и в logs_websocket
+    if let Err(e) = state.session_manager.match_session_id(params.session_id).await {
+        return ApiError::Session(e).into_response();
+    }
+
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    let subscription = subscribe(&state, last_event_id.or(params.since)).await;
+
+    let events = stream::unfold(
+        (subscription, state, params.session_id),
+        |(mut logs, state, session_id)| async move {
+            loop {
+                tokio::select! {
+                    event = logs.recv() => {
+                        let event = sse_event(event?, logs.cursor());
+                        return Some((Ok::<Event, Infallible>(event), (logs, state, session_id)));
+                    }
+                    _ = sleep(SESSION_CHECK_INTERVAL) => {
+                        // Поток закрывается, как только управление перешло к другому клиенту
+                        if state.session_manager.get_session_id().await != Some(session_id) {
+                            return None;
+                        }
+                    }
+                }
+            }
+        },
+    );
+
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
+
+/// Преобразует событие конвейера в SSE событие
+fn sse_event(event: LogEvent, cursor: u64) -> Event {
+    let event_out = Event::default().id(cursor.to_string());
+    match event {
+        LogEvent::Line(line) => event_out.data(line),
+        LogEvent::Dropped(count) => event_out.event("dropped").data(count.to_string()),
+    }
+}
+
+/// Long-poll логов: сразу возвращает доступные строки или ждет новые до timeout
+pub async fn logs_tail_handler(
+    State(state): State<AppState>,
+    Query(params): Query<LogsTailQuery>,
+) -> Response {
+    let wait = params.timeout.unwrap_or(TAIL_DEFAULT_TIMEOUT);
+    if !(0.0..=TAIL_MAX_TIMEOUT).contains(&wait) {
+        return ApiError::Validation(format!(
+            "timeout must be between 0 and {} seconds",
+            TAIL_MAX_TIMEOUT
+        ))
+        .into_response();
+    }
+
+    if let Err(e) = state.session_manager.match_session_id(params.session_id).await {
+        return ApiError::Session(e).into_response();
+    }
+
+    let mut logs = subscribe(&state, params.since).await;
+    let mut response = LogsTailResponse {
+        lines: Vec::new(),
+        cursor: 0,
+        dropped: 0,
+    };
+
+    collect_available(&mut logs, &mut response);
+    if response.lines.is_empty() && response.dropped == 0 {
+        if let Ok(Some(event)) = timeout(Duration::from_secs_f64(wait), logs.recv()).await {
+            push_event(&mut response, event);
+            collect_available(&mut logs, &mut response);
+        }
+    }
+
+    response.cursor = logs.cursor();
+    Json(response).into_response()
+}
+
+/// Забирает уже доступные события, не ожидая новых
+fn collect_available(logs: &mut LogSubscription, response: &mut LogsTailResponse) {
+    while response.lines.len() < TAIL_MAX_LINES {
+        match logs.try_recv() {
+            Some(event) => push_event(response, event),
+            None => break,
+        }
+    }
+}
+
+fn push_event(response: &mut LogsTailResponse, event: LogEvent) {
+    match event {
+        LogEvent::Line(line) => response.lines.push(line),
+        LogEvent::Dropped(count) => response.dropped += count,
+    }
+}
+
+/// Текущие уровни логов Xray и ноды
+pub async fn log_level_handler(
+    State(state): State<AppState>,
+    Query(params): Query<LogLevelQuery>,
+) -> Response {
+    match state.session_manager.log_level(params.session_id).await {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => ApiError::Session(e).into_response(),
+    }
+}
+
+/// Меняет уровень логов Xray, access лог и уровень логов ноды
+/// xray_level выше уровня запуска Xray отклоняется с 409
+pub async fn set_log_level_handler(
+    State(state): State<AppState>,
+    Json(request): Json<LogLevelRequest>,
+) -> Response {
+    match state.session_manager
+        .set_log_level(request.session_id, request.xray_level, request.access_log, request.node_level)
+        .await
+    {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => ApiError::Session(e).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xray::logs::{LogsPipeline, LogsPipelineOptions};
+
+    fn empty_response() -> LogsTailResponse {
+        LogsTailResponse {
+            lines: Vec::new(),
+            cursor: 0,
+            dropped: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tail_resumes_from_cursor() {
+        let pipeline = LogsPipeline::new(LogsPipelineOptions {
+            buffer_lines: 2,
+            ..LogsPipelineOptions::default()
+        });
+        for i in 1..=4 {
+            pipeline.publish(format!("line{}", i)).await;
+        }
+
+        // Без курсора - текущий буфер
+        let mut logs = pipeline.subscribe_after(0).await;
+        let mut response = empty_response();
+        collect_available(&mut logs, &mut response);
+        assert_eq!(response.lines, vec!["line3", "line4"]);
+        assert_eq!(logs.cursor(), 4);
+
+        // Курсор 1: строка 2 уже вытеснена и считается пропущенной
+        let mut logs = pipeline.subscribe_after(1).await;
+        let mut response = empty_response();
+        collect_available(&mut logs, &mut response);
+        assert_eq!(response.dropped, 1);
+        assert_eq!(response.lines, vec!["line3", "line4"]);
+
+        // Курсор из ответа: новых строк нет, следующая придет без пропусков
+        let mut logs = pipeline.subscribe_after(4).await;
+        let mut response = empty_response();
+        collect_available(&mut logs, &mut response);
+        assert!(response.lines.is_empty());
+        pipeline.publish("line5".to_string()).await;
+        collect_available(&mut logs, &mut response);
+        assert_eq!(response.lines, vec!["line5"]);
+        assert_eq!(logs.cursor(), 5);
+    }
+}
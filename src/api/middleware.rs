@@ -0,0 +1,117 @@
+use axum::{
+    extract::Request,
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+use tracing::Span;
+use uuid::Uuid;
+
+/// Заголовок с ID запроса (принимается от панели и возвращается в ответе)
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Максимальная длина принимаемого X-Request-Id
+const REQUEST_ID_MAX_LEN: usize = 128;
+
+tokio::task_local! {
+    /// ID текущего запроса, доступен обработчикам и ApiError
+    static REQUEST_ID: String;
+}
+
+/// ID запроса в extensions
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+/// ID запроса, который сейчас обрабатывается
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Входящий ID принимается только если он короткий и без спецсимволов
+fn is_valid_request_id(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= REQUEST_ID_MAX_LEN
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+/// Назначает запросу ID (из X-Request-Id или новый) и возвращает его в ответе
+pub async fn request_id_middleware(mut request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| is_valid_request_id(value))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    request.extensions_mut().insert(RequestId(request_id.clone()));
+    let mut response = REQUEST_ID.scope(request_id.clone(), next.run(request)).await;
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// Span запроса для TraceLayer: все события обработчика, SessionManager и XrayCore
+/// попадают в него и несут request_id
+pub fn make_request_span<B>(request: &axum::http::Request<B>) -> Span {
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .map(|id| id.0.as_str())
+        .unwrap_or_default();
+
+    tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %request.method(),
+        uri = %request.uri().path(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::handlers::ApiError;
+    use axum::{body::Body, http::StatusCode, middleware, response::IntoResponse, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route("/ok", get(|| async { "ok" }))
+            .route("/fail", get(|| async { ApiError::Internal("boom".to_string()).into_response() }))
+            .layer(middleware::from_fn(request_id_middleware))
+    }
+
+    #[tokio::test]
+    async fn test_inbound_request_id_is_propagated() {
+        let request = Request::builder()
+            .uri("/fail")
+            .header(REQUEST_ID_HEADER, "panel-42")
+            .body(Body::empty())
+            .unwrap();
+        let response = app().oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "panel-42");
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["request_id"], "panel-42");
+        assert_eq!(body["detail"], "boom");
+    }
+
+    #[tokio::test]
+    async fn test_request_id_is_generated() {
+        let request = Request::builder()
+            .uri("/ok")
+            .header(REQUEST_ID_HEADER, "bad id\twith spaces")
+            .body(Body::empty())
+            .unwrap();
+        let response = app().oneshot(request).await.unwrap();
+
+        let request_id = response.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+        assert!(Uuid::parse_str(request_id).is_ok());
+    }
+}
@@ -1,4 +1,6 @@
 pub mod handlers;
+pub mod logs;
+pub mod middleware;
 pub mod server;
 pub mod websocket;
 
@@ -1,4 +1,5 @@
 use axum::{
+    middleware,
     routing::{get, post},
     Router,
 };
@@ -11,9 +12,11 @@ use tower_http::trace::TraceLayer;
 use crate::{
     api::{
         handlers::{
-            base_handler, connect_handler, disconnect_handler, ping_handler,
-            restart_handler, start_handler, stop_handler, AppState,
+            base_handler, config_history_handler, connect_handler, crash_handler, crashes_handler,
+            disconnect_handler, effective_config_handler, logs_history_handler, metrics_handler, ping_handler, rollback_handler, restart_handler, start_handler, stop_handler, validate_handler, AppState,
         },
+        logs::{log_level_handler, logs_sse_handler, logs_tail_handler, set_log_level_handler},
+        middleware::{make_request_span, request_id_middleware},
         websocket::logs_websocket_handler,
     },
     config::Config,
@@ -24,13 +27,7 @@ use crate::{
 /// Создает REST сервер (аналог FastAPI app в Python)
 pub async fn create_rest_server(config: Arc<Config>) -> Result<(), Box<dyn std::error::Error>> {
     // Создаем SessionManager (аналог Service() в Python)
-    let session_manager = Arc::new(
-        SessionManager::new(
-            config.xray_executable_path.clone(),
-            config.xray_assets_path.clone(),
-        )
-        .await?,
-    );
+    let session_manager = Arc::new(SessionManager::new(&config).await?);
     
     // Создаем состояние приложения
     let app_state = AppState {
@@ -48,12 +45,33 @@ pub async fn create_rest_server(config: Arc<Config>) -> Result<(), Box<dyn std::
         .route("/start", post(|state, json| async move { start_handler(state, json).await }))
         .route("/stop", post(stop_handler))
         .route("/restart", post(|state, json| async move { restart_handler(state, json).await }))
+        // Проверка конфигурации без запуска
+        .route("/validate", post(validate_handler))
+        // Конфигурация работающего Xray
+        .route("/config", get(effective_config_handler))
+        // История примененных конфигураций и откат
+        .route("/config/history", get(config_history_handler))
+        .route("/config/rollback", post(rollback_handler))
         // WebSocket endpoint для логов
         .route("/logs", get(logs_websocket_handler))
+        // Исторические логи из файлов (LOGS_DIR)
+        .route("/logs/history", get(logs_history_handler))
+        // Альтернативы WebSocket для HTTP-only клиентов
+        .route("/logs/sse", get(logs_sse_handler))
+        .route("/logs/tail", get(logs_tail_handler))
+        // Уровни логов Xray и ноды без перезапуска
+        .route("/logs/level", get(log_level_handler).post(set_log_level_handler))
+        // Потребление ресурсов Xray
+        .route("/metrics", get(metrics_handler))
+        // Записи о неожиданных завершениях Xray (STATE_DIR)
+        .route("/crashes", get(crashes_handler))
+        .route("/crashes/{id}", get(crash_handler))
         .with_state(app_state)
         .layer(
             ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
+                // ID запроса назначается до TraceLayer, чтобы попасть в span запроса
+                .layer(middleware::from_fn(request_id_middleware))
+                .layer(TraceLayer::new_for_http().make_span_with(make_request_span))
                 .into_inner(),
         );
     
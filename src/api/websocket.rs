@@ -7,12 +7,13 @@ use axum::{
 };
 use serde::Deserialize;
 use std::{collections::HashMap, time::Duration};
-use tokio::time::sleep;
+use tokio::time::{sleep, Instant};
 use uuid::Uuid;
 
 use crate::api::handlers::AppState;
 
 /// Параметры WebSocket для логов (аналог query_params в Python)
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 pub struct LogsQuery {
     pub session_id: String,
@@ -75,12 +76,12 @@ pub async fn logs_websocket_handler(
     ws.on_upgrade(move |socket| logs_websocket(socket, state, session_id, interval))
 }
 
-/// Основная логика WebSocket для логов (упрощенная версия)
+/// Основная логика WebSocket для логов (аналог logs в Python rest_service.py)
 async fn logs_websocket(
     mut socket: WebSocket,
     state: AppState,
     session_id: Uuid,
-    _interval: Option<f64>, // TODO: Реализовать interval логику позже
+    interval: Option<f64>,
 ) {
     // Проверяем session_id как в Python
     let current_session_id = state.session_manager.get_session_id().await;
@@ -89,10 +90,16 @@ async fn logs_websocket(
         return;
     }
     
-    // Получаем буфер логов
-    let logs_buffer = state.session_manager.get_logs_buffer();
+    // Собственная очередь подписчика с копией текущего буфера (аналог get_logs в Python)
+    let mut logs = state.session_manager.get_logs().subscribe().await;
+    
+    // Интервал отправки накопленных логов (как cache в Python версии)
+    let interval = interval
+        .filter(|val| *val > 0.0)
+        .map(Duration::from_secs_f64);
+    let mut cache = String::new();
+    let mut last_sent = Instant::now();
     
-    // Упрощенная логика - отправляем логи по мере поступления
     loop {
         // Проверяем что session_id все еще актуален
         let current_session_id = state.session_manager.get_session_id().await;
@@ -100,22 +107,43 @@ async fn logs_websocket(
             break;
         }
         
-        // Получаем новые логи
-        let new_logs = {
-            let buffer = logs_buffer.lock().unwrap();
-            buffer.get_all()
-        };
-        
-        // Отправляем логи
-        for log in new_logs {
-            if socket.send(Message::Text(log.into())).await.is_err() {
-                return;
+        if let Some(interval) = interval {
+            if !cache.is_empty() && last_sent.elapsed() >= interval {
+                if socket.send(Message::Text(std::mem::take(&mut cache).into())).await.is_err() {
+                    return;
+                }
+                last_sent = Instant::now();
             }
         }
         
-        // Ждем немного перед следующей проверкой
-        sleep(Duration::from_millis(200)).await;
+        tokio::select! {
+            event = logs.recv() => {
+                let Some(event) = event else {
+                    break;
+                };
+                
+                let text = event.into_text();
+                if interval.is_some() {
+                    cache.push_str(&text);
+                    cache.push('\n');
+                    continue;
+                }
+                
+                if socket.send(Message::Text(text.into())).await.is_err() {
+                    return;
+                }
+            }
+            message = socket.recv() => {
+                // Клиент закрыл соединение
+                if matches!(message, None | Some(Err(_)) | Some(Ok(Message::Close(_)))) {
+                    return;
+                }
+            }
+            _ = sleep(Duration::from_millis(200)) => {}
+        }
     }
+    
+    let _ = socket.send(Message::Close(None)).await;
 }
 
 #[cfg(test)]
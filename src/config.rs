@@ -1,6 +1,12 @@
 use config::{Config as ConfigBuilder, ConfigError, Environment};
 use serde::{Deserialize, Serialize};
 use std::env;
+use crate::utils::logging::{parse_log_directives, parse_rotation, LogFormat};
+use crate::xray::launch::{parse_env, IoPriority};
+use crate::xray::log_level::XrayLogLevel;
+use crate::xray::pid_file::OrphanPolicy;
+use crate::xray::process::DEFAULT_READY_PATTERN;
+use crate::xray::redact::IpRedaction;
 
 /// Application configuration, identical to config.py from Python version
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,8 +44,171 @@ pub struct Config {
     /// SERVICE_PROTOCOL - service protocol (default: "rest")
     pub service_protocol: String,
     
-    /// INBOUNDS - list of allowed inbounds (comma-separated)
+    /// INBOUNDS - inbound selectors: tags, globs, re:regex, protocol:/transport:, !excludes (comma-separated)
+    #[serde(skip_deserializing)]
     pub inbounds: Vec<String>,
+    
+    /// LOGS_BUFFER_LINES - Xray log lines kept for new subscribers (default: 100)
+    pub logs_buffer_lines: usize,
+    
+    /// LOGS_BUFFER_BYTES - total size limit of kept Xray log lines (default: 1 MiB)
+    pub logs_buffer_bytes: usize,
+    
+    /// LOGS_SUBSCRIBER_QUEUE - per-subscriber log queue length (default: 1000)
+    pub logs_subscriber_queue: usize,
+    
+    /// LOGS_DIR - directory for persistent Xray log files (optional, disabled if empty)
+    pub logs_dir: Option<String>,
+    
+    /// LOGS_FILE_MAX_BYTES - size of the active log file before rotation (default: 10 MiB)
+    pub logs_file_max_bytes: u64,
+    
+    /// LOGS_FILE_ROTATE_SECS - maximum age of the active log file before rotation (default: 86400)
+    pub logs_file_rotate_secs: u64,
+    
+    /// LOGS_FILE_COMPRESS - gzip rotated log files (default: true)
+    pub logs_file_compress: bool,
+    
+    /// LOGS_FILE_MAX_COUNT - number of rotated log files to keep (default: 10)
+    pub logs_file_max_count: usize,
+    
+    /// LOGS_FILE_MAX_AGE_SECS - maximum age of rotated log files (default: 7 days)
+    pub logs_file_max_age_secs: u64,
+    
+    /// LOGS_REDACT_IPS - client IP redaction in Xray logs: off, mask or hash (default: "off")
+    pub logs_redact_ips: String,
+    
+    /// LOGS_REDACT_IPV4_PREFIX - IPv4 octets kept when redacting (default: 2)
+    pub logs_redact_ipv4_prefix: u8,
+    
+    /// LOGS_REDACT_IPV6_PREFIX - IPv6 groups kept when redacting (default: 3)
+    pub logs_redact_ipv6_prefix: u8,
+    
+    /// LOGS_REDACT_EMAILS - hash user emails in Xray logs (default: false)
+    pub logs_redact_emails: bool,
+    
+    /// LOGS_REDACT_DESTINATIONS - drop destination domains and addresses (default: false)
+    pub logs_redact_destinations: bool,
+    
+    /// LOGS_REDACT_SALT - salt for redaction hashes (default: random per process)
+    pub logs_redact_salt: Option<String>,
+    
+    /// XRAY_RUNTIME_LOG_LEVEL - minimum level Xray is launched with, so it can be raised at runtime (default: "warning")
+    pub xray_runtime_log_level: String,
+    
+    /// XRAY_STARTUP_TIMEOUT_SECS - how long to wait for the Xray startup banner (default: 3)
+    pub xray_startup_timeout_secs: u64,
+    
+    /// XRAY_READY_PATTERN - regex of the log line that marks Xray as running (default: "Xray \S+ started")
+    pub xray_ready_pattern: String,
+    
+    /// XRAY_STOP_TIMEOUT_SECS - grace period between SIGTERM and SIGKILL when stopping Xray (default: 5)
+    pub xray_stop_timeout_secs: u64,
+    
+    /// XRAY_CONTROL_API_PORT - loopback-only Xray API port used to apply config changes without restarts (default: 0, disabled)
+    pub xray_control_api_port: u16,
+    
+    /// XRAY_ENV - extra Xray environment variables, e.g. "GOMAXPROCS=2,GOMEMLIMIT=512MiB"
+    pub xray_env: String,
+    
+    /// XRAY_ARGS - extra whitespace-separated Xray arguments after "run -config stdin:"
+    pub xray_args: String,
+    
+    /// XRAY_WORKING_DIR - working directory of the Xray process (default: node's one)
+    pub xray_working_dir: Option<String>,
+    
+    /// XRAY_RLIMIT_NOFILE - open files limit of the Xray process (default: 0, inherited)
+    pub xray_rlimit_nofile: u64,
+    
+    /// XRAY_RLIMIT_NPROC - processes limit of the Xray process (default: 0, inherited)
+    pub xray_rlimit_nproc: u64,
+    
+    /// XRAY_NICE - scheduling priority of the Xray process, -20..19 (default: 0, inherited)
+    pub xray_nice: i32,
+    
+    /// XRAY_IONICE - I/O priority of the Xray process: idle, best-effort:N or realtime:N (default: inherited)
+    pub xray_ionice: String,
+    
+    /// XRAY_UID - user id to run Xray as (default: node's user)
+    pub xray_uid: Option<u32>,
+    
+    /// XRAY_GID - group id to run Xray as (default: node's group)
+    pub xray_gid: Option<u32>,
+    
+    /// XRAY_WATCHDOG_INTERVAL_SECS - how often Xray RSS, fds and threads are sampled (default: 10, 0 disables)
+    pub xray_watchdog_interval_secs: u64,
+    
+    /// XRAY_WATCHDOG_RSS_WARN_MB - RSS that triggers a warning (default: 0, disabled)
+    pub xray_watchdog_rss_warn_mb: u64,
+    
+    /// XRAY_WATCHDOG_RSS_RESTART_MB - RSS that triggers a restart (default: 0, disabled)
+    pub xray_watchdog_rss_restart_mb: u64,
+    
+    /// XRAY_WATCHDOG_FDS_WARN - open files that trigger a warning (default: 0, disabled)
+    pub xray_watchdog_fds_warn: u64,
+    
+    /// XRAY_WATCHDOG_FDS_RESTART - open files that trigger a restart (default: 0, disabled)
+    pub xray_watchdog_fds_restart: u64,
+    
+    /// XRAY_WATCHDOG_THREADS_WARN - threads that trigger a warning (default: 0, disabled)
+    pub xray_watchdog_threads_warn: u64,
+    
+    /// XRAY_WATCHDOG_THREADS_RESTART - threads that trigger a restart (default: 0, disabled)
+    pub xray_watchdog_threads_restart: u64,
+    
+    /// XRAY_WATCHDOG_RESTART_COOLDOWN_SECS - minimum time between watchdog restarts (default: 300)
+    pub xray_watchdog_restart_cooldown_secs: u64,
+    
+    /// STATE_DIR - directory for the running core's PID file (default: disabled)
+    pub state_dir: Option<String>,
+    
+    /// CONFIG_HISTORY_SIZE - successfully applied Xray configs kept for rollback (default: 5, 0 disables)
+    pub config_history_size: usize,
+    
+    /// CONFIG_LINT_STRICT - refuse to start or restart Xray with configs that have lint errors (default: false)
+    pub config_lint_strict: bool,
+    
+    /// CONFIG_OVERRIDES_DIR - directory with JSON patches applied to every pushed config (default: disabled)
+    pub config_overrides_dir: Option<String>,
+    
+    /// INBOUND_REWRITES_FILE - JSON table of per-node inbound ports, listen addresses and certificate paths (default: disabled)
+    pub inbound_rewrites_file: Option<String>,
+    
+    /// NODE_PUBLIC_IP - value of ${NODE_PUBLIC_IP} in pushed configs (default: unset)
+    pub node_public_ip: Option<String>,
+    
+    /// CONFIG_ENV_ALLOW - environment variables pushed configs may read as ${env:NAME} (comma-separated, default: none)
+    pub config_env_allow: String,
+    
+    /// CRASH_REPORTS_MAX - number of Xray crash records kept in STATE_DIR (default: 20)
+    pub crash_reports_max: usize,
+    
+    /// CRASH_LOG_LINES - log lines saved in a crash record (default: 100)
+    pub crash_log_lines: usize,
+    
+    /// ORPHAN_POLICY - what to do with an Xray process left from a previous run: terminate or adopt (default: "terminate")
+    pub orphan_policy: String,
+    
+    /// OPERATION_WAIT_SECS - how long start/stop/restart wait for a running operation before 409 (default: 0)
+    pub operation_wait_secs: u64,
+    
+    /// LOG_FORMAT - node log format: colored, plain or json (default: "colored")
+    pub log_format: String,
+    
+    /// LOG_DIRECTIVES - comma-separated per-module levels, e.g. "rustzban_node::xray=debug,tower_http=warn"
+    pub log_directives: String,
+    
+    /// LOG_FILE_DIR - directory for rotating node log files (default: disabled)
+    pub log_file_dir: Option<String>,
+    
+    /// LOG_FILE_FORMAT - node log file format: plain or json (default: "json")
+    pub log_file_format: String,
+    
+    /// LOG_FILE_ROTATION - node log file rotation: minutely, hourly, daily or never (default: "daily")
+    pub log_file_rotation: String,
+    
+    /// LOG_FILE_MAX_COUNT - number of node log files to keep (default: 7)
+    pub log_file_max_count: usize,
 }
 
 impl Default for Config {
@@ -57,6 +226,60 @@ impl Default for Config {
             debug: false,
             service_protocol: "rest".to_string(),
             inbounds: Vec::new(),
+            logs_buffer_lines: 100,
+            logs_buffer_bytes: 1024 * 1024,
+            logs_subscriber_queue: 1000,
+            logs_dir: None,
+            logs_file_max_bytes: 10 * 1024 * 1024,
+            logs_file_rotate_secs: 86400,
+            logs_file_compress: true,
+            logs_file_max_count: 10,
+            logs_file_max_age_secs: 7 * 86400,
+            logs_redact_ips: "off".to_string(),
+            logs_redact_ipv4_prefix: 2,
+            logs_redact_ipv6_prefix: 3,
+            logs_redact_emails: false,
+            logs_redact_destinations: false,
+            logs_redact_salt: None,
+            xray_runtime_log_level: "warning".to_string(),
+            xray_startup_timeout_secs: 3,
+            xray_ready_pattern: DEFAULT_READY_PATTERN.to_string(),
+            xray_stop_timeout_secs: 5,
+            xray_control_api_port: 0,
+            xray_env: String::new(),
+            xray_args: String::new(),
+            xray_working_dir: None,
+            xray_rlimit_nofile: 0,
+            xray_rlimit_nproc: 0,
+            xray_nice: 0,
+            xray_ionice: String::new(),
+            xray_uid: None,
+            xray_gid: None,
+            xray_watchdog_interval_secs: 10,
+            xray_watchdog_rss_warn_mb: 0,
+            xray_watchdog_rss_restart_mb: 0,
+            xray_watchdog_fds_warn: 0,
+            xray_watchdog_fds_restart: 0,
+            xray_watchdog_threads_warn: 0,
+            xray_watchdog_threads_restart: 0,
+            xray_watchdog_restart_cooldown_secs: 300,
+            state_dir: None,
+            config_history_size: 5,
+            config_lint_strict: false,
+            config_overrides_dir: None,
+            inbound_rewrites_file: None,
+            node_public_ip: None,
+            config_env_allow: String::new(),
+            crash_reports_max: 20,
+            crash_log_lines: 100,
+            orphan_policy: "terminate".to_string(),
+            operation_wait_secs: 0,
+            log_format: "colored".to_string(),
+            log_directives: String::new(),
+            log_file_dir: None,
+            log_file_format: "json".to_string(),
+            log_file_rotation: "daily".to_string(),
+            log_file_max_count: 7,
         }
     }
 }
@@ -80,7 +303,50 @@ impl Config {
             .set_default("ssl_key_file", "/var/lib/rustzban-node/ssl_key.pem")?
             .set_default("debug", false)?
             .set_default("service_protocol", "rest")?
-            .set_default("inbounds", Vec::<String>::new())?
+            .set_default("logs_buffer_lines", 100)?
+            .set_default("logs_buffer_bytes", 1024 * 1024)?
+            .set_default("logs_subscriber_queue", 1000)?
+            .set_default("logs_file_max_bytes", 10 * 1024 * 1024)?
+            .set_default("logs_file_rotate_secs", 86400)?
+            .set_default("logs_file_compress", true)?
+            .set_default("logs_file_max_count", 10)?
+            .set_default("logs_file_max_age_secs", 7 * 86400)?
+            .set_default("logs_redact_ips", "off")?
+            .set_default("logs_redact_ipv4_prefix", 2)?
+            .set_default("logs_redact_ipv6_prefix", 3)?
+            .set_default("logs_redact_emails", false)?
+            .set_default("logs_redact_destinations", false)?
+            .set_default("xray_runtime_log_level", "warning")?
+            .set_default("xray_startup_timeout_secs", 3)?
+            .set_default("xray_ready_pattern", DEFAULT_READY_PATTERN)?
+            .set_default("xray_stop_timeout_secs", 5)?
+            .set_default("xray_control_api_port", 0)?
+            .set_default("xray_env", "")?
+            .set_default("xray_args", "")?
+            .set_default("xray_rlimit_nofile", 0)?
+            .set_default("xray_rlimit_nproc", 0)?
+            .set_default("xray_nice", 0)?
+            .set_default("xray_ionice", "")?
+            .set_default("xray_watchdog_interval_secs", 10)?
+            .set_default("xray_watchdog_rss_warn_mb", 0)?
+            .set_default("xray_watchdog_rss_restart_mb", 0)?
+            .set_default("xray_watchdog_fds_warn", 0)?
+            .set_default("xray_watchdog_fds_restart", 0)?
+            .set_default("xray_watchdog_threads_warn", 0)?
+            .set_default("xray_watchdog_threads_restart", 0)?
+            .set_default("xray_watchdog_restart_cooldown_secs", 300)?
+            .set_default("config_history_size", 5)?
+            .set_default("config_lint_strict", false)?
+            .set_default("config_env_allow", "")?
+            .set_default("crash_reports_max", 20)?
+            .set_default("crash_log_lines", 100)?
+            .set_default("orphan_policy", "terminate")?
+            .set_default("operation_wait_secs", 0)?
+            .set_default("log_format", "colored")?
+            .set_default("log_directives", "")?
+            .set_default("log_file_format", "json")?
+            .set_default("log_file_rotation", "daily")?
+            .set_default("log_file_max_count", 7)?
             // Load environment variables (like decouple.config in Python)
             .add_source(Environment::default())
             .build()?;
@@ -96,6 +362,46 @@ impl Config {
             };
         }
         
+        // Handle LOGS_DIR (empty string disables log persistence)
+        if settings.logs_dir.as_deref().is_some_and(str::is_empty) {
+            settings.logs_dir = None;
+        }
+        
+        // Handle LOG_FILE_DIR (empty string disables node log files)
+        if settings.log_file_dir.as_deref().is_some_and(str::is_empty) {
+            settings.log_file_dir = None;
+        }
+        
+        // Handle XRAY_WORKING_DIR (empty string keeps the node's directory)
+        if settings.xray_working_dir.as_deref().is_some_and(str::is_empty) {
+            settings.xray_working_dir = None;
+        }
+        
+        // Handle STATE_DIR (empty string disables the PID file)
+        if settings.state_dir.as_deref().is_some_and(str::is_empty) {
+            settings.state_dir = None;
+        }
+        
+        // Handle CONFIG_OVERRIDES_DIR (empty string disables overrides)
+        if settings.config_overrides_dir.as_deref().is_some_and(str::is_empty) {
+            settings.config_overrides_dir = None;
+        }
+        
+        // Handle INBOUND_REWRITES_FILE (empty string disables rewrites)
+        if settings.inbound_rewrites_file.as_deref().is_some_and(str::is_empty) {
+            settings.inbound_rewrites_file = None;
+        }
+        
+        // Handle NODE_PUBLIC_IP (empty string leaves ${NODE_PUBLIC_IP} unresolved)
+        if settings.node_public_ip.as_deref().is_some_and(str::is_empty) {
+            settings.node_public_ip = None;
+        }
+        
+        // Handle LOGS_REDACT_SALT (empty string means random salt)
+        if settings.logs_redact_salt.as_deref().is_some_and(str::is_empty) {
+            settings.logs_redact_salt = None;
+        }
+        
         // Handle INBOUNDS (comma-separated list like in Python)
         if let Ok(inbounds_str) = env::var("INBOUNDS") {
             if !inbounds_str.is_empty() {
@@ -138,6 +444,86 @@ impl Config {
             return Err("SERVICE_PORT and XRAY_API_PORT must be different".to_string());
         }
         
+        if self.xray_control_api_port != 0
+            && (self.xray_control_api_port == self.service_port || self.xray_control_api_port == self.xray_api_port)
+        {
+            return Err("XRAY_CONTROL_API_PORT must differ from SERVICE_PORT and XRAY_API_PORT".to_string());
+        }
+        
+        // Check logs pipeline limits
+        if self.logs_buffer_lines == 0 || self.logs_buffer_bytes == 0 {
+            return Err("LOGS_BUFFER_LINES and LOGS_BUFFER_BYTES must be greater than 0".to_string());
+        }
+        
+        if self.logs_subscriber_queue == 0 {
+            return Err("LOGS_SUBSCRIBER_QUEUE must be greater than 0".to_string());
+        }
+        
+        if self.logs_dir.is_some() && (self.logs_file_max_bytes == 0 || self.logs_file_rotate_secs == 0) {
+            return Err("LOGS_FILE_MAX_BYTES and LOGS_FILE_ROTATE_SECS must be greater than 0".to_string());
+        }
+        
+        // Check logs redaction policy
+        self.logs_redact_ips.parse::<IpRedaction>()
+            .map_err(|e| format!("LOGS_REDACT_IPS: {}", e))?;
+        
+        if self.logs_redact_ipv4_prefix > 4 || self.logs_redact_ipv6_prefix > 8 {
+            return Err("LOGS_REDACT_IPV4_PREFIX must be at most 4 and LOGS_REDACT_IPV6_PREFIX at most 8".to_string());
+        }
+        
+        // Check runtime Xray log level
+        self.xray_runtime_log_level.parse::<XrayLogLevel>()
+            .map_err(|e| format!("XRAY_RUNTIME_LOG_LEVEL: {}", e))?;
+        
+        // Check Xray startup detection
+        if self.xray_startup_timeout_secs == 0 {
+            return Err("XRAY_STARTUP_TIMEOUT_SECS must be greater than 0".to_string());
+        }
+        
+        regex::Regex::new(&self.xray_ready_pattern)
+            .map_err(|e| format!("XRAY_READY_PATTERN: {}", e))?;
+        
+        crate::xray::inbound_filter::InboundFilter::parse(&self.inbounds)
+            .map_err(|e| format!("INBOUNDS: {}", e))?;
+        
+        if let Some(ip) = &self.node_public_ip {
+            ip.parse::<std::net::IpAddr>()
+                .map_err(|e| format!("NODE_PUBLIC_IP: {}", e))?;
+        }
+        
+        // Check Xray launch options
+        parse_env(&self.xray_env)
+            .map_err(|e| format!("XRAY_ENV: {}", e))?;
+        
+        if !(-20..=19).contains(&self.xray_nice) {
+            return Err("XRAY_NICE must be between -20 and 19".to_string());
+        }
+        
+        if !self.xray_ionice.is_empty() {
+            self.xray_ionice.parse::<IoPriority>()
+                .map_err(|e| format!("XRAY_IONICE: {}", e))?;
+        }
+        
+        self.orphan_policy.parse::<OrphanPolicy>()
+            .map_err(|e| format!("ORPHAN_POLICY: {}", e))?;
+        
+        // Check node logging
+        self.log_format.parse::<LogFormat>()
+            .map_err(|e| format!("LOG_FORMAT: {}", e))?;
+        
+        parse_log_directives(&self.log_directives)
+            .map_err(|e| format!("LOG_DIRECTIVES: {}", e))?;
+        
+        if self.log_file_dir.is_some() {
+            let file_format = self.log_file_format.parse::<LogFormat>()
+                .map_err(|e| format!("LOG_FILE_FORMAT: {}", e))?;
+            if file_format == LogFormat::Colored {
+                return Err("LOG_FILE_FORMAT must be 'plain' or 'json'".to_string());
+            }
+            parse_rotation(&self.log_file_rotation)
+                .map_err(|e| format!("LOG_FILE_ROTATION: {}", e))?;
+        }
+        
         Ok(())
     }
 }
@@ -7,7 +7,7 @@ mod session;
 
 use config::Config;
 use ssl::certificate::{generate_certificate, save_certificate_files};
-use utils::logging::init_logging;
+use utils::logging::{init_logging, LoggingOptions};
 use std::fs;
 use tracing::{info, warn, error};
 
@@ -29,7 +29,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
     
     // Инициализируем логирование
-    init_logging(config.debug)?;
+    init_logging(&LoggingOptions::from(&config))?;
     
     info!("Starting Marzban Node (Rust version)");
     info!("Service protocol: {}", config.service_protocol);
@@ -37,7 +37,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     
     // Проверяем и генерируем SSL сертификаты если необходимо
     // Идентично логике из main.py Python версии
-    if !fs::metadata(&config.ssl_cert_file).is_ok() || !fs::metadata(&config.ssl_key_file).is_ok() {
+    if fs::metadata(&config.ssl_cert_file).is_err() || fs::metadata(&config.ssl_key_file).is_err() {
         info!("SSL certificate or key file missing, generating new ones...");
         
         let cert_pair = generate_certificate().map_err(|e| {
@@ -60,7 +60,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
     
     if let Some(ref client_cert_file) = config.ssl_client_cert_file {
-        if !fs::metadata(client_cert_file).is_ok() {
+        if fs::metadata(client_cert_file).is_err() {
             error!("Client's certificate file specified on SSL_CLIENT_CERT_FILE is missing");
             std::process::exit(1);
         }
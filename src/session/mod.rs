@@ -1,8 +1,26 @@
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{Mutex as AsyncMutex, MutexGuard, RwLock};
 use uuid::Uuid;
 use std::net::IpAddr;
-use crate::xray::{XrayCore, XrayConfig};
+use crate::config::Config;
+use crate::xray::{XrayCore, XrayCoreOptions, XrayConfig};
+use crate::utils::logging::{node_log_filter, parse_node_log_filter, set_node_log_filter};
+use crate::xray::config::XrayConfigError;
+use crate::xray::crash::{CrashRecord, CrashStore, CrashSummary};
+use crate::xray::diff::ChangeSummary;
+use crate::xray::history::RevisionSummary;
+use crate::xray::inbound_filter::DroppedInbound;
+use crate::xray::log_files::{query_logs, LogQuery, LogQueryResult};
+use crate::xray::log_level::{LogLevelState, XrayLogLevel};
+use crate::xray::lint::{lint, LintReport};
+use crate::xray::logs::LogsPipeline;
+use crate::xray::model::ConfigIssue;
+use crate::xray::overrides::OverridesReport;
+use crate::xray::process::{ApplyOutcome, XrayStartError};
+use crate::xray::startup::StartupFailure;
+use crate::xray::state::CoreStateName;
+use crate::xray::watchdog::ProcessResources;
 
 /// Session manager (analog of Service class from Python rest_service.py)
 /// Manages connection state, session_id and Xray core
@@ -22,23 +40,91 @@ pub struct SessionManager {
     
     /// Xray version (analog of self.core_version in Python)
     core_version: Option<String>,
+    
+    /// Serializes control operations: only one runs at a time
+    operation_lock: AsyncMutex<()>,
+    
+    /// Name of the running operation for the 409 response
+    current_operation: Mutex<Option<&'static str>>,
+    
+    /// How long start/stop/restart wait for another operation to finish (0 - 409 at once)
+    operation_wait: Duration,
+}
+
+/// Running control operation, released on drop
+struct OperationGuard<'a> {
+    _lock: MutexGuard<'a, ()>,
+    current: &'a Mutex<Option<&'static str>>,
+}
+
+impl Drop for OperationGuard<'_> {
+    fn drop(&mut self) {
+        *self.current.lock().unwrap() = None;
+    }
 }
 
 impl SessionManager {
     /// Create new session manager (analog of __init__ in Python)
-    pub async fn new(executable_path: String, assets_path: String) -> Result<Self, Box<dyn std::error::Error>> {
-        let xray_core = Arc::new(XrayCore::new(executable_path, assets_path).await?);
+    pub async fn new(config: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let xray_core = Arc::new(
+            XrayCore::new(
+                config.xray_executable_path.clone(),
+                config.xray_assets_path.clone(),
+                XrayCoreOptions::from(config),
+            )
+            .await?,
+        );
         let core_version = xray_core.version.clone();
         
+        // A process left from the previous run must be handled before the panel's first command
+        // The value is checked in Config::validate
+        xray_core.recover_orphan(config.orphan_policy.parse().unwrap_or_default()).await;
+        xray_core.start_watchdog();
+        
         Ok(Self {
             connected: Arc::new(RwLock::new(false)),
             client_ip: Arc::new(RwLock::new(None)),
             session_id: Arc::new(RwLock::new(None)),
             xray_core,
             core_version,
+            operation_lock: AsyncMutex::new(()),
+            current_operation: Mutex::new(None),
+            operation_wait: Duration::from_secs(config.operation_wait_secs),
         })
     }
     
+    /// Begin a control operation
+    /// wait = None waits as long as needed (a client change must not be rejected)
+    async fn begin_operation(&self, name: &'static str, wait: Option<Duration>) -> Result<OperationGuard<'_>, SessionError> {
+        let lock = match wait {
+            None => self.operation_lock.lock().await,
+            Some(wait) if wait.is_zero() => self.operation_lock.try_lock().map_err(|_| self.operation_in_progress())?,
+            Some(wait) => tokio::time::timeout(wait, self.operation_lock.lock())
+                .await
+                .map_err(|_| self.operation_in_progress())?,
+        };
+        
+        *self.current_operation.lock().unwrap() = Some(name);
+        Ok(OperationGuard {
+            _lock: lock,
+            current: &self.current_operation,
+        })
+    }
+    
+    fn operation_in_progress(&self) -> SessionError {
+        let operation = self.current_operation.lock().unwrap().unwrap_or("operation");
+        SessionError::OperationInProgress(operation.to_string())
+    }
+    
+    /// Begin start/stop/restart on behalf of the client with session_id
+    /// session_id is checked again after waiting: another client may have taken over meanwhile
+    async fn begin_session_operation(&self, name: &'static str, session_id: Uuid) -> Result<OperationGuard<'_>, SessionError> {
+        self.match_session_id(session_id).await?;
+        let guard = self.begin_operation(name, Some(self.operation_wait)).await?;
+        self.match_session_id(session_id).await?;
+        Ok(guard)
+    }
+    
     /// Check session_id match (analog of match_session_id in Python)
     pub async fn match_session_id(&self, session_id: Uuid) -> Result<(), SessionError> {
         let current_session = self.session_id.read().await;
@@ -51,19 +137,29 @@ impl SessionManager {
     /// Create standard response (analog of response in Python)
     pub async fn create_response(&self) -> SessionResponse {
         let connected = *self.connected.read().await;
-        let started = self.xray_core.started().await;
+        let state = self.xray_core.state();
         
         SessionResponse {
             connected,
-            started,
+            started: state.is_running(),
+            state: state.name(),
+            failure_reason: state.failure_reason().map(str::to_string),
             core_version: self.core_version.clone(),
+            resources: self.xray_core.resources(),
+            action: None,
+            changes: None,
+            lint: None,
+            dropped_inbounds: Vec::new(),
             session_id: None, // Will be set in specific methods if needed
         }
     }
     
     /// Client connection (analog of connect in Python)
+    #[tracing::instrument(skip(self), fields(session_id = tracing::field::Empty))]
     pub async fn connect(&self, client_ip: IpAddr) -> Result<SessionResponse, SessionError> {
         let new_session_id = Uuid::new_v4();
+        tracing::Span::current().record("session_id", tracing::field::display(new_session_id));
+        let _operation = self.begin_operation("connect", None).await?;
         
         // Check if there's already a connection
         let was_connected = *self.connected.read().await;
@@ -103,7 +199,10 @@ impl SessionManager {
     }
     
     /// Client disconnection (analog of disconnect in Python)
+    #[tracing::instrument(skip(self))]
     pub async fn disconnect(&self) -> Result<SessionResponse, SessionError> {
+        let _operation = self.begin_operation("disconnect", None).await?;
+        
         let client_ip = {
             let client_ip_lock = self.client_ip.read().await;
             *client_ip_lock
@@ -150,120 +249,298 @@ impl SessionManager {
     }
     
     /// Start Xray (analog of start in Python)
-    pub async fn start(&self, session_id: Uuid, config_json: String, app_config: &crate::config::Config) -> Result<SessionResponse, SessionError> {
-        self.match_session_id(session_id).await?;
-        
-        // Get client_ip for configuration
-        let client_ip = {
-            let client_ip_lock = self.client_ip.read().await;
-            client_ip_lock.ok_or(SessionError::NoClientIp)?
+    #[tracing::instrument(skip(self, config_json, app_config), fields(client_ip = tracing::field::Empty))]
+    pub async fn start(&self, session_id: Uuid, config_json: String, app_config: &crate::config::Config, force: bool) -> Result<SessionResponse, SessionError> {
+        let _operation = self.begin_session_operation("start", session_id).await?;
+        let (xray_config, lint) = self.load_config(&config_json, app_config).await?;
+        let dropped_inbounds = xray_config.dropped_inbounds().to_vec();
+        
+        // Sending the same config again leaves the running core alone
+        let action = if !force && self.is_running_config(&xray_config).await {
+            ConfigAction::Unchanged
+        } else {
+            // Start Xray and wait for startup (like in Python)
+            self.xray_core.start(xray_config).await?;
+            ConfigAction::Started
         };
         
-        // Parse configuration
-        let xray_config = XrayConfig::new(&config_json, client_ip.to_string(), app_config)
-            .map_err(|e| SessionError::ConfigError(e.to_string()))?;
-        
-        // Start Xray with logs like in Python
-        let logs_buffer = self.xray_core.get_logs_buffer();
+        Ok(SessionResponse {
+            lint,
+            dropped_inbounds,
+            ..self.action_response(action).await
+        })
+    }
+    
+    /// Stop Xray (analog of stop in Python)
+    #[tracing::instrument(skip(self))]
+    pub async fn stop(&self, session_id: Uuid) -> Result<SessionResponse, SessionError> {
+        let _operation = self.begin_session_operation("stop", session_id).await?;
         
-        self.xray_core.start(xray_config).await
-            .map_err(|e| SessionError::CoreError(e.to_string()))?;
+        // Stop core (ignore errors like in Python)
+        let _ = self.xray_core.stop().await;
         
-        // Wait for startup like in Python (3 seconds)
-        let start_time = std::time::Instant::now();
-        let timeout = std::time::Duration::from_secs(3);
-        let mut last_log = String::new();
+        Ok(self.create_response().await)
+    }
+    
+    /// Restart Xray (analog of restart in Python)
+    #[tracing::instrument(skip(self, config_json, app_config), fields(client_ip = tracing::field::Empty))]
+    pub async fn restart(&self, session_id: Uuid, config_json: String, app_config: &crate::config::Config, force: bool) -> Result<SessionResponse, SessionError> {
+        let _operation = self.begin_session_operation("restart", session_id).await?;
+        let (xray_config, lint) = self.load_config(&config_json, app_config).await?;
+        let dropped_inbounds = xray_config.dropped_inbounds().to_vec();
+        
+        // A restart drops every user connection, so the same config does not trigger one
+        let (action, changes) = if !force && self.is_running_config(&xray_config).await {
+            (ConfigAction::Unchanged, None)
+        } else if force {
+            self.xray_core.restart(xray_config).await?;
+            (ConfigAction::Restarted, None)
+        } else {
+            // Inbound and user changes are applied without a restart when possible
+            match self.xray_core.apply(xray_config).await? {
+                ApplyOutcome::Unchanged => (ConfigAction::Unchanged, None),
+                ApplyOutcome::Updated(changes) => (ConfigAction::Updated, Some(changes)),
+                ApplyOutcome::Restarted(changes) => (ConfigAction::Restarted, Some(changes)),
+            }
+        };
         
-        while start_time.elapsed() < timeout {
-            // Check logs for successful startup
-            let logs = {
-                let buffer = logs_buffer.lock().unwrap();
-                buffer.get_all()
-            };
-            
-            for log in logs.iter().rev().take(10) { // Take last 10 logs
-                last_log = log.clone();
-                if let Some(ref version) = self.core_version {
-                    if log.contains(&format!("Xray {} started", version)) {
-                        return Ok(self.create_response().await);
-                    }
+        Ok(SessionResponse {
+            changes,
+            lint,
+            dropped_inbounds,
+            ..self.action_response(action).await
+        })
+    }
+    
+    /// Check a config without starting Xray
+    #[tracing::instrument(skip(self, config_json, app_config), fields(client_ip = tracing::field::Empty))]
+    pub async fn validate(&self, session_id: Uuid, config_json: String, app_config: &crate::config::Config) -> Result<ValidateResponse, SessionError> {
+        self.match_session_id(session_id).await?;
+        let client_ip = self.client_ip().await?;
+        
+        let response = match XrayConfig::new(&config_json, client_ip.to_string(), app_config) {
+            Ok(xray_config) => {
+                let lint = lint(&xray_config, app_config);
+                ValidateResponse {
+                    valid: lint.errors.is_empty(),
+                    config_errors: Vec::new(),
+                    lint,
+                    dropped_inbounds: xray_config.dropped_inbounds().to_vec(),
+                    overrides: xray_config.overrides().cloned(),
                 }
             }
-            
-            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+            Err(XrayConfigError::Validation(issues)) => ValidateResponse {
+                valid: false,
+                config_errors: issues,
+                lint: LintReport::default(),
+                dropped_inbounds: Vec::new(),
+                overrides: None,
+            },
+            Err(XrayConfigError::JsonParseError(msg)) => ValidateResponse {
+                valid: false,
+                config_errors: vec![ConfigIssue { path: "$".to_string(), reason: msg }],
+                lint: LintReport::default(),
+                dropped_inbounds: Vec::new(),
+                overrides: None,
+            },
+        };
+        Ok(response)
+    }
+    
+    /// Panel IP the API routing rule is built for
+    async fn client_ip(&self) -> Result<IpAddr, SessionError> {
+        let client_ip = self.client_ip.read().await.ok_or(SessionError::NoClientIp)?;
+        tracing::Span::current().record("client_ip", tracing::field::display(client_ip));
+        Ok(client_ip)
+    }
+    
+    /// Parse and lint the panel's config
+    /// In strict mode lint errors keep the config from being applied
+    async fn load_config(&self, config_json: &str, app_config: &crate::config::Config) -> Result<(XrayConfig, Option<LintReport>), SessionError> {
+        let client_ip = self.client_ip().await?;
+        let xray_config = XrayConfig::new(config_json, client_ip.to_string(), app_config)?;
+        
+        let report = lint(&xray_config, app_config);
+        if !report.errors.is_empty() {
+            if app_config.config_lint_strict {
+                return Err(SessionError::LintFailed(report));
+            }
+            tracing::warn!("Config has {} lint errors, applying it anyway", report.errors.len());
         }
-        
-        // Check that core actually started
-        if !self.xray_core.started().await {
-            return Err(SessionError::StartupFailed(last_log));
+        Ok((xray_config, (!report.is_empty()).then_some(report)))
+    }
+    
+    /// Whether the core runs the same config (by canonical hash)
+    async fn is_running_config(&self, config: &XrayConfig) -> bool {
+        if !self.xray_core.state().is_running() {
+            return false;
         }
-        
-        Ok(self.create_response().await)
+        let hash = config.canonical_hash();
+        let unchanged = self.xray_core.running().await.is_some_and(|record| record.config_hash == hash);
+        if unchanged {
+            tracing::info!("Config is unchanged ({}), keeping Xray running", hash);
+        }
+        unchanged
     }
     
-    /// Stop Xray (analog of stop in Python)
-    pub async fn stop(&self, session_id: Uuid) -> Result<SessionResponse, SessionError> {
+    /// Response with the result of applying a config
+    async fn action_response(&self, action: ConfigAction) -> SessionResponse {
+        SessionResponse {
+            action: Some(action),
+            ..self.create_response().await
+        }
+    }
+    
+    /// Successfully applied Xray configs, newest first
+    pub async fn config_history(&self, session_id: Uuid) -> Result<Vec<RevisionSummary>, SessionError> {
         self.match_session_id(session_id).await?;
+        Ok(self.xray_core.config_history().list())
+    }
+    
+    /// Restart Xray with a stored config revision
+    #[tracing::instrument(skip(self))]
+    pub async fn rollback(&self, session_id: Uuid, revision: u64) -> Result<SessionResponse, SessionError> {
+        let _operation = self.begin_session_operation("rollback", session_id).await?;
         
-        // Stop core (ignore errors like in Python)
-        let _ = self.xray_core.stop().await;
+        let stored = self.xray_core.config_history()
+            .get(revision)
+            .ok_or_else(|| SessionError::NotFound(format!("Config revision {} not found", revision)))?;
         
-        Ok(self.create_response().await)
+        self.xray_core.restart(stored.config).await?;
+        
+        Ok(self.action_response(ConfigAction::Restarted).await)
     }
     
-    /// Restart Xray (analog of restart in Python)
-    pub async fn restart(&self, session_id: Uuid, config_json: String, app_config: &crate::config::Config) -> Result<SessionResponse, SessionError> {
+    /// Получает конвейер логов для WebSocket (аналог get_logs в Python)
+    pub fn get_logs(&self) -> Arc<LogsPipeline> {
+        self.xray_core.get_logs()
+    }
+    
+    /// Search Xray logs persisted to disk
+    pub async fn query_logs(&self, session_id: Uuid, query: LogQuery) -> Result<LogQueryResult, SessionError> {
         self.match_session_id(session_id).await?;
         
-        // Get client_ip for configuration
-        let client_ip = {
-            let client_ip_lock = self.client_ip.read().await;
-            client_ip_lock.ok_or(SessionError::NoClientIp)?
-        };
+        let dir = self.xray_core.log_files()
+            .map(|options| options.dir.clone())
+            .ok_or_else(|| SessionError::NotFound("Log persistence is disabled (LOGS_DIR is not set)".to_string()))?;
         
-        // Parse configuration
-        let xray_config = XrayConfig::new(&config_json, client_ip.to_string(), app_config)
-            .map_err(|e| SessionError::ConfigError(e.to_string()))?;
+        tokio::task::spawn_blocking(move || query_logs(&dir, &query))
+            .await
+            .map_err(|e| SessionError::LogsError(e.to_string()))?
+            .map_err(|e| SessionError::LogsError(e.to_string()))
+    }
+    
+    /// Current Xray and node log levels
+    pub async fn log_level(&self, session_id: Uuid) -> Result<LogLevelResponse, SessionError> {
+        self.match_session_id(session_id).await?;
         
-        // Перезапускаем с логами как в Python
-        let logs_buffer = self.xray_core.get_logs_buffer();
+        Ok(LogLevelResponse {
+            xray: self.xray_core.log_level().state(),
+            node_level: node_log_filter(),
+        })
+    }
+    
+    /// Change the Xray log level, access log and node log filter without a restart
+    /// The request is applied entirely or not at all
+    #[tracing::instrument(skip(self))]
+    pub async fn set_log_level(
+        &self,
+        session_id: Uuid,
+        xray_level: Option<XrayLogLevel>,
+        access_log: Option<bool>,
+        node_level: Option<String>,
+    ) -> Result<LogLevelResponse, SessionError> {
+        self.match_session_id(session_id).await?;
         
-        self.xray_core.restart(xray_config).await
-            .map_err(|e| SessionError::CoreError(e.to_string()))?;
+        let node_filter = node_level.as_deref()
+            .map(parse_node_log_filter)
+            .transpose()
+            .map_err(SessionError::InvalidRequest)?;
         
-        // Ждем запуска как в start
-        let start_time = std::time::Instant::now();
-        let timeout = std::time::Duration::from_secs(3);
-        let mut last_log = String::new();
+        let control = self.xray_core.log_level();
+        let previous = control.state();
+        let xray = control.set(xray_level, access_log).map_err(SessionError::Conflict)?;
         
-        while start_time.elapsed() < timeout {
-            let logs = {
-                let buffer = logs_buffer.lock().unwrap();
-                buffer.get_all()
-            };
-            
-            for log in logs.iter().rev().take(10) {
-                last_log = log.clone();
-                if let Some(ref version) = self.core_version {
-                    if log.contains(&format!("Xray {} started", version)) {
-                        return Ok(self.create_response().await);
-                    }
-                }
+        if let Some(filter) = node_filter {
+            if let Err(e) = set_node_log_filter(filter) {
+                // The previous settings were in effect before, so restoring them succeeds
+                let _ = control.set(Some(previous.xray_level), Some(previous.access_log));
+                return Err(SessionError::LogsError(e));
             }
-            
-            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
         }
         
-        if !self.xray_core.started().await {
-            return Err(SessionError::StartupFailed(last_log));
-        }
+        tracing::info!(
+            "Log level changed: xray={}, access_log={}, node={}",
+            xray.xray_level,
+            xray.access_log,
+            node_log_filter().unwrap_or_default()
+        );
         
-        Ok(self.create_response().await)
+        Ok(LogLevelResponse {
+            xray,
+            node_level: node_log_filter(),
+        })
+    }
+    
+    /// Xray process state and resource usage
+    pub async fn metrics(&self, session_id: Uuid) -> Result<MetricsResponse, SessionError> {
+        self.match_session_id(session_id).await?;
+        
+        let state = self.xray_core.state();
+        let running = match state.is_running() {
+            true => self.xray_core.running().await,
+            false => None,
+        };
+        Ok(MetricsResponse {
+            state: state.name(),
+            pid: running.as_ref().map(|record| record.pid),
+            uptime_secs: running
+                .as_ref()
+                .map(|record| (chrono::Utc::now() - record.started_at).num_seconds().max(0) as u64),
+            resources: self.xray_core.resources(),
+            watchdog_restarts: self.xray_core.watchdog_restarts(),
+        })
+    }
+    
+    /// Config the running Xray received; secrets are masked if redact
+    pub async fn effective_config(&self, session_id: Uuid, redact: bool) -> Result<EffectiveConfigResponse, SessionError> {
+        self.match_session_id(session_id).await?;
+        
+        let effective = self.xray_core.effective_config().await
+            .ok_or_else(|| SessionError::NotFound("Xray is not running or its config is unknown".to_string()))?;
+        Ok(EffectiveConfigResponse {
+            config: match redact {
+                true => effective.config.redacted(),
+                false => effective.config.as_value().clone(),
+            },
+            config_hash: effective.config_hash,
+            applied_at: effective.applied_at,
+            redacted: redact,
+        })
+    }
+    
+    /// Xray crash records, newest first
+    pub async fn crashes(&self, session_id: Uuid) -> Result<Vec<CrashSummary>, SessionError> {
+        self.match_session_id(session_id).await?;
+        let store = self.crash_store()?;
+        tokio::task::spawn_blocking(move || store.list())
+            .await
+            .map_err(|e| SessionError::CoreError(e.to_string()))
+    }
+    
+    /// Full Xray crash record
+    pub async fn crash(&self, session_id: Uuid, id: String) -> Result<CrashRecord, SessionError> {
+        self.match_session_id(session_id).await?;
+        let store = self.crash_store()?;
+        tokio::task::spawn_blocking(move || store.get(&id).ok_or(id))
+            .await
+            .map_err(|e| SessionError::CoreError(e.to_string()))?
+            .map_err(|id| SessionError::NotFound(format!("Crash record {} not found", id)))
     }
     
-    /// Получает буфер логов для WebSocket (аналог get_logs в Python)
-    pub fn get_logs_buffer(&self) -> Arc<std::sync::Mutex<crate::xray::logs::LogsBuffer>> {
-        self.xray_core.get_logs_buffer()
+    fn crash_store(&self) -> Result<Arc<CrashStore>, SessionError> {
+        self.xray_core.crashes()
+            .ok_or_else(|| SessionError::NotFound("Crash reports are disabled (STATE_DIR is not set)".to_string()))
     }
     
     /// Получает текущий session_id
@@ -272,6 +549,7 @@ impl SessionManager {
     }
     
     /// Проверяет подключен ли клиент
+    #[allow(dead_code)]
     pub async fn is_connected(&self) -> bool {
         *self.connected.read().await
     }
@@ -281,12 +559,93 @@ impl SessionManager {
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct SessionResponse {
     pub connected: bool,
+    /// true only in the running state
     pub started: bool,
+    /// Core state: stopped, starting, running, stopping, failed
+    pub state: CoreStateName,
+    /// Reason for the failed state
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure_reason: Option<String>,
     pub core_version: Option<String>,
+    /// Latest Xray resource sample taken by the watchdog
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resources: Option<ProcessResources>,
+    /// What start, restart and rollback did to the core
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<ConfigAction>,
+    /// What restart changed and why a restart was needed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changes: Option<ChangeSummary>,
+    /// Config lint warnings and errors
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lint: Option<LintReport>,
+    /// Inbounds removed by the INBOUNDS filter
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dropped_inbounds: Vec<DroppedInbound>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub session_id: Option<Uuid>,
 }
 
+/// Result of applying a config
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigAction {
+    Started,
+    Restarted,
+    /// Changes were applied to the running core without a restart
+    Updated,
+    /// The core already runs this config and was not restarted
+    Unchanged,
+}
+
+/// Result of checking a config
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ValidateResponse {
+    /// The config was parsed and has no lint errors
+    pub valid: bool,
+    /// Parse errors with paths
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub config_errors: Vec<ConfigIssue>,
+    pub lint: LintReport,
+    /// Inbounds removed by the INBOUNDS filter
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dropped_inbounds: Vec<DroppedInbound>,
+    /// Applied local patches if CONFIG_OVERRIDES_DIR is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overrides: Option<OverridesReport>,
+}
+
+/// Xray process metrics
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MetricsResponse {
+    pub state: CoreStateName,
+    pub pid: Option<u32>,
+    pub uptime_secs: Option<u64>,
+    /// Latest resource sample (every XRAY_WATCHDOG_INTERVAL_SECS)
+    pub resources: Option<ProcessResources>,
+    /// Restarts on resource limits since the node started
+    pub watchdog_restarts: u64,
+}
+
+/// Config of the running Xray
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EffectiveConfigResponse {
+    pub config: serde_json::Value,
+    /// Hash as in /config/history
+    pub config_hash: String,
+    pub applied_at: chrono::DateTime<chrono::Utc>,
+    pub redacted: bool,
+}
+
+/// Xray and node log levels
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LogLevelResponse {
+    #[serde(flatten)]
+    pub xray: LogLevelState,
+    /// Node log filter directives
+    pub node_level: Option<String>,
+}
+
 /// Ошибки сессии
 #[derive(Debug, thiserror::Error)]
 pub enum SessionError {
@@ -299,24 +658,62 @@ pub enum SessionError {
     #[error("Config error: {0}")]
     ConfigError(String),
     
+    /// Errors in known config sections with paths
+    #[error("Config error: {}", .0.iter().map(ConfigIssue::to_string).collect::<Vec<_>>().join("; "))]
+    ConfigIssues(Vec<ConfigIssue>),
+    
     #[error("Core error: {0}")]
     CoreError(String),
     
     #[error("Startup failed: {0}")]
-    StartupFailed(String),
+    StartupFailed(Box<StartupFailure>),
+    
+    #[error("{0}")]
+    NotFound(String),
+    
+    #[error("Logs error: {0}")]
+    LogsError(String),
+    
+    #[error("{0}")]
+    InvalidRequest(String),
+    
+    #[error("{0}")]
+    Conflict(String),
+    
+    #[error("Config has {} lint errors", .0.errors.len())]
+    LintFailed(LintReport),
+    
+    #[error("Another operation is in progress: {0}")]
+    OperationInProgress(String),
+}
+
+impl From<XrayConfigError> for SessionError {
+    fn from(error: XrayConfigError) -> Self {
+        match error {
+            XrayConfigError::Validation(issues) => SessionError::ConfigIssues(issues),
+            error => SessionError::ConfigError(error.to_string()),
+        }
+    }
+}
+
+impl From<XrayStartError> for SessionError {
+    fn from(error: XrayStartError) -> Self {
+        match error {
+            XrayStartError::Core(msg) => SessionError::CoreError(msg),
+            XrayStartError::Startup(failure) => SessionError::StartupFailed(failure),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::net::IpAddr;
+    use crate::xray::testing::{fake_xray_started_after, fake_xray_with_api};
     
     #[tokio::test]
     async fn test_session_manager_creation() {
-        let manager = SessionManager::new(
-            "/usr/local/bin/xray".to_string(),
-            "/usr/local/share/xray".to_string()
-        ).await;
+        let manager = SessionManager::new(&Config::default()).await;
         
         // Может упасть если xray не установлен - это нормально
         if manager.is_err() {
@@ -330,10 +727,7 @@ mod tests {
     
     #[tokio::test]
     async fn test_connect_disconnect() {
-        let manager = SessionManager::new(
-            "/usr/local/bin/xray".to_string(),
-            "/usr/local/share/xray".to_string()
-        ).await;
+        let manager = SessionManager::new(&Config::default()).await;
         
         if manager.is_err() {
             return;
@@ -361,10 +755,7 @@ mod tests {
     
     #[tokio::test]
     async fn test_session_id_mismatch() {
-        let manager = SessionManager::new(
-            "/usr/local/bin/xray".to_string(),
-            "/usr/local/share/xray".to_string()
-        ).await;
+        let manager = SessionManager::new(&Config::default()).await;
         
         if manager.is_err() {
             return;
@@ -379,4 +770,210 @@ mod tests {
             Err(SessionError::SessionMismatch)
         ));
     }
-} 
\ No newline at end of file
+    
+    /// Менеджер с поддельным xray, уже подключенный клиентом
+    async fn connected_manager(dir: &tempfile::TempDir, operation_wait_secs: u64) -> (Arc<SessionManager>, Uuid, Arc<Config>) {
+        let config = Arc::new(Config {
+            xray_executable_path: fake_xray_started_after(dir, "0.5"),
+            operation_wait_secs,
+            ..Config::default()
+        });
+        let manager = Arc::new(SessionManager::new(&config).await.unwrap());
+        let session_id = manager.connect("127.0.0.1".parse().unwrap()).await.unwrap().session_id.unwrap();
+        (manager, session_id, config)
+    }
+    
+    #[tokio::test]
+    async fn test_concurrent_operation_conflict() {
+        let dir = tempfile::tempdir().unwrap();
+        let (manager, session_id, config) = connected_manager(&dir, 0).await;
+        
+        let start = tokio::spawn({
+            let (manager, config) = (Arc::clone(&manager), Arc::clone(&config));
+            async move { manager.start(session_id, r#"{"inbounds": []}"#.to_string(), &config, false).await }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        
+        // Пока идет start, вторая операция сразу получает конфликт
+        let restart = manager.restart(session_id, r#"{"inbounds": []}"#.to_string(), &config, false).await;
+        assert!(matches!(restart, Err(SessionError::OperationInProgress(ref operation)) if operation == "start"));
+        
+        let response = start.await.unwrap().unwrap();
+        assert!(response.started);
+        manager.disconnect().await.unwrap();
+    }
+    
+    #[tokio::test]
+    async fn test_concurrent_operation_waits_when_configured() {
+        let dir = tempfile::tempdir().unwrap();
+        let (manager, session_id, config) = connected_manager(&dir, 5).await;
+        
+        let start = tokio::spawn({
+            let (manager, config) = (Arc::clone(&manager), Arc::clone(&config));
+            async move { manager.start(session_id, r#"{"inbounds": []}"#.to_string(), &config, false).await }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        
+        // stop дожидается завершения start и выполняется после него
+        let response = manager.stop(session_id).await.unwrap();
+        assert!(start.await.unwrap().unwrap().started);
+        assert!(!response.started);
+        assert_eq!(response.state, CoreStateName::Stopped);
+    }
+    
+    #[tokio::test]
+    async fn test_unchanged_config_is_not_restarted() {
+        let dir = tempfile::tempdir().unwrap();
+        let (manager, session_id, config) = connected_manager(&dir, 0).await;
+        let pid = || async { manager.xray_core.running().await.unwrap().pid };
+        
+        let config_json = r#"{"inbounds": [], "outbounds": [{"tag": "direct", "protocol": "freedom"}]}"#;
+        let response = manager.start(session_id, config_json.to_string(), &config, false).await.unwrap();
+        assert_eq!(response.action, Some(ConfigAction::Started));
+        let first_pid = pid().await;
+        
+        // Тот же конфиг с другим порядком ключей: ядро не перезапускается
+        let reordered = r#"{"outbounds": [{"protocol": "freedom", "tag": "direct"}], "inbounds": []}"#;
+        let response = manager.restart(session_id, reordered.to_string(), &config, false).await.unwrap();
+        assert_eq!(response.action, Some(ConfigAction::Unchanged));
+        assert!(response.started);
+        assert_eq!(pid().await, first_pid);
+        
+        let response = manager.restart(session_id, config_json.to_string(), &config, true).await.unwrap();
+        assert_eq!(response.action, Some(ConfigAction::Restarted));
+        assert_ne!(pid().await, first_pid);
+        
+        manager.disconnect().await.unwrap();
+    }
+    
+    #[tokio::test]
+    async fn test_lint_and_validate() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            xray_executable_path: fake_xray_started_after(&dir, "0"),
+            config_lint_strict: true,
+            inbounds: vec!["!blocked-*".to_string()],
+            ..Config::default()
+        };
+        let manager = SessionManager::new(&config).await.unwrap();
+        let session_id = manager.connect("127.0.0.1".parse().unwrap()).await.unwrap().session_id.unwrap();
+        let misrouted = r#"{"inbounds": [], "outbounds": [{"tag": "direct"}], "routing": {"rules": [{"outboundTag": "proxy"}]}}"#;
+        
+        let response = manager.validate(session_id, misrouted.to_string(), &config).await.unwrap();
+        assert!(!response.valid);
+        assert_eq!(response.lint.errors[0].path, "routing.rules[0].outboundTag");
+        
+        let response = manager.validate(session_id, "{".to_string(), &config).await.unwrap();
+        assert!(!response.valid);
+        assert_eq!(response.config_errors[0].path, "$");
+        
+        // Строгий режим не запускает Xray с ошибками проверки
+        let result = manager.start(session_id, misrouted.to_string(), &config, false).await;
+        assert!(matches!(result, Err(SessionError::LintFailed(ref report)) if report.errors.len() == 1));
+        assert!(!manager.xray_core.state().is_running());
+        
+        // Предупреждения не мешают запуску и возвращаются в ответе
+        let warned = r#"{"inbounds": [{"tag": "blocked-1", "protocol": "vless"}],
+            "routing": {"rules": [{"inboundTag": "gone", "outboundTag": "API"}]}}"#;
+        let response = manager.start(session_id, warned.to_string(), &config, false).await.unwrap();
+        assert!(response.started);
+        assert_eq!(response.lint.unwrap().warnings.len(), 1);
+        // Ответ сообщает панели, какие inbounds отбросил INBOUNDS
+        assert_eq!(response.dropped_inbounds[0].tag, "blocked-1");
+        assert_eq!(response.dropped_inbounds[0].reason, "excluded by '!blocked-*'");
+        
+        manager.disconnect().await.unwrap();
+    }
+    
+    #[tokio::test]
+    async fn test_effective_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let (manager, session_id, config) = connected_manager(&dir, 0).await;
+        let config_json = r#"{"log": {"logLevel": "error"},
+            "inbounds": [{"tag": "vless-in", "protocol": "vless", "settings": {"clients": [{"id": "5f2c", "email": "a"}]}}]}"#;
+        
+        // Без работающего ядра показывать нечего
+        assert!(matches!(manager.effective_config(session_id, true).await, Err(SessionError::NotFound(_))));
+        
+        manager.start(session_id, config_json.to_string(), &config, false).await.unwrap();
+        let effective = manager.effective_config(session_id, true).await.unwrap();
+        // Xray получил поднятый уровень логов и API inbound ноды
+        assert_eq!(effective.config["log"]["loglevel"], "warning");
+        assert!(effective.config["log"].get("logLevel").is_none());
+        assert_eq!(effective.config["inbounds"][0]["tag"], "API_INBOUND");
+        assert_eq!(effective.config["inbounds"][1]["settings"]["clients"][0]["id"], "***");
+        assert_eq!(effective.config_hash, manager.xray_core.config_history().list()[0].config_hash);
+        
+        let plain = manager.effective_config(session_id, false).await.unwrap();
+        assert_eq!(plain.config["inbounds"][1]["settings"]["clients"][0]["id"], "5f2c");
+        
+        manager.disconnect().await.unwrap();
+    }
+    
+    #[tokio::test]
+    async fn test_user_changes_are_applied_without_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            xray_executable_path: fake_xray_with_api(&dir),
+            xray_control_api_port: 62052,
+            ..Config::default()
+        };
+        let manager = SessionManager::new(&config).await.unwrap();
+        let session_id = manager.connect("127.0.0.1".parse().unwrap()).await.unwrap().session_id.unwrap();
+        let pid = || async { manager.xray_core.running().await.unwrap().pid };
+        let config_json = |emails: &str, outbound: &str| format!(
+            r#"{{"inbounds": [{{"tag": "vless-in", "port": 443, "protocol": "vless", "settings": {{"clients": [{}]}}}}],
+                "outbounds": [{{"tag": "{}", "protocol": "freedom"}}]}}"#,
+            emails, outbound
+        );
+        
+        let first = config_json(r#"{"id": "1", "email": "a"}"#, "direct");
+        manager.start(session_id, first, &config, false).await.unwrap();
+        let first_pid = pid().await;
+        
+        // Новый пользователь добавляется через API, процесс тот же
+        let second = config_json(r#"{"id": "1", "email": "a"}, {"id": "2", "email": "b"}"#, "direct");
+        let response = manager.restart(session_id, second.clone(), &config, false).await.unwrap();
+        assert_eq!(response.action, Some(ConfigAction::Updated));
+        assert_eq!(response.changes.unwrap().users_added, 1);
+        assert_eq!(pid().await, first_pid);
+        let api_log = std::fs::read_to_string(dir.path().join("api.log")).unwrap();
+        assert!(api_log.starts_with("api adu --server=127.0.0.1:62052"), "{}", api_log);
+        
+        // Повтор той же конфигурации ничего не меняет
+        let response = manager.restart(session_id, second, &config, false).await.unwrap();
+        assert_eq!(response.action, Some(ConfigAction::Unchanged));
+        
+        // Изменение outbounds требует перезапуска
+        let third = config_json(r#"{"id": "1", "email": "a"}"#, "proxy");
+        let response = manager.restart(session_id, third, &config, false).await.unwrap();
+        assert_eq!(response.action, Some(ConfigAction::Restarted));
+        assert_eq!(response.changes.unwrap().restart_reason.as_deref(), Some("'outbounds' changed"));
+        assert_ne!(pid().await, first_pid);
+        
+        manager.disconnect().await.unwrap();
+    }
+    
+    #[tokio::test]
+    async fn test_raising_log_level_above_launch_level_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            xray_executable_path: fake_xray_started_after(&dir, "0"),
+            ..Config::default()
+        };
+        let manager = SessionManager::new(&config).await.unwrap();
+        let session_id = manager.connect("127.0.0.1".parse().unwrap()).await.unwrap().session_id.unwrap();
+        manager.start(session_id, r#"{"log": {"loglevel": "warning"}}"#.to_string(), &config, false).await.unwrap();
+        
+        let result = manager.set_log_level(session_id, Some(XrayLogLevel::Debug), Some(false), None).await;
+        let Err(SessionError::Conflict(message)) = result else {
+            panic!("raising the level above the launch level must be rejected");
+        };
+        assert!(message.contains("LoggerService cannot raise it to debug"), "{}", message);
+        let response = manager.log_level(session_id).await.unwrap();
+        assert_eq!(response.xray.xray_level, XrayLogLevel::Warning);
+        assert!(response.xray.access_log);
+        
+        manager.disconnect().await.unwrap();
+    }
+}
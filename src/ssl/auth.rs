@@ -13,6 +13,7 @@ use tracing::{debug, warn};
 
 /// Ошибки SSL аутентификации
 #[derive(Debug)]
+#[allow(dead_code)]
 pub enum SslAuthError {
     CertificateNotFound(String),
     InvalidCertificate(String),
@@ -54,6 +55,7 @@ impl From<std::io::Error> for SslAuthError {
 pub struct SslConfig {
     pub cert_file: String,
     pub key_file: String,
+    #[allow(dead_code)]
     pub client_cert_file: Option<String>,
 }
 
@@ -67,6 +69,7 @@ impl SslConfig {
     }
     
     /// Проверяет существование необходимых файлов сертификатов
+    #[allow(dead_code)]
     pub fn validate_files(&self) -> Result<(), SslAuthError> {
         // Проверяем серверный сертификат и ключ
         if !Path::new(&self.cert_file).exists() {
@@ -90,6 +93,7 @@ impl SslConfig {
 
 /// Создает SSL acceptor для HTTPS сервера
 /// Идентично логике из main.py Python версии
+#[allow(dead_code)]
 pub fn create_ssl_acceptor(config: &SslConfig) -> Result<SslAcceptor, SslAuthError> {
     config.validate_files()?;
     
@@ -127,6 +131,7 @@ pub fn create_ssl_acceptor(config: &SslConfig) -> Result<SslAcceptor, SslAuthErr
 
 /// Middleware для проверки клиентского сертификата
 /// Аналог проверок в Python версии main.py
+#[allow(dead_code)]
 pub async fn client_cert_middleware(
     request: Request,
     next: Next,
@@ -140,6 +145,7 @@ pub async fn client_cert_middleware(
 }
 
 /// Проверяет валидность клиентского сертификата
+#[allow(dead_code)]
 pub fn validate_client_certificate(cert_pem: &str) -> Result<(), SslAuthError> {
     let _cert = X509::from_pem(cert_pem.as_bytes())
         .map_err(|e| SslAuthError::InvalidCertificate(format!("Failed to parse certificate: {}", e)))?;
@@ -1,15 +1,24 @@
 use tracing::{Level, Subscriber};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
 use tracing_subscriber::{
-    fmt::{self, format::Writer, FormatEvent, FormatFields},
-    layer::SubscriberExt,
+    filter::Directive,
+    fmt::{self, format::Writer, FormatEvent, FormatFields, MakeWriter},
+    layer::{Layered, SubscriberExt},
+    reload,
     util::SubscriberInitExt,
-    EnvFilter, Registry,
+    EnvFilter, Layer, Registry,
 };
-use std::fmt::Result as FmtResult;
+use std::{fmt::Result as FmtResult, path::PathBuf, str::FromStr, sync::OnceLock};
+
+use crate::config::Config;
+
+/// Handle для смены фильтра логов ноды без перезапуска
+static FILTER_HANDLE: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
 
 /// ANSI color codes для терминала (идентично Colors из logger.py)
 pub struct Colors;
 
+#[allow(dead_code)]
 impl Colors {
     pub const BLACK: &'static str = "\x1b[0;30m";
     pub const RED: &'static str = "\x1b[0;31m";
@@ -81,29 +90,174 @@ where
     }
 }
 
+/// Формат логов ноды (LOG_FORMAT, LOG_FILE_FORMAT)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// "LEVEL: message" с цветами, как в Python версии
+    #[default]
+    Colored,
+    /// Текст с временем, уровнем, target и полями span
+    Plain,
+    /// JSON строки для сборщиков логов
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "colored" => Ok(LogFormat::Colored),
+            "plain" => Ok(LogFormat::Plain),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!("unknown log format '{}', expected colored, plain or json", other)),
+        }
+    }
+}
+
+/// Настройки файлов логов ноды
+#[derive(Debug, Clone)]
+pub struct LogFileOptions {
+    pub dir: PathBuf,
+    pub format: LogFormat,
+    pub rotation: Rotation,
+    pub max_files: usize,
+}
+
+/// Настройки логирования ноды
+#[derive(Debug, Clone, Default)]
+pub struct LoggingOptions {
+    /// DEBUG вместо INFO (как DEBUG в Python)
+    pub debug: bool,
+    pub format: LogFormat,
+    /// Директивы уровней по модулям поверх RUST_LOG
+    pub directives: String,
+    /// Файлы логов (None - только stdout)
+    pub file: Option<LogFileOptions>,
+}
+
+impl From<&Config> for LoggingOptions {
+    fn from(config: &Config) -> Self {
+        // Значения проверены в Config::validate
+        Self {
+            debug: config.debug,
+            format: config.log_format.parse().unwrap_or_default(),
+            directives: config.log_directives.clone(),
+            file: config.log_file_dir.as_ref().map(|dir| LogFileOptions {
+                dir: PathBuf::from(dir),
+                format: config.log_file_format.parse().unwrap_or(LogFormat::Json),
+                rotation: parse_rotation(&config.log_file_rotation).unwrap_or(Rotation::DAILY),
+                max_files: config.log_file_max_count,
+            }),
+        }
+    }
+}
+
+/// Разбирает LOG_FILE_ROTATION
+pub fn parse_rotation(value: &str) -> Result<Rotation, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "minutely" => Ok(Rotation::MINUTELY),
+        "hourly" => Ok(Rotation::HOURLY),
+        "daily" => Ok(Rotation::DAILY),
+        "never" => Ok(Rotation::NEVER),
+        other => Err(format!("unknown rotation '{}', expected minutely, hourly, daily or never", other)),
+    }
+}
+
+/// Разбирает директивы вида "rustzban_node::xray=debug,tower_http=warn"
+pub fn parse_log_directives(directives: &str) -> Result<Vec<Directive>, String> {
+    directives
+        .split(',')
+        .map(str::trim)
+        .filter(|directive| !directive.is_empty())
+        .map(|directive| {
+            directive.parse().map_err(|e| format!("invalid directive '{}': {}", directive, e))
+        })
+        .collect()
+}
+
+/// Subscriber под слоем фильтра, на который вешаются слои вывода
+type FilteredRegistry = Layered<reload::Layer<EnvFilter, Registry>, Registry>;
+
+/// Слой вывода в выбранном формате
+fn format_layer<W>(format: LogFormat, writer: W) -> Box<dyn Layer<FilteredRegistry> + Send + Sync>
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    match format {
+        LogFormat::Colored => fmt::layer().event_format(ColoredFormatter).with_writer(writer).boxed(),
+        LogFormat::Plain => fmt::layer().with_ansi(false).with_writer(writer).boxed(),
+        LogFormat::Json => fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .with_writer(writer)
+            .boxed(),
+    }
+}
+
 /// Инициализация системы логирования
-/// Идентично настройке logger в Python версии
-pub fn init_logging(debug: bool) -> Result<(), Box<dyn std::error::Error>> {
+/// Формат colored по умолчанию идентичен настройке logger в Python версии
+pub fn init_logging(options: &LoggingOptions) -> Result<(), Box<dyn std::error::Error>> {
     // Определяем уровень логирования (как в Python: DEBUG если debug=True, иначе INFO)
-    let level = if debug { Level::DEBUG } else { Level::INFO };
+    let level = if options.debug { Level::DEBUG } else { Level::INFO };
     
-    // Создаем filter для уровня логирования
-    let env_filter = EnvFilter::from_default_env()
+    // Создаем filter для уровня логирования, директивы LOG_DIRECTIVES уточняют его по модулям
+    let mut env_filter = EnvFilter::from_default_env()
         .add_directive(level.into());
+    for directive in parse_log_directives(&options.directives)? {
+        env_filter = env_filter.add_directive(directive);
+    }
     
-    // Настраиваем форматтер с цветным выводом
-    let fmt_layer = fmt::layer()
-        .event_format(ColoredFormatter);
+    // Фильтр можно заменить во время работы (POST /logs/level)
+    let (env_filter, handle) = reload::Layer::new(env_filter);
+    
+    // Вывод в stdout и, если включено, в ротируемые файлы
+    let mut layers = vec![format_layer(options.format, std::io::stdout)];
+    if let Some(ref file) = options.file {
+        let appender = RollingFileAppender::builder()
+            .rotation(file.rotation.clone())
+            .filename_prefix("rustzban-node")
+            .filename_suffix("log")
+            .max_log_files(file.max_files)
+            .build(&file.dir)?;
+        // Цвета в файле не нужны
+        let format = match file.format {
+            LogFormat::Colored => LogFormat::Plain,
+            format => format,
+        };
+        layers.push(format_layer(format, appender));
+    }
     
     // Инициализируем tracing subscriber
     Registry::default()
         .with(env_filter)
-        .with(fmt_layer)
-        .init();
+        .with(layers)
+        .try_init()?;
     
+    let _ = FILTER_HANDLE.set(handle);
     Ok(())
 }
 
+/// Текущий фильтр логов ноды (None, если логирование не инициализировано)
+pub fn node_log_filter() -> Option<String> {
+    FILTER_HANDLE.get()?.with_current(|filter| filter.to_string()).ok()
+}
+
+/// Разбирает директивы фильтра ("debug", "rustzban_node=debug,tower_http=info")
+pub fn parse_node_log_filter(directives: &str) -> Result<EnvFilter, String> {
+    EnvFilter::try_new(directives).map_err(|e| format!("Invalid log directives '{}': {}", directives, e))
+}
+
+/// Заменяет фильтр логов ноды
+pub fn set_node_log_filter(filter: EnvFilter) -> Result<(), String> {
+    FILTER_HANDLE
+        .get()
+        .ok_or_else(|| "Logging is not initialized".to_string())?
+        .reload(filter)
+        .map_err(|e| e.to_string())
+}
+
 /// Макросы для удобного логирования (аналог logger.info, logger.warning и т.д.)
 #[macro_export]
 macro_rules! log_info {
@@ -136,17 +290,39 @@ macro_rules! log_debug {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tracing::info;
     
     #[test]
     fn test_logging_init() {
         // Тестируем инициализацию без ошибок  
         // Может упасть если уже инициализировано - это нормально
-        let _result1 = init_logging(false);
-        let _result2 = init_logging(true);
+        let _result1 = init_logging(&LoggingOptions::default());
+        let _result2 = init_logging(&LoggingOptions {
+            debug: true,
+            format: LogFormat::Json,
+            ..LoggingOptions::default()
+        });
         // Не проверяем результат, так как может быть уже инициализировано
     }
     
+    #[test]
+    fn test_logging_options_parsing() {
+        assert_eq!("JSON".parse::<LogFormat>().unwrap(), LogFormat::Json);
+        assert!("xml".parse::<LogFormat>().is_err());
+        assert!(parse_rotation("hourly").is_ok());
+        assert!(parse_rotation("weekly").is_err());
+        
+        assert_eq!(parse_log_directives("").unwrap().len(), 0);
+        assert_eq!(parse_log_directives("rustzban_node::xray=debug, tower_http=warn").unwrap().len(), 2);
+        assert!(parse_log_directives("rustzban_node=loud").is_err());
+    }
+    
+    #[test]
+    fn test_parse_node_log_filter() {
+        assert!(parse_node_log_filter("debug").is_ok());
+        assert!(parse_node_log_filter("rustzban_node=debug,tower_http=info").is_ok());
+        assert!(parse_node_log_filter("rustzban_node=loud").is_err());
+    }
+    
     #[test] 
     fn test_color_constants() {
         // Проверяем что цвета определены правильно
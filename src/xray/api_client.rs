@@ -0,0 +1,146 @@
+use serde_json::{json, Value};
+use std::{
+    fs,
+    io::Write,
+    os::unix::fs::OpenOptionsExt,
+    path::PathBuf,
+    time::Duration,
+};
+use tokio::process::Command as TokioCommand;
+use tracing::debug;
+
+use crate::xray::diff::InboundChanges;
+
+/// Управление inbounds и пользователями работающего Xray через `xray api`
+/// (HandlerService на локальном порту XRAY_CONTROL_API_PORT)
+#[derive(Debug, Clone)]
+pub struct XrayApiClient {
+    executable_path: String,
+    server: String,
+    /// Сколько ждать одну команду `xray api`
+    timeout: Duration,
+}
+
+impl XrayApiClient {
+    pub fn new(executable_path: &str, port: u16, timeout: Duration) -> Self {
+        Self {
+            executable_path: executable_path.to_string(),
+            server: format!("127.0.0.1:{}", port),
+            timeout,
+        }
+    }
+
+    /// Применяет изменения: сначала удаления, затем добавления,
+    /// чтобы замененный inbound или пользователь не конфликтовал со старым
+    pub async fn apply(&self, changes: &InboundChanges) -> Result<(), String> {
+        for (tag, email) in &changes.removed_users {
+            self.run("rmu", &[format!("-tag={}", tag), email.clone()]).await?;
+        }
+        if !changes.removed_inbounds.is_empty() {
+            // Без -tags rmi читает аргументы как файлы конфигурации
+            let args: Vec<String> = std::iter::once("-tags".to_string())
+                .chain(changes.removed_inbounds.iter().cloned())
+                .collect();
+            self.run("rmi", &args).await?;
+        }
+        if !changes.added_inbounds.is_empty() {
+            self.run_with_file("adi", &changes.added_inbounds).await?;
+        }
+        if !changes.added_users.is_empty() {
+            self.run_with_file("adu", &changes.added_users).await?;
+        }
+        Ok(())
+    }
+
+    /// Команда, которой inbounds передаются файлом конфигурации
+    async fn run_with_file(&self, command: &str, inbounds: &[Value]) -> Result<(), String> {
+        let path = write_private_file(&json!({ "inbounds": inbounds }))
+            .map_err(|e| format!("failed to write {} request: {}", command, e))?;
+        let result = self.run(command, &[path.to_string_lossy().into_owned()]).await;
+        // В файле ключи и пароли пользователей
+        let _ = fs::remove_file(&path);
+        result
+    }
+
+    async fn run(&self, command: &str, args: &[String]) -> Result<(), String> {
+        debug!("Running xray api {}", command);
+        let output = TokioCommand::new(&self.executable_path)
+            .arg("api")
+            .arg(command)
+            .arg(format!("--server={}", self.server))
+            .args(args)
+            .kill_on_drop(true)
+            .output();
+        let output = tokio::time::timeout(self.timeout, output)
+            .await
+            .map_err(|_| format!("xray api {} timed out after {:?}", command, self.timeout))?
+            .map_err(|e| format!("failed to run xray api {}: {}", command, e))?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let message = [stderr.trim(), stdout.trim()]
+            .into_iter()
+            .find(|text| !text.is_empty())
+            .unwrap_or("no output");
+        Err(format!("xray api {} failed ({}): {}", command, output.status, message))
+    }
+}
+
+/// Временный JSON файл, доступный только владельцу
+fn write_private_file(value: &Value) -> std::io::Result<PathBuf> {
+    let path = std::env::temp_dir().join(format!("rustzban-xray-api-{}.json", uuid::Uuid::new_v4()));
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&path)?;
+    file.write_all(value.to_string().as_bytes())?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xray::testing::fake_xray_with_api;
+
+    #[tokio::test]
+    async fn test_apply_runs_commands_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let client = XrayApiClient::new(&fake_xray_with_api(&dir), 62052, Duration::from_secs(5));
+        let changes = InboundChanges {
+            removed_inbounds: vec!["old".to_string()],
+            added_inbounds: vec![json!({"tag": "new", "port": 443})],
+            removed_users: vec![("vless-in".to_string(), "a@example.com".to_string())],
+            added_users: vec![json!({"tag": "vless-in", "settings": {"clients": [{"email": "b@example.com"}]}})],
+        };
+        client.apply(&changes).await.unwrap();
+
+        let log = std::fs::read_to_string(dir.path().join("api.log")).unwrap();
+        let commands: Vec<&str> = log
+            .lines()
+            .filter_map(|line| line.strip_prefix("api "))
+            .map(|line| line.split_whitespace().next().unwrap())
+            .collect();
+        assert_eq!(commands, vec!["rmu", "rmi", "adi", "adu"]);
+        assert!(log.contains("--server=127.0.0.1:62052 -tag=vless-in a@example.com"));
+        assert!(log.contains("rmi --server=127.0.0.1:62052 -tags old"));
+        assert!(log.contains(r#""email":"b@example.com""#));
+    }
+
+    #[tokio::test]
+    async fn test_failed_command_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = fake_xray_with_api(&dir);
+        std::fs::write(dir.path().join("api.fail"), "").unwrap();
+        let client = XrayApiClient::new(&script, 62052, Duration::from_secs(5));
+        let changes = InboundChanges {
+            removed_inbounds: vec!["old".to_string()],
+            ..InboundChanges::default()
+        };
+        let error = client.apply(&changes).await.unwrap_err();
+        assert!(error.contains("xray api rmi failed"), "{}", error);
+        assert!(error.contains("unknown service"), "{}", error);
+    }
+}
@@ -1,6 +1,14 @@
 use serde_json::{json, Map, Value};
-use std::fmt;
+use std::{fmt, path::Path};
 use crate::config::Config as AppConfig;
+use crate::xray::inbound_filter::{DroppedInbound, InboundFilter};
+use crate::xray::model::{ApiSection, ConfigIssue, Inbound, Port, RoutingRule, Routing, StringList, XrayConfigModel};
+use crate::xray::overrides::{apply_overrides, OverridesReport};
+use crate::xray::placeholders::NodeVariables;
+use crate::xray::rewrite::RewriteTable;
+
+/// Тег локального API inbound ноды (XRAY_CONTROL_API_PORT)
+pub const CONTROL_API_INBOUND_TAG: &str = "NODE_API_INBOUND";
 
 /// Конфигурация Xray (аналог класса XRayConfig из xray.py Python версии)
 /// Наследуется от serde_json::Value чтобы работать как dict в Python
@@ -18,30 +26,106 @@ pub struct XrayConfig {
     ssl_cert: String,
     /// Путь к SSL ключу (аналог self.ssl_key в Python)
     ssl_key: String,
-    /// Разрешенные inbounds (аналог INBOUNDS в Python)
-    inbounds_filter: Vec<String>,
+    /// Фильтр inbounds (аналог INBOUNDS в Python, с шаблонами и исключениями)
+    inbounds_filter: InboundFilter,
+    /// Inbounds, удаленные фильтром, с причинами
+    dropped_inbounds: Vec<DroppedInbound>,
+    /// Индекс каждого inbound в документе панели; None - inbound добавила нода
+    panel_inbounds: Vec<Option<usize>>,
+    /// Индекс каждого правила routing.rules в документе панели; None - правило добавила нода
+    panel_rules: Vec<Option<usize>>,
+    /// Порты, адреса и пути сертификатов inbounds этой ноды (INBOUND_REWRITES_FILE)
+    rewrites: Option<RewriteTable>,
+    /// Порт локального API без TLS для команд `xray api` (0 - выключен)
+    control_api_port: u16,
+    /// Примененные патчи CONFIG_OVERRIDES_DIR (None - каталог не задан)
+    overrides: Option<OverridesReport>,
 }
 
 impl XrayConfig {
     /// Create new configuration Xray из JSON строки
     /// Идентично __init__ из Python версии
+    #[tracing::instrument(name = "xray_config", skip(config_json, app_config))]
     pub fn new(config_json: &str, peer_ip: String, app_config: &AppConfig) -> Result<Self, XrayConfigError> {
         // Парсим JSON как в Python версии
-        let config: Value = serde_json::from_str(config_json)
+        let mut config: Value = serde_json::from_str(config_json)
             .map_err(|e| XrayConfigError::JsonParseError(e.to_string()))?;
         
+        // Значения, которые знает только нода, подставляются до проверки типов
+        NodeVariables::new(app_config).resolve(&mut config).map_err(XrayConfigError::Validation)?;
+        
+        let mut model = XrayConfigModel::from_value(config).map_err(XrayConfigError::Validation)?;
+        
         let mut xray_config = Self {
-            config,
+            config: Value::Null,
             peer_ip,
             api_host: app_config.xray_api_host.clone(),
             api_port: app_config.xray_api_port,
             ssl_cert: app_config.ssl_cert_file.clone(),
             ssl_key: app_config.ssl_key_file.clone(),
-            inbounds_filter: app_config.inbounds.clone(),
+            inbounds_filter: InboundFilter::parse(&app_config.inbounds).map_err(|reason| {
+                XrayConfigError::Validation(vec![ConfigIssue { path: "INBOUNDS".to_string(), reason }])
+            })?,
+            dropped_inbounds: Vec::new(),
+            panel_inbounds: Vec::new(),
+            panel_rules: Vec::new(),
+            // Таблица читается при каждой конфигурации, чтобы правка не требовала перезапуска ноды
+            rewrites: app_config
+                .inbound_rewrites_file
+                .as_deref()
+                .map(|path| RewriteTable::load(Path::new(path)))
+                .transpose()
+                .map_err(|reason| {
+                    XrayConfigError::Validation(vec![ConfigIssue { path: "INBOUND_REWRITES_FILE".to_string(), reason }])
+                })?,
+            control_api_port: app_config.xray_control_api_port,
+            overrides: None,
         };
         
         // Применяем API настройки (аналог self._apply_api() в Python)
-        xray_config.apply_api()?;
+        xray_config.apply_api(&mut model);
+        xray_config.config = model.to_value();
+        
+        // Локальные патчи ноды применяются последними и могут менять и то, что добавила нода
+        if let Some(dir) = app_config.config_overrides_dir.as_deref() {
+            let before = xray_config.config.clone();
+            let report = apply_overrides(&mut xray_config.config, Path::new(dir)).map_err(XrayConfigError::Validation)?;
+            XrayConfigModel::from_value(xray_config.config.clone()).map_err(|issues| {
+                XrayConfigError::Validation(
+                    issues
+                        .into_iter()
+                        .map(|issue| ConfigIssue {
+                            reason: format!("{} (after overrides)", issue.reason),
+                            ..issue
+                        })
+                        .collect(),
+                )
+            })?;
+            // API inbounds, их правила и секция api нужны ноде для управления и статистики
+            let node_inbounds = xray_config.panel_inbounds.iter().take_while(|index| index.is_none()).count();
+            let node_rules = xray_config.panel_rules.iter().take_while(|index| index.is_none()).count();
+            let issues = check_node_owned(&before, &xray_config.config, node_inbounds, node_rules);
+            if !issues.is_empty() {
+                return Err(XrayConfigError::Validation(issues));
+            }
+            if !report.conflicts.is_empty() {
+                tracing::warn!("Config overrides change the same paths: {:?}", report.conflicts);
+            }
+            // Патч, добавивший или удаливший элементы, сдвигает индексы; связь с панелью теряется
+            let inbounds = xray_config.config.pointer("/inbounds").and_then(Value::as_array).map_or(0, Vec::len);
+            if inbounds != xray_config.panel_inbounds.len() {
+                xray_config.panel_inbounds = vec![None; inbounds];
+            }
+            let rules = xray_config.config.pointer("/routing/rules").and_then(Value::as_array).map_or(0, Vec::len);
+            if rules != xray_config.panel_rules.len() {
+                xray_config.panel_rules = vec![None; rules];
+            }
+            xray_config.overrides = Some(report);
+        }
+        tracing::debug!(
+            "Xray config prepared with {} inbounds",
+            xray_config.config.get("inbounds").and_then(|inbounds| inbounds.as_array()).map_or(0, Vec::len)
+        );
         
         Ok(xray_config)
     }
@@ -52,7 +136,51 @@ impl XrayConfig {
         serde_json::to_string(&self.config).unwrap_or_default()
     }
     
+    /// sha256 канонического JSON конфигурации: ключи отсортированы, без пробелов
+    /// Одинаков для конфигураций, отличающихся только порядком ключей
+    pub fn canonical_hash(&self) -> String {
+        let mut canonical = String::new();
+        write_canonical(&self.config, &mut canonical);
+        openssl::sha::sha256(canonical.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+    
+    /// Конфигурация как JSON значение
+    pub fn as_value(&self) -> &Value {
+        &self.config
+    }
+    
+    /// Конфигурация с замаскированными ключами и паролями пользователей
+    pub fn redacted(&self) -> Value {
+        let mut config = self.config.clone();
+        redact_secrets(&mut config);
+        config
+    }
+    
+    /// Inbounds, которые удалил фильтр INBOUNDS
+    pub fn dropped_inbounds(&self) -> &[DroppedInbound] {
+        &self.dropped_inbounds
+    }
+    
+    /// Индекс inbound в документе панели; None - inbound добавила нода
+    pub fn panel_inbound_index(&self, index: usize) -> Option<usize> {
+        self.panel_inbounds.get(index).copied().flatten()
+    }
+    
+    /// Индекс правила routing.rules в документе панели; None - правило добавила нода
+    pub fn panel_rule_index(&self, index: usize) -> Option<usize> {
+        self.panel_rules.get(index).copied().flatten()
+    }
+    
+    /// Отчет о локальных патчах, если CONFIG_OVERRIDES_DIR задан
+    pub fn overrides(&self) -> Option<&OverridesReport> {
+        self.overrides.as_ref()
+    }
+    
     /// Получает значение из конфигурации как объект
+    #[allow(dead_code)]
     pub fn as_object(&self) -> Option<&Map<String, Value>> {
         self.config.as_object()
     }
@@ -64,88 +192,63 @@ impl XrayConfig {
     
     /// Применяет настройки API к конфигурации
     /// Идентично методу _apply_api из Python версии
-    fn apply_api(&mut self) -> Result<(), XrayConfigError> {
-        let config_obj = self.config.as_object_mut()
-            .ok_or(XrayConfigError::InvalidConfig("Config is not an object".to_string()))?;
-        
+    /// Запоминает inbounds, удаленные фильтром INBOUNDS, и индексы элементов в документе панели
+    fn apply_api(&mut self, model: &mut XrayConfigModel) {
         // Удаляем существующие API inbounds и фильтруем по INBOUNDS (как в Python)
-        if let Some(inbounds) = config_obj.get_mut("inbounds") {
-            if let Some(inbounds_array) = inbounds.as_array_mut() {
-                // Создаем новый массив без API_INBOUND и с фильтрацией по INBOUNDS
-                let filtered_inbounds: Vec<Value> = inbounds_array
-                    .iter()
-                    .filter(|inbound| {
-                        // Удаляем API_INBOUND (как в Python)
-                        if let Some(obj) = inbound.as_object() {
-                            if obj.get("protocol").and_then(|v| v.as_str()) == Some("dokodemo-door") &&
-                               obj.get("tag").and_then(|v| v.as_str()) == Some("API_INBOUND") {
-                                return false;
-                            }
-                            
-                            // Фильтруем по INBOUNDS если указано (как в Python)
-                            if !self.inbounds_filter.is_empty() {
-                                if let Some(tag) = obj.get("tag").and_then(|v| v.as_str()) {
-                                    return self.inbounds_filter.contains(&tag.to_string());
-                                }
-                            }
-                        }
-                        true
-                    })
-                    .cloned()
-                    .collect();
-                
-                *inbounds_array = filtered_inbounds;
-            }
+        let mut dropped = Vec::new();
+        let mut panel_inbounds = Vec::new();
+        if let Some(ref mut inbounds) = model.inbounds {
+            let mut panel_index = 0;
+            inbounds.retain(|inbound| {
+                let keep = self.keep_inbound(inbound, &mut dropped);
+                if keep {
+                    panel_inbounds.push(Some(panel_index));
+                }
+                panel_index += 1;
+                keep
+            });
         }
         
-                // Получаем API tag для фильтрации (до мутабельных операций)
-        let api_tag = config_obj.get("api")
-            .and_then(|api| api.as_object())
-            .and_then(|api_obj| api_obj.get("tag"))
-            .and_then(|tag| tag.as_str())
-            .map(|s| s.to_string());
+        // Inbounds панели получают порты, адреса и сертификаты этой ноды
+        if let (Some(rewrites), Some(inbounds)) = (&self.rewrites, model.inbounds.as_mut()) {
+            rewrites.apply(inbounds);
+        }
         
         // Удаляем существующие API routing rules (как в Python)
-        if let Some(routing) = config_obj.get_mut("routing") {
-            if let Some(routing_obj) = routing.as_object_mut() {
-                if let Some(rules) = routing_obj.get_mut("rules") {
-                    if let Some(rules_array) = rules.as_array_mut() {
-                        if let Some(ref api_tag) = api_tag {
-                            rules_array.retain(|rule| {
-                                if let Some(rule_obj) = rule.as_object() {
-                                    if let Some(outbound_tag) = rule_obj.get("outboundTag").and_then(|v| v.as_str()) {
-                                        return outbound_tag != api_tag;
-                                    }
-                                }
-                                true
-                            });
-                        }
-                    }
+        let api_tag = model.api.as_ref().and_then(|api| api.tag.clone());
+        let mut panel_rules = Vec::new();
+        if let Some(rules) = model.routing.as_mut().and_then(|routing| routing.rules.as_mut()) {
+            let mut panel_index = 0;
+            rules.retain(|rule| {
+                let keep = api_tag.is_none() || rule.outbound_tag != api_tag;
+                if keep {
+                    panel_rules.push(Some(panel_index));
                 }
-            }
+                panel_index += 1;
+                keep
+            });
         }
         
         // Добавляем API конфигурацию (идентично Python версии)
-        config_obj.insert("api".to_string(), json!({
-            "services": [
-                "HandlerService",
-                "StatsService", 
-                "LoggerService"
-            ],
-            "tag": "API"
-        }));
-        
-        config_obj.insert("stats".to_string(), json!({}));
-        
-        // Создаем API inbound (идентично Python версии)
-        let api_inbound = json!({
-            "listen": self.api_host,
-            "port": self.api_port,
-            "protocol": "dokodemo-door",
-            "settings": {
-                "address": "127.0.0.1"
-            },
-            "streamSettings": {
+        model.api = Some(ApiSection {
+            tag: Some("API".to_string()),
+            services: Some(vec![
+                "HandlerService".to_string(),
+                "StatsService".to_string(),
+                "LoggerService".to_string(),
+            ]),
+            extra: Map::new(),
+        });
+        model.stats = Some(Map::new());
+        
+        // API inbound и правило маршрутизации идут первыми (как в Python)
+        let mut node_inbounds = vec![Inbound {
+            tag: Some("API_INBOUND".to_string()),
+            listen: Some(self.api_host.clone()),
+            port: Some(Port::Number(self.api_port)),
+            protocol: Some("dokodemo-door".to_string()),
+            settings: Some(object(json!({"address": "127.0.0.1"}))),
+            stream_settings: Some(object(json!({
                 "security": "tls",
                 "tlsSettings": {
                     "certificates": [
@@ -155,45 +258,122 @@ impl XrayConfig {
                         }
                     ]
                 }
-            },
-            "tag": "API_INBOUND"
-        });
+            }))),
+            extra: Map::new(),
+        }];
+        let mut node_rules = vec![RoutingRule {
+            rule_type: Some("field".to_string()),
+            inbound_tag: Some(StringList::Many(vec!["API_INBOUND".to_string()])),
+            outbound_tag: Some("API".to_string()),
+            balancer_tag: None,
+            extra: object(json!({"source": ["127.0.0.1", self.peer_ip]})),
+        }];
         
-        // Добавляем API inbound в начало массива (как в Python)
-        if let Some(inbounds) = config_obj.get_mut("inbounds") {
-            if let Some(inbounds_array) = inbounds.as_array_mut() {
-                inbounds_array.insert(0, api_inbound);
-            }
-        } else {
-            config_obj.insert("inbounds".to_string(), json!([api_inbound]));
+        // Локальный API для инкрементального применения конфигурации: только loopback,
+        // без TLS, потому что `xray api` подключается без шифрования
+        if self.control_api_port != 0 {
+            node_inbounds.push(Inbound {
+                tag: Some(CONTROL_API_INBOUND_TAG.to_string()),
+                listen: Some("127.0.0.1".to_string()),
+                port: Some(Port::Number(self.control_api_port)),
+                protocol: Some("dokodemo-door".to_string()),
+                settings: Some(object(json!({"address": "127.0.0.1"}))),
+                ..Inbound::default()
+            });
+            node_rules.push(RoutingRule {
+                rule_type: Some("field".to_string()),
+                inbound_tag: Some(StringList::Many(vec![CONTROL_API_INBOUND_TAG.to_string()])),
+                outbound_tag: Some("API".to_string()),
+                ..RoutingRule::default()
+            });
         }
         
-        // Создаем routing rule для API (идентично Python версии)
-        let api_rule = json!({
-            "inboundTag": ["API_INBOUND"],
-            "source": ["127.0.0.1", self.peer_ip],
-            "outboundTag": "API",
-            "type": "field"
-        });
-        
-        // Добавляем rule в начало массива (как в Python)
-        if let Some(routing) = config_obj.get_mut("routing") {
-            if let Some(routing_obj) = routing.as_object_mut() {
-                if let Some(rules) = routing_obj.get_mut("rules") {
-                    if let Some(rules_array) = rules.as_array_mut() {
-                        rules_array.insert(0, api_rule);
-                    }
-                } else {
-                    routing_obj.insert("rules".to_string(), json!([api_rule]));
-                }
+        panel_inbounds.splice(0..0, vec![None; node_inbounds.len()]);
+        panel_rules.splice(0..0, vec![None; node_rules.len()]);
+        let inbounds = model.inbounds.get_or_insert_with(Vec::new);
+        inbounds.splice(0..0, node_inbounds);
+        let rules = model.routing.get_or_insert_with(Routing::default).rules.get_or_insert_with(Vec::new);
+        rules.splice(0..0, node_rules);
+        self.dropped_inbounds = dropped;
+        self.panel_inbounds = panel_inbounds;
+        self.panel_rules = panel_rules;
+    }
+    
+    /// Остается ли inbound панели: API inbounds панели заменяются inbounds ноды,
+    /// остальные проверяет фильтр INBOUNDS
+    fn keep_inbound(&self, inbound: &Inbound, dropped: &mut Vec<DroppedInbound>) -> bool {
+        let tag = inbound.tag.as_deref();
+        if inbound.protocol.as_deref() == Some("dokodemo-door")
+            && matches!(tag, Some("API_INBOUND" | CONTROL_API_INBOUND_TAG))
+        {
+            return false;
+        }
+        // Inbounds без тега фильтр не затрагивает (как в Python)
+        let Some(tag) = tag else {
+            return true;
+        };
+        match self.inbounds_filter.check(tag, inbound) {
+            Some(reason) => {
+                tracing::debug!("Inbound {} dropped: {}", tag, reason);
+                dropped.push(DroppedInbound {
+                    tag: tag.to_string(),
+                    reason,
+                });
+                false
             }
-        } else {
-            config_obj.insert("routing".to_string(), json!({
-                "rules": [api_rule]
-            }));
+            None => true,
         }
-        
-        Ok(())
+    }
+}
+
+/// Проверяет, что патчи оставили секцию api, первые node_inbounds inbounds (по тегу)
+/// и первые node_rules правил маршрутизации такими, какими их добавила нода
+fn check_node_owned(before: &Value, after: &Value, node_inbounds: usize, node_rules: usize) -> Vec<ConfigIssue> {
+    let mut issues = Vec::new();
+    let issue = |path: String, reason: String| ConfigIssue {
+        path,
+        reason: format!("{} (after overrides)", reason),
+    };
+    if before.get("api") != after.get("api") {
+        issues.push(issue("api".to_string(), "the node's API section must not be changed".to_string()));
+    }
+
+    fn array<'a>(config: &'a Value, pointer: &str) -> &'a [Value] {
+        config.pointer(pointer).and_then(Value::as_array).map_or(&[], Vec::as_slice)
+    }
+    let after_inbounds = array(after, "/inbounds");
+    for inbound in array(before, "/inbounds").iter().take(node_inbounds) {
+        let tag = inbound.get("tag").and_then(Value::as_str).unwrap_or_default();
+        match after_inbounds.iter().position(|other| other.get("tag").and_then(Value::as_str) == Some(tag)) {
+            Some(index) if after_inbounds[index] != *inbound => issues.push(issue(
+                format!("inbounds[{}]", index),
+                format!("the node's inbound '{}' must not be changed", tag),
+            )),
+            Some(_) => {}
+            None => issues.push(issue(
+                "inbounds".to_string(),
+                format!("the node's inbound '{}' must not be removed", tag),
+            )),
+        }
+    }
+
+    let after_rules = array(after, "/routing/rules");
+    for (index, rule) in array(before, "/routing/rules").iter().take(node_rules).enumerate() {
+        if after_rules.get(index) != Some(rule) {
+            issues.push(issue(
+                format!("routing.rules[{}]", index),
+                format!("the node's routing rule for {} must stay first and unchanged", rule["inboundTag"]),
+            ));
+        }
+    }
+    issues
+}
+
+/// Объект из литерала json!
+fn object(value: Value) -> Map<String, Value> {
+    match value {
+        Value::Object(map) => map,
+        _ => Map::new(),
     }
 }
 
@@ -201,20 +381,77 @@ impl XrayConfig {
 #[derive(Debug)]
 pub enum XrayConfigError {
     JsonParseError(String),
-    InvalidConfig(String),
+    /// Ошибки в известных секциях с путями
+    Validation(Vec<ConfigIssue>),
 }
 
 impl fmt::Display for XrayConfigError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             XrayConfigError::JsonParseError(msg) => write!(f, "JSON parse error: {}", msg),
-            XrayConfigError::InvalidConfig(msg) => write!(f, "Invalid config: {}", msg),
+            XrayConfigError::Validation(issues) => {
+                let issues: Vec<String> = issues.iter().map(ConfigIssue::to_string).collect();
+                write!(f, "Invalid config: {}", issues.join("; "))
+            }
         }
     }
 }
 
 impl std::error::Error for XrayConfigError {}
 
+/// Поля с UUID пользователей, паролями и приватными ключами
+const SECRET_FIELDS: &[&str] = &[
+    "id", "password", "pass", "privateKey", "secretKey", "preSharedKey", "key", "seed",
+];
+
+/// Заменяет значения секретных полей на "***" на любой глубине
+fn redact_secrets(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if SECRET_FIELDS.contains(&key.as_str()) && !value.is_null() {
+                    *value = Value::String("***".to_string());
+                } else {
+                    redact_secrets(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_secrets),
+        _ => {}
+    }
+}
+
+/// Пишет JSON с ключами объектов в порядке сортировки, независимо от порядка в Map
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            out.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(value, out);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -234,6 +471,7 @@ mod tests {
             debug: false,
             service_protocol: "rest".to_string(),
             inbounds: vec![],
+            ..AppConfig::default()
         }
     }
     
@@ -297,6 +535,70 @@ mod tests {
         
         let user_inbound = &inbounds[1]; // API_INBOUND всегда первый
         assert_eq!(user_inbound.get("tag").unwrap().as_str().unwrap(), "vmess-in");
+        assert_eq!(xray_config.dropped_inbounds(), &[DroppedInbound {
+            tag: "trojan-in".to_string(),
+            reason: "not matched by INBOUNDS".to_string(),
+        }]);
+    }
+    
+    #[test]
+    fn test_inbound_rewrites() {
+        let dir = tempfile::tempdir().unwrap();
+        let rewrites = dir.path().join("rewrites.json");
+        std::fs::write(&rewrites, r#"{
+            "inbounds": {"vless-in": {"port": 8443, "listen": "::"}},
+            "certificates": {"/certs/": "/etc/ssl/node/"}
+        }"#).unwrap();
+        let mut app_config = create_test_app_config();
+        app_config.ssl_cert_file = "/certs/node.pem".to_string();
+        app_config.inbound_rewrites_file = Some(rewrites.to_string_lossy().into_owned());
+        
+        let config_json = r#"{"inbounds": [
+            {"tag": "vless-in", "port": 443, "protocol": "vless",
+             "streamSettings": {"tlsSettings": {"certificates": [{"certificateFile": "/certs/a.pem", "keyFile": "/certs/a.key"}]}}},
+            {"tag": "trojan-in", "port": 443, "listen": "0.0.0.0", "protocol": "trojan"}
+        ]}"#;
+        let xray_config = XrayConfig::new(config_json, "192.168.1.1".to_string(), &app_config).unwrap();
+        let inbounds = xray_config.as_value()["inbounds"].as_array().unwrap();
+        assert_eq!(inbounds[1]["port"], 8443);
+        assert_eq!(inbounds[1]["listen"], "::");
+        let certificate = &inbounds[1]["streamSettings"]["tlsSettings"]["certificates"][0];
+        assert_eq!(certificate["certificateFile"], "/etc/ssl/node/a.pem");
+        assert_eq!(certificate["keyFile"], "/etc/ssl/node/a.key");
+        assert_eq!(inbounds[2]["port"], 443);
+        // Сертификат API ноды не перезаписывается
+        assert_eq!(inbounds[0]["streamSettings"]["tlsSettings"]["certificates"][0]["certificateFile"], "/certs/node.pem");
+        
+        std::fs::write(&rewrites, "{").unwrap();
+        let Err(XrayConfigError::Validation(issues)) = XrayConfig::new(config_json, "192.168.1.1".to_string(), &app_config) else {
+            panic!("broken rewrite table must be reported");
+        };
+        assert_eq!(issues[0].path, "INBOUND_REWRITES_FILE");
+    }
+    
+    #[test]
+    fn test_placeholders_resolved_before_validation() {
+        let mut app_config = create_test_app_config();
+        app_config.node_public_ip = Some("203.0.113.7".to_string());
+        let config_json = r#"{"inbounds": [{"tag": "vless-in", "listen": "${NODE_PUBLIC_IP}", "port": 443}]}"#;
+        let xray_config = XrayConfig::new(config_json, "192.168.1.1".to_string(), &app_config).unwrap();
+        assert_eq!(xray_config.as_value()["inbounds"][1]["listen"], "203.0.113.7");
+        
+        let config_json = r#"{"inbounds": [{"tag": "vless-in", "listen": "${NODE_PRIVATE_IP}"}]}"#;
+        let Err(XrayConfigError::Validation(issues)) = XrayConfig::new(config_json, "192.168.1.1".to_string(), &app_config) else {
+            panic!("unresolved placeholder must be reported");
+        };
+        assert_eq!(issues[0].to_string(), "inbounds[0].listen: ${NODE_PRIVATE_IP}: unknown variable");
+    }
+    
+    #[test]
+    fn test_invalid_inbounds_filter() {
+        let mut app_config = create_test_app_config();
+        app_config.inbounds = vec!["re:[".to_string()];
+        let Err(XrayConfigError::Validation(issues)) = XrayConfig::new("{}", "192.168.1.1".to_string(), &app_config) else {
+            panic!("invalid INBOUNDS must be reported");
+        };
+        assert_eq!(issues[0].path, "INBOUNDS");
     }
     
     #[test]
@@ -312,4 +614,129 @@ mod tests {
         let parsed: Value = serde_json::from_str(&json_output).unwrap();
         assert!(parsed.is_object());
     }
-} 
\ No newline at end of file
+    
+    #[test]
+    fn test_canonical_hash() {
+        let app_config = create_test_app_config();
+        let config = |json: &str| XrayConfig::new(json, "192.168.1.1".to_string(), &app_config).unwrap();
+        
+        // Порядок ключей и пробелы не влияют на хеш, значения и порядок массивов - влияют
+        let a = config(r#"{"inbounds": [], "outbounds": [{"tag": "direct", "protocol": "freedom"}]}"#);
+        let b = config(r#"{"outbounds":[{"protocol":"freedom","tag":"direct"}],"inbounds":[]}"#);
+        let c = config(r#"{"inbounds": [], "outbounds": [{"tag": "block", "protocol": "blackhole"}]}"#);
+        assert_eq!(a.canonical_hash(), b.canonical_hash());
+        assert_ne!(a.canonical_hash(), c.canonical_hash());
+        assert_eq!(a.canonical_hash().len(), 64);
+    }
+    
+    #[test]
+    fn test_malformed_sections_are_rejected() {
+        let config_json = r#"{"inbounds": {"tag": "vless-in"}, "routing": {"rules": [{"outboundTag": 1}]}}"#;
+        let Err(XrayConfigError::Validation(issues)) = XrayConfig::new(config_json, "192.168.1.1".to_string(), &create_test_app_config()) else {
+            panic!("malformed sections must be reported");
+        };
+        let paths: Vec<&str> = issues.iter().map(|issue| issue.path.as_str()).collect();
+        assert_eq!(paths, vec!["routing.rules[0].outboundTag", "inbounds"]);
+    }
+    
+    #[test]
+    fn test_redacted() {
+        let config_json = r#"{"inbounds": [{"tag": "vless-in", "protocol": "vless",
+            "settings": {"clients": [{"id": "5f2c", "email": "a@example.com"}]},
+            "streamSettings": {"realitySettings": {"privateKey": "secret", "shortIds": [""]}}}]}"#;
+        let xray_config = XrayConfig::new(config_json, "192.168.1.1".to_string(), &create_test_app_config()).unwrap();
+        
+        let redacted = xray_config.redacted();
+        let inbound = &redacted["inbounds"][1];
+        assert_eq!(inbound["settings"]["clients"][0]["id"], "***");
+        assert_eq!(inbound["settings"]["clients"][0]["email"], "a@example.com");
+        assert_eq!(inbound["streamSettings"]["realitySettings"]["privateKey"], "***");
+        // Исходная конфигурация не меняется
+        assert_eq!(xray_config.as_object().unwrap()["inbounds"][1]["settings"]["clients"][0]["id"], "5f2c");
+    }
+    
+    #[test]
+    fn test_control_api_inbound() {
+        let mut app_config = create_test_app_config();
+        app_config.xray_control_api_port = 62052;
+        let config_json = r#"{"inbounds": [{"protocol": "vless", "tag": "vless-in"}]}"#;
+        let xray_config = XrayConfig::new(config_json, "192.168.1.1".to_string(), &app_config).unwrap();
+        
+        let config_obj = xray_config.as_object().unwrap();
+        let inbounds = config_obj["inbounds"].as_array().unwrap();
+        assert_eq!(inbounds.len(), 3);
+        assert_eq!(inbounds[1]["tag"], CONTROL_API_INBOUND_TAG);
+        assert_eq!(inbounds[1]["listen"], "127.0.0.1");
+        assert!(inbounds[1].get("streamSettings").is_none());
+        assert_eq!(config_obj["routing"]["rules"][1]["inboundTag"][0], CONTROL_API_INBOUND_TAG);
+        
+        // Повторная обработка не дублирует inbound
+        let again = XrayConfig::new(&xray_config.to_json(), "192.168.1.1".to_string(), &app_config).unwrap();
+        assert_eq!(again.as_object().unwrap()["inbounds"].as_array().unwrap().len(), 3);
+    }
+    
+    #[test]
+    fn test_overrides_applied_after_node_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("10-log.json"), r#"{"log": {"loglevel": "error"}}"#).unwrap();
+        std::fs::write(
+            dir.path().join("20-outbounds.json"),
+            r#"[{"op": "add", "path": "/outbounds", "value": [{"tag": "direct", "protocol": "freedom"}]}]"#,
+        ).unwrap();
+        let mut app_config = create_test_app_config();
+        app_config.config_overrides_dir = Some(dir.path().to_string_lossy().into_owned());
+        
+        let config_json = r#"{"log": {"loglevel": "warning"}, "inbounds": [{"protocol": "vless", "tag": "vless-in"}]}"#;
+        let xray_config = XrayConfig::new(config_json, "192.168.1.1".to_string(), &app_config).unwrap();
+        let config_obj = xray_config.as_object().unwrap();
+        assert_eq!(config_obj["log"]["loglevel"], "error");
+        assert_eq!(config_obj["outbounds"][0]["tag"], "direct");
+        assert_eq!(xray_config.overrides().unwrap().applied, vec!["10-log.json", "20-outbounds.json"]);
+        
+        // Патч, ломающий структуру, отклоняет конфигурацию
+        std::fs::write(dir.path().join("30-broken.json"), r#"{"inbounds": {"tag": "x"}}"#).unwrap();
+        let Err(XrayConfigError::Validation(issues)) = XrayConfig::new(config_json, "192.168.1.1".to_string(), &app_config) else {
+            panic!("broken override must be reported");
+        };
+        assert_eq!(issues[0].path, "inbounds");
+        assert!(issues[0].reason.ends_with("(after overrides)"), "{}", issues[0]);
+    }
+    
+    #[test]
+    fn test_overrides_cannot_change_node_inbounds() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app_config = create_test_app_config();
+        app_config.xray_control_api_port = 62052;
+        app_config.config_overrides_dir = Some(dir.path().to_string_lossy().into_owned());
+        let config_json = r#"{"inbounds": [{"protocol": "vless", "tag": "vless-in"}]}"#;
+        let rejected = |patch: &str| {
+            std::fs::write(dir.path().join("10-patch.json"), patch).unwrap();
+            match XrayConfig::new(config_json, "192.168.1.1".to_string(), &app_config) {
+                Err(XrayConfigError::Validation(issues)) => issues.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                other => panic!("override of node inbounds must be rejected: {:?}", other.map(|config| config.to_json())),
+            }
+        };
+        
+        assert_eq!(rejected(r#"[{"op": "replace", "path": "/inbounds/0/listen", "value": "0.0.0.0"}]"#), vec![
+            "inbounds[0]: the node's inbound 'API_INBOUND' must not be changed (after overrides)",
+        ]);
+        assert_eq!(rejected(r#"[{"op": "remove", "path": "/inbounds/1"}]"#), vec![
+            "inbounds: the node's inbound 'NODE_API_INBOUND' must not be removed (after overrides)",
+        ]);
+        assert_eq!(rejected(r#"[{"op": "add", "path": "/routing/rules/0", "value": {"outboundTag": "direct"}}]"#), vec![
+            r#"routing.rules[0]: the node's routing rule for ["API_INBOUND"] must stay first and unchanged (after overrides)"#,
+            r#"routing.rules[1]: the node's routing rule for ["NODE_API_INBOUND"] must stay first and unchanged (after overrides)"#,
+        ]);
+        assert_eq!(rejected(r#"{"api": {"tag": "other"}}"#), vec![
+            "api: the node's API section must not be changed (after overrides)",
+        ]);
+        
+        // Новые inbounds и правила после правил ноды допустимы
+        std::fs::write(dir.path().join("10-patch.json"), r#"[
+            {"op": "add", "path": "/inbounds/0", "value": {"tag": "local-socks", "protocol": "socks"}},
+            {"op": "add", "path": "/routing/rules/-", "value": {"outboundTag": "direct"}}
+        ]"#).unwrap();
+        let xray_config = XrayConfig::new(config_json, "192.168.1.1".to_string(), &app_config).unwrap();
+        assert_eq!(xray_config.as_value()["inbounds"][0]["tag"], "local-socks");
+    }
+}
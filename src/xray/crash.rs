@@ -0,0 +1,171 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+
+/// Запись о неожиданном завершении Xray
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashRecord {
+    pub id: String,
+    pub crashed_at: DateTime<Utc>,
+    /// Последняя строка лога или описание завершения
+    pub reason: String,
+    pub pid: u32,
+    pub exit_code: Option<i32>,
+    pub exit_signal: Option<i32>,
+    /// Время работы процесса до падения
+    pub uptime_secs: Option<u64>,
+    /// sha256 конфигурации, с которой работал процесс
+    pub config_hash: Option<String>,
+    pub xray_version: Option<String>,
+    pub node_version: String,
+    /// Последние строки лога этого запуска
+    pub log_tail: Vec<String>,
+}
+
+/// Краткая запись для списка
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CrashSummary {
+    pub id: String,
+    pub crashed_at: DateTime<Utc>,
+    pub reason: String,
+    pub exit_code: Option<i32>,
+    pub exit_signal: Option<i32>,
+    pub uptime_secs: Option<u64>,
+}
+
+impl From<&CrashRecord> for CrashSummary {
+    fn from(record: &CrashRecord) -> Self {
+        Self {
+            id: record.id.clone(),
+            crashed_at: record.crashed_at,
+            reason: record.reason.clone(),
+            exit_code: record.exit_code,
+            exit_signal: record.exit_signal,
+            uptime_secs: record.uptime_secs,
+        }
+    }
+}
+
+/// Записи о падениях в STATE_DIR/crashes, по файлу на падение
+#[derive(Debug)]
+pub struct CrashStore {
+    dir: PathBuf,
+    /// Сколько последних записей хранить
+    max_records: usize,
+}
+
+impl CrashStore {
+    pub fn new(state_dir: &Path, max_records: usize) -> Self {
+        Self {
+            dir: state_dir.join("crashes"),
+            max_records,
+        }
+    }
+
+    /// ID новой записи: время падения и PID
+    pub fn new_id(crashed_at: DateTime<Utc>, pid: u32) -> String {
+        format!("{}-{}", crashed_at.format("%Y%m%dT%H%M%S%.3fZ"), pid)
+    }
+
+    /// Сохраняет запись и удаляет самые старые сверх лимита
+    pub fn save(&self, record: &CrashRecord) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let tmp = self.dir.join(format!("{}.json.tmp", record.id));
+        fs::write(&tmp, serde_json::to_vec_pretty(record)?)?;
+        fs::rename(tmp, self.path(&record.id))?;
+
+        let mut records = self.list();
+        if records.len() > self.max_records {
+            for old in records.split_off(self.max_records) {
+                if let Err(e) = fs::remove_file(self.path(&old.id)) {
+                    warn!("Failed to remove old crash record {}: {}", old.id, e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Записи от новых к старым; нечитаемые файлы пропускаются
+    pub fn list(&self) -> Vec<CrashSummary> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut records: Vec<CrashSummary> = entries
+            .flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|entry| read_record(&entry.path()))
+            .map(|record| CrashSummary::from(&record))
+            .collect();
+        records.sort_by_key(|record| std::cmp::Reverse(record.crashed_at));
+        records
+    }
+
+    pub fn get(&self, id: &str) -> Option<CrashRecord> {
+        // ID попадает в путь, поэтому разрешены только символы, которые генерирует new_id
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.')) {
+            return None;
+        }
+        read_record(&self.path(id))
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+}
+
+fn read_record(path: &Path) -> Option<CrashRecord> {
+    serde_json::from_slice(&fs::read(path).ok()?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(crashed_at: DateTime<Utc>, pid: u32) -> CrashRecord {
+        CrashRecord {
+            id: CrashStore::new_id(crashed_at, pid),
+            crashed_at,
+            reason: "panic: runtime error".to_string(),
+            pid,
+            exit_code: Some(2),
+            exit_signal: None,
+            uptime_secs: Some(3600),
+            config_hash: None,
+            xray_version: Some("1.8.4".to_string()),
+            node_version: "0.1.0".to_string(),
+            log_tail: vec!["panic: runtime error".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_save_list_and_prune() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = CrashStore::new(dir.path(), 2);
+        let now = Utc::now();
+        for (offset, pid) in [(3, 100), (2, 101), (1, 102)] {
+            store.save(&record(now - chrono::Duration::hours(offset), pid)).unwrap();
+        }
+
+        // Самая старая запись удалена, новые идут первыми
+        let list = store.list();
+        assert_eq!(list.len(), 2);
+        assert!(list[0].id.ends_with("-102"));
+        assert!(list[1].id.ends_with("-101"));
+
+        let full = store.get(&list[0].id).unwrap();
+        assert_eq!(full.log_tail, vec!["panic: runtime error".to_string()]);
+    }
+
+    #[test]
+    fn test_get_rejects_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = CrashStore::new(dir.path(), 10);
+        assert!(store.get("../xray.pid").is_none());
+        assert!(store.get("").is_none());
+        assert!(store.list().is_empty());
+    }
+}
@@ -0,0 +1,254 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::xray::config::{XrayConfig, CONTROL_API_INBOUND_TAG};
+
+/// Протоколы, пользователей которых HandlerService умеет добавлять и удалять по email
+const USER_PROTOCOLS: &[&str] = &["vmess", "vless", "trojan", "shadowsocks"];
+
+/// Изменения inbounds, которые применяются к работающему Xray через HandlerService
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InboundChanges {
+    /// Теги inbounds для RemoveInbound (удаленные и замененные)
+    pub removed_inbounds: Vec<String>,
+    /// Inbounds для AddInbound (новые и замененные)
+    pub added_inbounds: Vec<Value>,
+    /// Пользователи для RemoveUser: тег inbound и email
+    pub removed_users: Vec<(String, String)>,
+    /// Пользователи для AddUser: inbound, в settings.clients которого только новые клиенты
+    pub added_users: Vec<Value>,
+}
+
+impl InboundChanges {
+    pub fn summary(&self) -> ChangeSummary {
+        ChangeSummary {
+            inbounds_removed: self.removed_inbounds.len(),
+            inbounds_added: self.added_inbounds.len(),
+            users_removed: self.removed_users.len(),
+            users_added: self
+                .added_users
+                .iter()
+                .map(|inbound| clients(inbound).map_or(0, Vec::len))
+                .sum(),
+            restart_reason: None,
+        }
+    }
+}
+
+/// Сводка изменений для ответа панели
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, serde::Deserialize)]
+pub struct ChangeSummary {
+    pub inbounds_removed: usize,
+    pub inbounds_added: usize,
+    pub users_removed: usize,
+    pub users_added: usize,
+    /// Почему понадобился полный перезапуск
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restart_reason: Option<String>,
+}
+
+impl ChangeSummary {
+    pub fn restart(reason: String) -> Self {
+        Self {
+            restart_reason: Some(reason),
+            ..Self::default()
+        }
+    }
+}
+
+/// Как применить новую конфигурацию к работающему Xray
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApplyPlan {
+    Unchanged,
+    Incremental(InboundChanges),
+    /// Изменение, которое требует перезапуска, с причиной
+    Restart(String),
+}
+
+/// Сравнивает конфигурацию работающего Xray с новой
+pub fn plan(running: &XrayConfig, new: &XrayConfig) -> ApplyPlan {
+    let (Some(running), Some(new)) = (running.as_object(), new.as_object()) else {
+        return ApplyPlan::Restart("config is not an object".to_string());
+    };
+
+    // Все, кроме inbounds, HandlerService менять не умеет
+    let sections: BTreeSet<&String> = running.keys().chain(new.keys()).collect();
+    for section in sections {
+        if section != "inbounds" && running.get(section) != new.get(section) {
+            return ApplyPlan::Restart(format!("'{}' changed", section));
+        }
+    }
+
+    let running_inbounds = match inbounds_by_tag(running) {
+        Ok(inbounds) => inbounds,
+        Err(reason) => return ApplyPlan::Restart(reason),
+    };
+    let new_inbounds = match inbounds_by_tag(new) {
+        Ok(inbounds) => inbounds,
+        Err(reason) => return ApplyPlan::Restart(reason),
+    };
+
+    let mut changes = InboundChanges::default();
+    for (tag, old) in &running_inbounds {
+        match new_inbounds.get(tag) {
+            None => changes.removed_inbounds.push(tag.to_string()),
+            Some(new) if old == new => {}
+            Some(_) if is_node_inbound(tag) => {
+                return ApplyPlan::Restart(format!("inbound '{}' changed", tag));
+            }
+            Some(new) => {
+                if !diff_users(tag, old, new, &mut changes) {
+                    // Изменились настройки самого inbound: заменяем его целиком
+                    changes.removed_inbounds.push(tag.to_string());
+                    changes.added_inbounds.push((*new).clone());
+                }
+            }
+        }
+    }
+    for (tag, new) in &new_inbounds {
+        if !running_inbounds.contains_key(tag) {
+            changes.added_inbounds.push((*new).clone());
+        }
+    }
+
+    if changes == InboundChanges::default() {
+        ApplyPlan::Unchanged
+    } else {
+        ApplyPlan::Incremental(changes)
+    }
+}
+
+/// Inbounds по тегу; без уникальных тегов inbound нельзя адресовать через API
+fn inbounds_by_tag(config: &Map<String, Value>) -> Result<BTreeMap<&str, &Value>, String> {
+    let mut inbounds = BTreeMap::new();
+    for inbound in config.get("inbounds").and_then(Value::as_array).into_iter().flatten() {
+        let tag = inbound
+            .get("tag")
+            .and_then(Value::as_str)
+            .ok_or_else(|| "an inbound has no tag".to_string())?;
+        if inbounds.insert(tag, inbound).is_some() {
+            return Err(format!("inbound tag '{}' is not unique", tag));
+        }
+    }
+    Ok(inbounds)
+}
+
+fn is_node_inbound(tag: &str) -> bool {
+    tag == "API_INBOUND" || tag == CONTROL_API_INBOUND_TAG
+}
+
+fn clients(inbound: &Value) -> Option<&Vec<Value>> {
+    inbound.get("settings")?.get("clients")?.as_array()
+}
+
+/// Inbound без списка клиентов
+fn without_clients(inbound: &Value) -> Value {
+    let mut inbound = inbound.clone();
+    if let Some(settings) = inbound.get_mut("settings").and_then(Value::as_object_mut) {
+        settings.remove("clients");
+    }
+    inbound
+}
+
+/// Добавляет изменения пользователей inbound; false, если изменилось что-то кроме клиентов
+/// или клиентов нельзя различить по email
+fn diff_users(tag: &str, old: &Value, new: &Value, changes: &mut InboundChanges) -> bool {
+    let protocol = new.get("protocol").and_then(Value::as_str).unwrap_or_default();
+    if !USER_PROTOCOLS.contains(&protocol) || without_clients(old) != without_clients(new) {
+        return false;
+    }
+    let (Some(old_clients), Some(new_clients)) = (clients_by_email(old), clients_by_email(new)) else {
+        return false;
+    };
+
+    for (email, client) in &old_clients {
+        if new_clients.get(email) != Some(client) {
+            changes.removed_users.push((tag.to_string(), email.to_string()));
+        }
+    }
+    let added: Vec<Value> = new_clients
+        .iter()
+        .filter(|(email, client)| old_clients.get(*email) != Some(*client))
+        .map(|(_, client)| (*client).clone())
+        .collect();
+    if !added.is_empty() {
+        let mut inbound = new.clone();
+        inbound["settings"]["clients"] = Value::Array(added);
+        changes.added_users.push(inbound);
+    }
+    true
+}
+
+/// Клиенты inbound по email; None, если у кого-то нет email или email повторяется
+fn clients_by_email(inbound: &Value) -> Option<BTreeMap<&str, &Value>> {
+    let mut by_email = BTreeMap::new();
+    for client in clients(inbound).into_iter().flatten() {
+        let email = client.get("email").and_then(Value::as_str)?;
+        if by_email.insert(email, client).is_some() {
+            return None;
+        }
+    }
+    Some(by_email)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config as AppConfig;
+    use serde_json::json;
+
+    fn config(inbounds: Value, log_level: &str) -> XrayConfig {
+        let json = json!({"log": {"loglevel": log_level}, "inbounds": inbounds}).to_string();
+        XrayConfig::new(&json, "127.0.0.1".to_string(), &AppConfig::default()).unwrap()
+    }
+
+    fn vless(tag: &str, port: u16, emails: &[&str]) -> Value {
+        let clients: Vec<Value> = emails
+            .iter()
+            .map(|email| json!({"id": format!("{}-uuid", email), "email": email}))
+            .collect();
+        json!({"tag": tag, "port": port, "protocol": "vless", "settings": {"clients": clients, "decryption": "none"}})
+    }
+
+    #[test]
+    fn test_user_changes_are_incremental() {
+        let running = config(json!([vless("vless-in", 443, &["a", "b"])]), "warning");
+        let new = config(json!([vless("vless-in", 443, &["b", "c"])]), "warning");
+
+        let ApplyPlan::Incremental(changes) = plan(&running, &new) else {
+            panic!("only users changed");
+        };
+        assert_eq!(changes.removed_users, vec![("vless-in".to_string(), "a".to_string())]);
+        assert_eq!(clients(&changes.added_users[0]).unwrap().len(), 1);
+        assert_eq!(changes.added_users[0]["settings"]["clients"][0]["email"], "c");
+        assert!(changes.removed_inbounds.is_empty() && changes.added_inbounds.is_empty());
+        assert_eq!(changes.summary().users_added, 1);
+    }
+
+    #[test]
+    fn test_inbound_changes() {
+        let running = config(json!([vless("a", 443, &["x"]), vless("b", 8443, &["y"])]), "warning");
+        let new = config(json!([vless("a", 444, &["x"]), vless("c", 9443, &["z"])]), "warning");
+
+        let ApplyPlan::Incremental(changes) = plan(&running, &new) else {
+            panic!("only inbounds changed");
+        };
+        // "a" заменен из-за порта, "b" удален, "c" добавлен
+        assert_eq!(changes.removed_inbounds, vec!["a".to_string(), "b".to_string()]);
+        let added: Vec<&str> = changes.added_inbounds.iter().map(|i| i["tag"].as_str().unwrap()).collect();
+        assert_eq!(added, vec!["a", "c"]);
+    }
+
+    #[test]
+    fn test_restart_and_unchanged() {
+        let running = config(json!([vless("a", 443, &["x"])]), "warning");
+        assert_eq!(plan(&running, &running.clone()), ApplyPlan::Unchanged);
+
+        let log_changed = config(json!([vless("a", 443, &["x"])]), "info");
+        assert_eq!(plan(&running, &log_changed), ApplyPlan::Restart("'log' changed".to_string()));
+
+        let untagged = config(json!([{"port": 1080, "protocol": "socks"}]), "warning");
+        assert_eq!(plan(&running, &untagged), ApplyPlan::Restart("an inbound has no tag".to_string()));
+    }
+}
@@ -0,0 +1,153 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{collections::VecDeque, sync::Mutex};
+
+use crate::xray::config::XrayConfig;
+
+/// Конфигурация, с которой Xray успешно запустился
+#[derive(Debug, Clone)]
+pub struct ConfigRevision {
+    pub revision: u64,
+    pub config_hash: String,
+    pub applied_at: DateTime<Utc>,
+    pub config: XrayConfig,
+}
+
+/// Ревизия без конфигурации для списка
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RevisionSummary {
+    pub revision: u64,
+    pub config_hash: String,
+    pub applied_at: DateTime<Utc>,
+}
+
+impl From<&ConfigRevision> for RevisionSummary {
+    fn from(revision: &ConfigRevision) -> Self {
+        Self {
+            revision: revision.revision,
+            config_hash: revision.config_hash.clone(),
+            applied_at: revision.applied_at,
+        }
+    }
+}
+
+/// Результат автоматического возврата к предыдущей конфигурации
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RollbackReport {
+    /// Ревизия, к которой вернулась нода
+    pub revision: u64,
+    pub config_hash: String,
+    /// Запустился ли Xray с этой ревизией
+    pub restored: bool,
+    /// Причина, если не запустился
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Последние успешно примененные конфигурации, от старых к новым
+#[derive(Debug)]
+pub struct ConfigHistory {
+    revisions: Mutex<VecDeque<ConfigRevision>>,
+    next_revision: Mutex<u64>,
+    /// Сколько ревизий хранить (0 - история выключена)
+    max_revisions: usize,
+}
+
+impl ConfigHistory {
+    pub fn new(max_revisions: usize) -> Self {
+        Self {
+            revisions: Mutex::new(VecDeque::new()),
+            next_revision: Mutex::new(1),
+            max_revisions,
+        }
+    }
+
+    /// Запоминает конфигурацию, с которой Xray запустился
+    /// Повторный запуск с последней конфигурацией не создает новую ревизию
+    pub fn record(&self, config: &XrayConfig) -> Option<u64> {
+        if self.max_revisions == 0 {
+            return None;
+        }
+        let hash = config.canonical_hash();
+        let mut revisions = self.revisions.lock().unwrap();
+
+        if let Some(latest) = revisions.back_mut() {
+            if latest.config_hash == hash {
+                latest.applied_at = Utc::now();
+                return Some(latest.revision);
+            }
+        }
+
+        let mut next_revision = self.next_revision.lock().unwrap();
+        let revision = *next_revision;
+        *next_revision += 1;
+        revisions.push_back(ConfigRevision {
+            revision,
+            config_hash: hash,
+            applied_at: Utc::now(),
+            config: config.clone(),
+        });
+        while revisions.len() > self.max_revisions {
+            revisions.pop_front();
+        }
+        Some(revision)
+    }
+
+    /// Последняя успешно примененная конфигурация
+    pub fn latest(&self) -> Option<ConfigRevision> {
+        self.revisions.lock().unwrap().back().cloned()
+    }
+
+    pub fn get(&self, revision: u64) -> Option<ConfigRevision> {
+        self.revisions
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|stored| stored.revision == revision)
+            .cloned()
+    }
+
+    /// Ревизии от новых к старым
+    pub fn list(&self) -> Vec<RevisionSummary> {
+        self.revisions
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .map(RevisionSummary::from)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config as AppConfig;
+
+    fn config(tag: &str) -> XrayConfig {
+        let json = format!(r#"{{"inbounds": [], "outbounds": [{{"tag": "{}", "protocol": "freedom"}}]}}"#, tag);
+        XrayConfig::new(&json, "127.0.0.1".to_string(), &AppConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn test_record_and_prune() {
+        let history = ConfigHistory::new(2);
+        assert_eq!(history.record(&config("a")), Some(1));
+        // Тот же конфиг не создает новую ревизию
+        assert_eq!(history.record(&config("a")), Some(1));
+        assert_eq!(history.record(&config("b")), Some(2));
+        assert_eq!(history.record(&config("c")), Some(3));
+
+        let list = history.list();
+        assert_eq!(list.iter().map(|r| r.revision).collect::<Vec<_>>(), vec![3, 2]);
+        assert!(history.get(1).is_none());
+        assert_eq!(history.latest().unwrap().revision, 3);
+    }
+
+    #[test]
+    fn test_disabled_history() {
+        let history = ConfigHistory::new(0);
+        assert_eq!(history.record(&config("a")), None);
+        assert!(history.latest().is_none());
+    }
+}
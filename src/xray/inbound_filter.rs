@@ -0,0 +1,166 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::xray::model::Inbound;
+
+/// Фильтр inbounds из INBOUNDS
+///
+/// Элементы: точный тег, glob (`vless-*`, `ws-?`), регулярное выражение (`re:^trojan-\d+$`),
+/// `protocol:vless`, `transport:ws`; с префиксом `!` элемент исключает inbounds.
+/// Пустой список включений оставляет все inbounds
+#[derive(Debug, Clone, Default)]
+pub struct InboundFilter {
+    include: Vec<Selector>,
+    exclude: Vec<Selector>,
+}
+
+/// Inbound, удаленный фильтром
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DroppedInbound {
+    pub tag: String,
+    pub reason: String,
+}
+
+/// Что сравнивает селектор
+#[derive(Debug, Clone, Copy)]
+enum Field {
+    Tag,
+    Protocol,
+    /// streamSettings.network, по умолчанию tcp
+    Transport,
+}
+
+#[derive(Debug, Clone)]
+struct Selector {
+    /// Элемент INBOUNDS как его написали, для причин удаления
+    source: String,
+    field: Field,
+    pattern: Regex,
+}
+
+impl Selector {
+    fn parse(source: &str) -> Result<Self, String> {
+        let (field, value) = if let Some(value) = source.strip_prefix("protocol:") {
+            (Field::Protocol, value)
+        } else if let Some(value) = source.strip_prefix("transport:") {
+            (Field::Transport, value)
+        } else {
+            (Field::Tag, source)
+        };
+        let pattern = match value.strip_prefix("re:") {
+            Some(regex) => Regex::new(regex).map_err(|e| format!("invalid regex '{}': {}", source, e))?,
+            None if value.is_empty() => return Err(format!("empty selector '{}'", source)),
+            None => glob(value),
+        };
+        Ok(Self {
+            source: source.to_string(),
+            field,
+            pattern,
+        })
+    }
+
+    fn matches(&self, tag: &str, inbound: &Inbound) -> bool {
+        let value = match self.field {
+            Field::Tag => tag,
+            Field::Protocol => inbound.protocol.as_deref().unwrap_or_default(),
+            Field::Transport => inbound
+                .stream_settings
+                .as_ref()
+                .and_then(|settings| settings.get("network"))
+                .and_then(|network| network.as_str())
+                .unwrap_or("tcp"),
+        };
+        self.pattern.is_match(value)
+    }
+}
+
+/// Glob с `*` и `?` во всю строку; без них - точное совпадение
+fn glob(pattern: &str) -> Regex {
+    let mut regex = String::from("^");
+    for ch in pattern.chars() {
+        match ch {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            ch => regex.push_str(&regex::escape(&ch.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex).expect("escaped glob is a valid regex")
+}
+
+impl InboundFilter {
+    /// Разбирает элементы INBOUNDS
+    pub fn parse(items: &[String]) -> Result<Self, String> {
+        let mut filter = Self::default();
+        for item in items {
+            match item.strip_prefix('!') {
+                Some(excluded) => filter.exclude.push(Selector::parse(excluded)?),
+                None => filter.include.push(Selector::parse(item)?),
+            }
+        }
+        Ok(filter)
+    }
+
+    /// Причина удаления inbound или None, если он остается
+    pub fn check(&self, tag: &str, inbound: &Inbound) -> Option<String> {
+        if !self.include.is_empty() && !self.include.iter().any(|selector| selector.matches(tag, inbound)) {
+            return Some("not matched by INBOUNDS".to_string());
+        }
+        self.exclude
+            .iter()
+            .find(|selector| selector.matches(tag, inbound))
+            .map(|selector| format!("excluded by '!{}'", selector.source))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xray::model::XrayConfigModel;
+    use serde_json::json;
+
+    fn inbound(value: serde_json::Value) -> Inbound {
+        XrayConfigModel::from_value(json!({"inbounds": [value]})).unwrap().inbounds.unwrap().remove(0)
+    }
+
+    fn filter(items: &[&str]) -> InboundFilter {
+        InboundFilter::parse(&items.iter().map(ToString::to_string).collect::<Vec<_>>()).unwrap()
+    }
+
+    #[test]
+    fn test_selectors() {
+        let vless_ws = inbound(json!({"tag": "vless-ws", "protocol": "vless", "streamSettings": {"network": "ws"}}));
+        let vless_tcp = inbound(json!({"tag": "vless-tcp", "protocol": "vless"}));
+        let trojan = inbound(json!({"tag": "trojan-1", "protocol": "trojan"}));
+        let check = |filter: &InboundFilter, inbound: &Inbound| filter.check(inbound.tag.as_deref().unwrap(), inbound);
+
+        let by_glob = filter(&["vless-*", "!*-ws"]);
+        assert_eq!(check(&by_glob, &vless_ws).as_deref(), Some("excluded by '!*-ws'"));
+        assert_eq!(check(&by_glob, &vless_tcp), None);
+        assert_eq!(check(&by_glob, &trojan).as_deref(), Some("not matched by INBOUNDS"));
+
+        let by_protocol = filter(&["protocol:trojan", "transport:ws"]);
+        assert_eq!(check(&by_protocol, &vless_ws), None);
+        assert!(check(&by_protocol, &vless_tcp).is_some());
+        assert_eq!(check(&by_protocol, &trojan), None);
+
+        let by_regex = filter(&[r"re:^trojan-\d+$"]);
+        assert_eq!(check(&by_regex, &trojan), None);
+        assert!(check(&by_regex, &vless_tcp).is_some());
+
+        // Только исключения оставляют остальные inbounds
+        let excludes = filter(&["!transport:tcp"]);
+        assert_eq!(check(&excludes, &vless_ws), None);
+        assert_eq!(check(&excludes, &trojan).as_deref(), Some("excluded by '!transport:tcp'"));
+
+        // Точный тег не работает как подстрока
+        assert!(check(&filter(&["vless"]), &vless_tcp).is_some());
+    }
+
+    #[test]
+    fn test_invalid_selectors() {
+        assert!(InboundFilter::parse(&["re:(".to_string()]).unwrap_err().contains("invalid regex 're:('"));
+        assert!(InboundFilter::parse(&["!".to_string()]).is_err());
+        assert!(InboundFilter::parse(&["protocol:".to_string()]).is_err());
+    }
+}
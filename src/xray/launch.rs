@@ -0,0 +1,263 @@
+use std::{fmt, path::PathBuf, str::FromStr};
+use tokio::process::Command as TokioCommand;
+
+use crate::config::Config as AppConfig;
+
+/// Класс приоритета ввода-вывода (ioprio_set)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoClass {
+    Realtime,
+    BestEffort,
+    Idle,
+}
+
+/// Приоритет ввода-вывода процесса Xray (XRAY_IONICE)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoPriority {
+    pub class: IoClass,
+    /// Уровень 0..=7 внутри класса, для idle не используется
+    pub level: u8,
+}
+
+impl IoPriority {
+    /// Значение для ioprio_set: класс в старших битах, уровень в младших
+    fn ioprio(self) -> i32 {
+        const IOPRIO_CLASS_SHIFT: i32 = 13;
+        let class = match self.class {
+            IoClass::Realtime => 1,
+            IoClass::BestEffort => 2,
+            IoClass::Idle => 3,
+        };
+        (class << IOPRIO_CLASS_SHIFT) | i32::from(self.level)
+    }
+}
+
+/// "idle", "best-effort:4", "realtime:0"
+impl FromStr for IoPriority {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (class, level) = match value.split_once(':') {
+            Some((class, level)) => (class, Some(level)),
+            None => (value, None),
+        };
+        let class = match class.to_ascii_lowercase().as_str() {
+            "realtime" | "rt" => IoClass::Realtime,
+            "best-effort" | "be" => IoClass::BestEffort,
+            "idle" => IoClass::Idle,
+            other => {
+                return Err(format!(
+                    "unknown I/O class '{}', expected realtime, best-effort or idle",
+                    other
+                ))
+            }
+        };
+        let level = match level {
+            Some(level) => level
+                .parse::<u8>()
+                .ok()
+                .filter(|level| *level <= 7)
+                .ok_or_else(|| format!("I/O priority level must be 0..7, got '{}'", level))?,
+            None => 4,
+        };
+        Ok(Self { class, level })
+    }
+}
+
+impl fmt::Display for IoPriority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.class {
+            IoClass::Realtime => write!(f, "realtime:{}", self.level),
+            IoClass::BestEffort => write!(f, "best-effort:{}", self.level),
+            IoClass::Idle => write!(f, "idle"),
+        }
+    }
+}
+
+/// Окружение, аргументы и ограничения процесса Xray
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LaunchOptions {
+    /// Дополнительные переменные окружения
+    pub env: Vec<(String, String)>,
+    /// Аргументы после "run -config stdin:"
+    pub args: Vec<String>,
+    /// Рабочий каталог (None - каталог ноды)
+    pub working_dir: Option<PathBuf>,
+    /// RLIMIT_NOFILE
+    pub rlimit_nofile: Option<u64>,
+    /// RLIMIT_NPROC
+    pub rlimit_nproc: Option<u64>,
+    /// Приоритет планировщика (nice)
+    pub nice: Option<i32>,
+    /// Приоритет ввода-вывода
+    pub ionice: Option<IoPriority>,
+    /// Пользователь, от которого запускается Xray
+    pub uid: Option<u32>,
+    /// Группа, от которой запускается Xray
+    pub gid: Option<u32>,
+}
+
+impl From<&AppConfig> for LaunchOptions {
+    fn from(config: &AppConfig) -> Self {
+        Self {
+            // Значения проверены в Config::validate
+            env: parse_env(&config.xray_env).unwrap_or_default(),
+            args: config.xray_args.split_whitespace().map(str::to_string).collect(),
+            working_dir: config.xray_working_dir.as_ref().map(PathBuf::from),
+            rlimit_nofile: (config.xray_rlimit_nofile > 0).then_some(config.xray_rlimit_nofile),
+            rlimit_nproc: (config.xray_rlimit_nproc > 0).then_some(config.xray_rlimit_nproc),
+            nice: (config.xray_nice != 0).then_some(config.xray_nice),
+            ionice: config.xray_ionice.parse().ok(),
+            uid: config.xray_uid,
+            gid: config.xray_gid,
+        }
+    }
+}
+
+impl LaunchOptions {
+    /// Применяет настройки к команде запуска Xray
+    ///
+    /// Лимиты и приоритеты выставляются в дочернем процессе до смены пользователя:
+    /// после нее поднять лимит или понизить nice уже нельзя.
+    pub fn apply(&self, cmd: &mut TokioCommand) {
+        cmd.args(&self.args);
+        cmd.envs(self.env.iter().map(|(key, value)| (key, value)));
+        if let Some(ref dir) = self.working_dir {
+            cmd.current_dir(dir);
+        }
+
+        let LaunchOptions { rlimit_nofile, rlimit_nproc, nice, ionice, uid, gid, .. } = self.clone();
+        if rlimit_nofile.is_none()
+            && rlimit_nproc.is_none()
+            && nice.is_none()
+            && ionice.is_none()
+            && uid.is_none()
+            && gid.is_none()
+        {
+            return;
+        }
+
+        // Между fork и exec допустимы только async-signal-safe вызовы, без аллокаций
+        unsafe {
+            cmd.pre_exec(move || {
+                if let Some(limit) = rlimit_nofile {
+                    check(libc::setrlimit(libc::RLIMIT_NOFILE, &rlimit(limit)))?;
+                }
+                if let Some(limit) = rlimit_nproc {
+                    check(libc::setrlimit(libc::RLIMIT_NPROC, &rlimit(limit)))?;
+                }
+                if let Some(nice) = nice {
+                    check(libc::setpriority(libc::PRIO_PROCESS, 0, nice))?;
+                }
+                #[cfg(target_os = "linux")]
+                if let Some(ionice) = ionice {
+                    const IOPRIO_WHO_PROCESS: libc::c_long = 1;
+                    let result = libc::syscall(
+                        libc::SYS_ioprio_set,
+                        IOPRIO_WHO_PROCESS,
+                        0 as libc::c_long,
+                        ionice.ioprio() as libc::c_long,
+                    );
+                    check(result as libc::c_int)?;
+                }
+                // Группы меняются раньше пользователя, пока на это есть права
+                if let Some(gid) = gid {
+                    check(libc::setgroups(0, std::ptr::null()))?;
+                    check(libc::setgid(gid))?;
+                }
+                if let Some(uid) = uid {
+                    check(libc::setuid(uid))?;
+                }
+                Ok(())
+            });
+        }
+    }
+}
+
+/// Одинаковые мягкий и жесткий лимиты
+fn rlimit(limit: u64) -> libc::rlimit {
+    libc::rlimit {
+        rlim_cur: limit as libc::rlim_t,
+        rlim_max: limit as libc::rlim_t,
+    }
+}
+
+fn check(result: libc::c_int) -> std::io::Result<()> {
+    if result == -1 {
+        Err(std::io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Разбирает XRAY_ENV: "KEY=VALUE,KEY2=VALUE2"
+pub fn parse_env(value: &str) -> Result<Vec<(String, String)>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", pair))?;
+            let key = key.trim();
+            if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(format!("invalid variable name '{}'", key));
+            }
+            Ok((key.to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xray::testing::fake_xray;
+
+    #[test]
+    fn test_parse_env() {
+        let env = parse_env("GOMAXPROCS=2, GOMEMLIMIT=512MiB,XRAY_BUF_READV=enable").unwrap();
+        assert_eq!(env[0], ("GOMAXPROCS".to_string(), "2".to_string()));
+        assert_eq!(env.len(), 3);
+        assert!(parse_env("").unwrap().is_empty());
+        assert!(parse_env("GOMAXPROCS").is_err());
+        assert!(parse_env("BAD NAME=1").is_err());
+    }
+
+    #[test]
+    fn test_io_priority_parsing() {
+        let priority = "best-effort:6".parse::<IoPriority>().unwrap();
+        assert_eq!(priority, IoPriority { class: IoClass::BestEffort, level: 6 });
+        assert_eq!(priority.ioprio(), (2 << 13) | 6);
+        assert_eq!("idle".parse::<IoPriority>().unwrap().to_string(), "idle");
+        assert!("best-effort:9".parse::<IoPriority>().is_err());
+        assert!("fast".parse::<IoPriority>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_apply_to_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = fake_xray(&dir, "");
+        std::fs::write(
+            &script,
+            "#!/bin/sh\necho \"$GOMAXPROCS $1 $(pwd) $(ulimit -n) $(nice)\"\n",
+        )
+        .unwrap();
+
+        let options = LaunchOptions {
+            env: vec![("GOMAXPROCS".to_string(), "2".to_string())],
+            args: vec!["-test".to_string()],
+            working_dir: Some(dir.path().to_path_buf()),
+            rlimit_nofile: Some(256),
+            nice: Some(5),
+            ..LaunchOptions::default()
+        };
+        let mut cmd = TokioCommand::new(&script);
+        options.apply(&mut cmd);
+        let output = cmd.output().await.unwrap();
+
+        let nice = unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) } + 5;
+        let expected = format!("2 -test {} 256 {}\n", dir.path().canonicalize().unwrap().display(), nice.min(19));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+    }
+}
@@ -0,0 +1,314 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::config::Config as AppConfig;
+use crate::xray::config::{XrayConfig, CONTROL_API_INBOUND_TAG};
+use crate::xray::model::{ConfigIssue, Inbound, Port, XrayConfigModel};
+
+/// Результат статической проверки конфигурации
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LintReport {
+    /// Ошибки, с которыми Xray не запустится или будет маршрутизировать неверно
+    pub errors: Vec<ConfigIssue>,
+    /// Подозрительные места, которые могут быть намеренными
+    pub warnings: Vec<ConfigIssue>,
+}
+
+impl LintReport {
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty() && self.warnings.is_empty()
+    }
+
+    fn error(&mut self, path: String, reason: String) {
+        self.errors.push(ConfigIssue { path, reason });
+    }
+
+    fn warning(&mut self, path: String, reason: String) {
+        self.warnings.push(ConfigIssue { path, reason });
+    }
+}
+
+/// Порт, который занимает сама нода
+struct NodePort {
+    port: u16,
+    listen: String,
+    setting: &'static str,
+}
+
+/// Путь inbound в документе панели; inbound, которого у панели нет, указывается
+/// по индексу в конфигурации ноды
+fn inbound_path(config: &XrayConfig, index: usize) -> String {
+    match config.panel_inbound_index(index) {
+        Some(panel) => format!("inbounds[{}]", panel),
+        None => format!("inbounds[{}] (in the node's config)", index),
+    }
+}
+
+/// Путь правила маршрутизации в документе панели, как inbound_path
+fn rule_path(config: &XrayConfig, index: usize) -> String {
+    match config.panel_rule_index(index) {
+        Some(panel) => format!("routing.rules[{}]", panel),
+        None => format!("routing.rules[{}] (in the node's config)", index),
+    }
+}
+
+/// Проверяет конфигурацию после преобразований ноды: теги, порты и ссылки маршрутизации.
+/// Пути inbounds и правил указывают на документ панели
+pub fn lint(config: &XrayConfig, app_config: &AppConfig) -> LintReport {
+    let mut report = LintReport::default();
+    let model = match XrayConfigModel::from_value(config.as_value().clone()) {
+        Ok(model) => model,
+        Err(issues) => {
+            report.errors = issues;
+            return report;
+        }
+    };
+    let inbounds = model.inbounds.as_deref().unwrap_or_default();
+    let outbounds = model.outbounds.as_deref().unwrap_or_default();
+
+    // Теги inbounds
+    let mut inbound_tags: HashMap<&str, usize> = HashMap::new();
+    for (index, inbound) in inbounds.iter().enumerate() {
+        let Some(tag) = inbound.tag.as_deref() else {
+            continue;
+        };
+        if let Some(first) = inbound_tags.insert(tag, index) {
+            report.error(
+                format!("{}.tag", inbound_path(config, index)),
+                format!("inbound tag '{}' is already used by {}", tag, inbound_path(config, first)),
+            );
+        }
+    }
+
+    // Теги outbounds
+    let mut outbound_tags: HashMap<&str, usize> = HashMap::new();
+    for (index, outbound) in outbounds.iter().enumerate() {
+        let Some(tag) = outbound.tag.as_deref() else {
+            continue;
+        };
+        if let Some(first) = outbound_tags.insert(tag, index) {
+            report.error(
+                format!("outbounds[{}].tag", index),
+                format!("outbound tag '{}' is already used by outbounds[{}]", tag, first),
+            );
+        }
+    }
+
+    lint_ports(config, inbounds, app_config, &mut report);
+
+    // Ссылки правил маршрутизации
+    let routing = model.routing.as_ref();
+    let balancer_tags: HashSet<&str> = routing
+        .and_then(|routing| routing.balancers.as_deref())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|balancer| balancer.tag.as_deref())
+        .collect();
+    // Правила с outboundTag API ведут в сервис API, а не в outbound
+    let api_tag = model.api.as_ref().and_then(|api| api.tag.as_deref());
+    let rules = routing.and_then(|routing| routing.rules.as_deref()).unwrap_or_default();
+    for (index, rule) in rules.iter().enumerate() {
+        let path = rule_path(config, index);
+        match (rule.outbound_tag.as_deref(), rule.balancer_tag.as_deref()) {
+            (Some(tag), _) if !outbound_tags.contains_key(tag) && Some(tag) != api_tag => {
+                report.error(format!("{}.outboundTag", path), format!("no outbound with tag '{}'", tag));
+            }
+            (None, Some(tag)) if !balancer_tags.contains(tag) => {
+                report.error(format!("{}.balancerTag", path), format!("no balancer with tag '{}'", tag));
+            }
+            (None, None) => {
+                report.warning(path.clone(), "rule has neither outboundTag nor balancerTag".to_string());
+            }
+            _ => {}
+        }
+        // Inbound мог быть исключен фильтром INBOUNDS, поэтому это только предупреждение
+        for tag in rule.inbound_tag.iter().flat_map(|tags| tags.iter()) {
+            if !inbound_tags.contains_key(tag.as_str()) {
+                report.warning(format!("{}.inboundTag", path), format!("no inbound with tag '{}'", tag));
+            }
+        }
+    }
+
+    report
+}
+
+/// Совпадающие порты inbounds и порты самой ноды
+fn lint_ports(config: &XrayConfig, inbounds: &[Inbound], app_config: &AppConfig, report: &mut LintReport) {
+    let mut node_ports = vec![
+        NodePort {
+            port: app_config.service_port,
+            listen: app_config.service_host.clone(),
+            setting: "SERVICE_PORT",
+        },
+        NodePort {
+            port: app_config.xray_api_port,
+            listen: app_config.xray_api_host.clone(),
+            setting: "XRAY_API_PORT",
+        },
+    ];
+    if app_config.xray_control_api_port != 0 {
+        node_ports.push(NodePort {
+            port: app_config.xray_control_api_port,
+            listen: "127.0.0.1".to_string(),
+            setting: "XRAY_CONTROL_API_PORT",
+        });
+    }
+
+    // Индекс, адрес и диапазоны портов уже проверенных inbounds
+    let mut seen: Vec<(usize, &str, Vec<PortRange>)> = Vec::new();
+    for (index, inbound) in inbounds.iter().enumerate() {
+        // Inbounds ноды сами занимают эти порты
+        if matches!(inbound.tag.as_deref(), Some("API_INBOUND" | CONTROL_API_INBOUND_TAG)) {
+            continue;
+        }
+        let listen = inbound.listen.as_deref().unwrap_or_default();
+        // Unix сокеты портов не занимают
+        if listen.starts_with('/') || listen.starts_with('@') {
+            continue;
+        }
+        let Some(ranges) = inbound.port.as_ref().and_then(port_ranges) else {
+            continue;
+        };
+        let path = format!("{}.port", inbound_path(config, index));
+
+        for node in &node_ports {
+            if overlaps(&ranges, &[(node.port, node.port)]) && listens_overlap(listen, &node.listen) {
+                report.error(path.clone(), format!("port {} is used by the node ({})", node.port, node.setting));
+            }
+        }
+        for (other, other_listen, other_ranges) in &seen {
+            if overlaps(&ranges, other_ranges) && listens_overlap(listen, other_listen) {
+                report.error(path.clone(), format!("port is already used by {}", inbound_path(config, *other)));
+            }
+        }
+        seen.push((index, listen, ranges));
+    }
+}
+
+/// Диапазон портов включительно
+type PortRange = (u16, u16);
+
+/// Диапазоны порта: "443", "1000-2000", "80,443"; None для "env:" и прочих форм
+fn port_ranges(port: &Port) -> Option<Vec<PortRange>> {
+    match port {
+        Port::Number(port) => Some(vec![(*port, *port)]),
+        Port::Text(text) => text
+            .split(',')
+            .map(|part| {
+                let part = part.trim();
+                match part.split_once('-') {
+                    Some((from, to)) => Some((from.trim().parse().ok()?, to.trim().parse().ok()?)),
+                    None => part.parse().ok().map(|port| (port, port)),
+                }
+            })
+            .collect(),
+    }
+}
+
+fn overlaps(a: &[PortRange], b: &[PortRange]) -> bool {
+    a.iter().any(|(a_from, a_to)| b.iter().any(|(b_from, b_to)| a_from <= b_to && b_from <= a_to))
+}
+
+/// Пересекаются ли адреса прослушивания: пустой адрес и 0.0.0.0/:: означают все адреса
+fn listens_overlap(a: &str, b: &str) -> bool {
+    let any = |listen: &str| matches!(listen, "" | "0.0.0.0" | "::" | "[::]");
+    any(a) || any(b) || a == b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn lint_json(value: serde_json::Value) -> LintReport {
+        lint_json_with(value, AppConfig::default())
+    }
+
+    fn lint_json_with(value: serde_json::Value, app_config: AppConfig) -> LintReport {
+        let config = XrayConfig::new(&value.to_string(), "127.0.0.1".to_string(), &app_config).unwrap();
+        lint(&config, &app_config)
+    }
+
+    #[test]
+    fn test_clean_config() {
+        let report = lint_json(json!({
+            "inbounds": [{"tag": "in", "port": 443, "protocol": "vless"}],
+            "outbounds": [{"tag": "direct", "protocol": "freedom"}],
+            "routing": {"rules": [{"type": "field", "inboundTag": ["in"], "outboundTag": "direct"}]}
+        }));
+        assert!(report.is_empty(), "{:?}", report);
+    }
+
+    #[test]
+    fn test_tags_and_references() {
+        let report = lint_json(json!({
+            "inbounds": [{"tag": "in", "port": 443}, {"tag": "in", "port": 8443}],
+            "outbounds": [{"tag": "direct"}],
+            "routing": {"rules": [
+                {"outboundTag": "blocked"},
+                {"balancerTag": "lb"},
+                {"inboundTag": "missing", "outboundTag": "direct"}
+            ]}
+        }));
+        let errors: Vec<String> = report.errors.iter().map(ToString::to_string).collect();
+        assert_eq!(errors, vec![
+            "inbounds[1].tag: inbound tag 'in' is already used by inbounds[0]",
+            "routing.rules[0].outboundTag: no outbound with tag 'blocked'",
+            "routing.rules[1].balancerTag: no balancer with tag 'lb'",
+        ]);
+        assert_eq!(report.warnings[0].to_string(), "routing.rules[2].inboundTag: no inbound with tag 'missing'");
+    }
+
+    #[test]
+    fn test_paths_refer_to_panel_document() {
+        // INBOUNDS и замененные API inbound и правило панели не сдвигают пути
+        let report = lint_json_with(
+            json!({
+                "api": {"tag": "api"},
+                "inbounds": [
+                    {"tag": "blocked", "port": 443},
+                    {"tag": "API_INBOUND", "protocol": "dokodemo-door"},
+                    {"tag": "in", "port": 443},
+                    {"tag": "in", "port": 8443}
+                ],
+                "outbounds": [{"tag": "direct"}],
+                "routing": {"rules": [{"inboundTag": ["API_INBOUND"], "outboundTag": "api"}, {"outboundTag": "proxy"}]}
+            }),
+            AppConfig {
+                inbounds: vec!["!blocked".to_string()],
+                ..AppConfig::default()
+            },
+        );
+        let errors: Vec<String> = report.errors.iter().map(ToString::to_string).collect();
+        assert_eq!(errors, vec![
+            "inbounds[3].tag: inbound tag 'in' is already used by inbounds[2]",
+            "routing.rules[1].outboundTag: no outbound with tag 'proxy'",
+        ]);
+
+        // Тег, занятый inbound ноды, указывает на конфигурацию ноды
+        let report = lint_json(json!({"inbounds": [{"tag": "API_INBOUND", "protocol": "vless", "port": 443}]}));
+        assert_eq!(
+            report.errors[0].to_string(),
+            "inbounds[0].tag: inbound tag 'API_INBOUND' is already used by inbounds[0] (in the node's config)"
+        );
+    }
+
+    #[test]
+    fn test_port_clashes() {
+        let report = lint_json(json!({
+            "inbounds": [
+                {"tag": "a", "port": 443},
+                {"tag": "b", "port": "400-500"},
+                {"tag": "c", "port": 443, "listen": "/run/xray.sock"},
+                {"tag": "d", "port": 62050},
+                {"tag": "e", "port": 8443, "listen": "10.0.0.1"},
+                {"tag": "f", "port": 8443, "listen": "10.0.0.2"}
+            ]
+        }));
+        let errors: Vec<String> = report.errors.iter().map(ToString::to_string).collect();
+        assert_eq!(errors, vec![
+            "inbounds[1].port: port is already used by inbounds[0]",
+            "inbounds[3].port: port 62050 is used by the node (SERVICE_PORT)",
+        ]);
+    }
+}
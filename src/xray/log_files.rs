@@ -0,0 +1,410 @@
+use chrono::{DateTime, Utc};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncWriteExt, BufWriter},
+    time::Instant,
+};
+use tracing::{error, warn};
+
+use crate::xray::logs::{LogEvent, LogSubscription};
+
+/// Имя активного файла логов
+const ACTIVE_FILE_NAME: &str = "xray.log";
+
+/// Префикс архивных файлов логов
+const ARCHIVE_PREFIX: &str = "xray-";
+
+/// Настройки хранения логов Xray на диске
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFilesOptions {
+    /// Каталог для файлов логов (LOGS_DIR)
+    pub dir: PathBuf,
+    /// Размер активного файла, после которого он ротируется (LOGS_FILE_MAX_BYTES)
+    pub max_file_bytes: u64,
+    /// Максимальное время записи в один файл (LOGS_FILE_ROTATE_SECS)
+    pub rotate_interval: Duration,
+    /// Сжимать архивные файлы gzip (LOGS_FILE_COMPRESS)
+    pub compress: bool,
+    /// Максимальное количество архивных файлов (LOGS_FILE_MAX_COUNT)
+    pub max_files: usize,
+    /// Максимальный возраст архивных файлов (LOGS_FILE_MAX_AGE_SECS)
+    pub max_age: Duration,
+}
+
+/// Одна строка лога в файле (JSON lines)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogRecord {
+    pub ts: DateTime<Utc>,
+    pub line: String,
+}
+
+/// Пишет логи Xray в файлы с ротацией по размеру и времени
+#[derive(Debug)]
+pub struct LogFileSink {
+    options: LogFilesOptions,
+    file: Option<BufWriter<File>>,
+    size: u64,
+    opened_at: Instant,
+}
+
+impl LogFileSink {
+    pub fn new(options: LogFilesOptions) -> Self {
+        Self {
+            options,
+            file: None,
+            size: 0,
+            opened_at: Instant::now(),
+        }
+    }
+
+    /// Читает подписку и пишет каждую строку на диск, пока конвейер не закрыт
+    pub async fn run(mut self, mut logs: LogSubscription) {
+        if let Err(e) = fs::create_dir_all(&self.options.dir) {
+            error!("Failed to create logs directory {}: {}", self.options.dir.display(), e);
+            return;
+        }
+
+        while let Some(event) = logs.recv().await {
+            let record = LogRecord::from(event);
+
+            if let Err(e) = self.write(&record).await {
+                warn!("Failed to write Xray log to {}: {}", self.options.dir.display(), e);
+                // Переоткроем файл на следующей записи
+                self.file = None;
+            }
+
+            // Сбрасываем буфер, когда очередь опустела, чтобы не терять строки при падении
+            if logs.is_drained() {
+                if let Some(file) = self.file.as_mut() {
+                    let _ = file.flush().await;
+                }
+            }
+        }
+    }
+
+    /// Записывает одну строку, при необходимости ротируя файл
+    async fn write(&mut self, record: &LogRecord) -> io::Result<()> {
+        if self.file.is_some()
+            && (self.size >= self.options.max_file_bytes
+                || self.opened_at.elapsed() >= self.options.rotate_interval)
+        {
+            self.rotate().await?;
+        }
+
+        if self.file.is_none() {
+            self.open().await?;
+        }
+
+        let mut data = serde_json::to_vec(record)?;
+        data.push(b'\n');
+
+        if let Some(file) = self.file.as_mut() {
+            file.write_all(&data).await?;
+            self.size += data.len() as u64;
+        }
+
+        Ok(())
+    }
+
+    /// Открывает активный файл на дозапись
+    async fn open(&mut self) -> io::Result<()> {
+        let path = self.options.dir.join(ACTIVE_FILE_NAME);
+        let file = OpenOptions::new().create(true).append(true).open(&path).await?;
+        self.size = file.metadata().await?.len();
+        self.file = Some(BufWriter::new(file));
+        self.opened_at = Instant::now();
+        Ok(())
+    }
+
+    /// Переименовывает активный файл в архивный, сжимает его и удаляет старые архивы
+    async fn rotate(&mut self) -> io::Result<()> {
+        if let Some(mut file) = self.file.take() {
+            file.flush().await?;
+        }
+
+        let archive_name = format!("{}{}.log", ARCHIVE_PREFIX, Utc::now().format("%Y%m%dT%H%M%S%.3f"));
+        let archive_path = self.options.dir.join(archive_name);
+        tokio::fs::rename(self.options.dir.join(ACTIVE_FILE_NAME), &archive_path).await?;
+
+        // Ждем завершения, чтобы сжатие и очистка разных ротаций не пересекались
+        let options = self.options.clone();
+        let _ = tokio::task::spawn_blocking(move || {
+            if options.compress {
+                if let Err(e) = compress_file(&archive_path) {
+                    warn!("Failed to compress {}: {}", archive_path.display(), e);
+                }
+            }
+            if let Err(e) = prune_archives(&options) {
+                warn!("Failed to prune Xray log archives: {}", e);
+            }
+        })
+        .await;
+
+        Ok(())
+    }
+}
+
+/// Сжимает файл в .gz и удаляет исходный
+fn compress_file(path: &Path) -> io::Result<()> {
+    let gz_path = PathBuf::from(format!("{}.gz", path.display()));
+    let mut input = fs::File::open(path)?;
+    let mut encoder = GzEncoder::new(fs::File::create(&gz_path)?, Compression::default());
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?.sync_all()?;
+    fs::remove_file(path)
+}
+
+/// Возвращает архивные файлы от старых к новым
+fn list_archives(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut archives: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(ARCHIVE_PREFIX))
+        })
+        .collect();
+    // Имена содержат метку времени, поэтому сортировка по имени хронологическая
+    archives.sort();
+    Ok(archives)
+}
+
+/// Удаляет архивы сверх лимита по количеству и возрасту
+/// Архив, который не удалось удалить, не мешает удалению остальных
+fn prune_archives(options: &LogFilesOptions) -> io::Result<()> {
+    let archives = list_archives(&options.dir)?;
+    let excess = archives.len().saturating_sub(options.max_files);
+    let now = SystemTime::now();
+
+    for (index, path) in archives.iter().enumerate() {
+        let expired = fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age > options.max_age);
+
+        if index < excess || expired {
+            if let Err(e) = fs::remove_file(path) {
+                warn!("Failed to remove Xray log archive {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Фильтры запроса исторических логов
+#[derive(Debug, Clone)]
+pub struct LogQuery {
+    /// Начало временного окна (включительно)
+    pub from: Option<DateTime<Utc>>,
+    /// Конец временного окна (включительно)
+    pub to: Option<DateTime<Utc>>,
+    /// Подстрока, которую должна содержать строка
+    pub contains: Option<String>,
+    /// Регулярное выражение для строки
+    pub regex: Option<Regex>,
+    /// Максимальное количество строк в ответе
+    pub limit: usize,
+}
+
+/// Результат запроса исторических логов
+#[derive(Debug, Serialize)]
+pub struct LogQueryResult {
+    pub lines: Vec<LogRecord>,
+    /// В окне есть еще строки; следующий запрос можно начать с ts последней строки
+    pub truncated: bool,
+}
+
+impl LogQuery {
+    fn matches(&self, record: &LogRecord) -> bool {
+        self.from.is_none_or(|from| record.ts >= from)
+            && self.to.is_none_or(|to| record.ts <= to)
+            && self.contains.as_ref().is_none_or(|needle| record.line.contains(needle.as_str()))
+            && self.regex.as_ref().is_none_or(|regex| regex.is_match(&record.line))
+    }
+}
+
+/// Ищет строки в архивных и активном файлах (блокирующая операция)
+pub fn query_logs(dir: &Path, query: &LogQuery) -> io::Result<LogQueryResult> {
+    let mut files = match list_archives(dir) {
+        Ok(files) => files,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    let active = dir.join(ACTIVE_FILE_NAME);
+    if active.exists() {
+        files.push(active);
+    }
+
+    let mut lines = Vec::new();
+    for path in files {
+        let file = match fs::File::open(&path) {
+            Ok(file) => file,
+            // Файл могли удалить при ротации между листингом и открытием
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        let reader: Box<dyn Read> = if path.extension().is_some_and(|ext| ext == "gz") {
+            Box::new(GzDecoder::new(file))
+        } else {
+            Box::new(file)
+        };
+
+        for line in BufReader::new(reader).lines() {
+            let Ok(record) = serde_json::from_str::<LogRecord>(&line?) else {
+                continue;
+            };
+            if query.to.is_some_and(|to| record.ts > to) {
+                break;
+            }
+            if query.matches(&record) {
+                if lines.len() == query.limit {
+                    return Ok(LogQueryResult { lines, truncated: true });
+                }
+                lines.push(record);
+            }
+        }
+    }
+
+    Ok(LogQueryResult { lines, truncated: false })
+}
+
+/// Создает задачу записи логов на диск
+pub fn spawn_log_file_sink(options: LogFilesOptions, logs: LogSubscription) {
+    tokio::spawn(LogFileSink::new(options).run(logs));
+}
+
+impl From<LogEvent> for LogRecord {
+    fn from(event: LogEvent) -> Self {
+        Self {
+            ts: Utc::now(),
+            line: event.into_text(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xray::logs::{LogsPipeline, LogsPipelineOptions};
+    use tempfile::tempdir;
+
+    fn options(dir: &Path) -> LogFilesOptions {
+        LogFilesOptions {
+            dir: dir.to_path_buf(),
+            max_file_bytes: 200,
+            rotate_interval: Duration::from_secs(3600),
+            compress: true,
+            max_files: 2,
+            max_age: Duration::from_secs(3600),
+        }
+    }
+
+    fn query() -> LogQuery {
+        LogQuery {
+            from: None,
+            to: None,
+            contains: None,
+            regex: None,
+            limit: 1000,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rotation_compression_and_retention() {
+        let temp_dir = tempdir().unwrap();
+        let mut sink = LogFileSink::new(options(temp_dir.path()));
+
+        for i in 0..40 {
+            let record = LogRecord::from(LogEvent::Line(format!("line {}", i)));
+            sink.write(&record).await.unwrap();
+        }
+        sink.file.as_mut().unwrap().flush().await.unwrap();
+
+        let archives = list_archives(temp_dir.path()).unwrap();
+        assert_eq!(archives.len(), 2);
+        assert!(archives.iter().all(|path| path.extension().unwrap() == "gz"));
+
+        // Старые строки удалены вместе с архивами, новые доступны
+        let result = query_logs(temp_dir.path(), &query()).unwrap();
+        assert_eq!(result.lines.last().unwrap().line, "line 39");
+        assert!(!result.lines.iter().any(|record| record.line == "line 0"));
+    }
+
+    #[test]
+    fn test_prune_continues_after_failed_delete() {
+        let temp_dir = tempdir().unwrap();
+        // Каталог с именем архива не удаляется через remove_file
+        let stuck = temp_dir.path().join(format!("{}20240101T000000.000.log", ARCHIVE_PREFIX));
+        fs::create_dir(&stuck).unwrap();
+        fs::write(stuck.join("keep"), "").unwrap();
+        for name in ["20240102T000000.000", "20240103T000000.000", "20240104T000000.000"] {
+            fs::write(temp_dir.path().join(format!("{}{}.log.gz", ARCHIVE_PREFIX, name)), "").unwrap();
+        }
+
+        prune_archives(&options(temp_dir.path())).unwrap();
+        let archives = list_archives(temp_dir.path()).unwrap();
+        assert_eq!(archives.len(), 3);
+        assert_eq!(archives[0], stuck);
+        assert!(archives[1].to_string_lossy().contains("20240103"));
+    }
+
+    #[test]
+    fn test_query_filters_and_limit() {
+        let temp_dir = tempdir().unwrap();
+        let base = Utc::now();
+        let records: Vec<String> = (0..5)
+            .map(|i| {
+                serde_json::to_string(&LogRecord {
+                    ts: base + chrono::Duration::seconds(i),
+                    line: format!("{} line {}", if i % 2 == 0 { "[Info]" } else { "[Warning]" }, i),
+                })
+                .unwrap()
+            })
+            .collect();
+        fs::write(temp_dir.path().join(ACTIVE_FILE_NAME), records.join("\n")).unwrap();
+
+        let mut window = query();
+        window.from = Some(base + chrono::Duration::seconds(1));
+        window.to = Some(base + chrono::Duration::seconds(3));
+        let result = query_logs(temp_dir.path(), &window).unwrap();
+        assert_eq!(result.lines.len(), 3);
+
+        let mut filtered = query();
+        filtered.contains = Some("[Warning]".to_string());
+        let result = query_logs(temp_dir.path(), &filtered).unwrap();
+        assert_eq!(result.lines.len(), 2);
+
+        let mut limited = query();
+        limited.regex = Some(Regex::new(r"line [0-3]$").unwrap());
+        limited.limit = 2;
+        let result = query_logs(temp_dir.path(), &limited).unwrap();
+        assert_eq!(result.lines.len(), 2);
+        assert!(result.truncated);
+    }
+
+    #[tokio::test]
+    async fn test_sink_writes_pipeline_lines() {
+        let temp_dir = tempdir().unwrap();
+        let pipeline = LogsPipeline::new(LogsPipelineOptions::default());
+        spawn_log_file_sink(options(temp_dir.path()), pipeline.subscribe_live().await);
+
+        pipeline.publish("hello".to_string()).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let result = query_logs(temp_dir.path(), &query()).unwrap();
+        assert_eq!(result.lines.len(), 1);
+        assert_eq!(result.lines[0].line, "hello");
+    }
+}
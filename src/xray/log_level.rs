@@ -0,0 +1,298 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{fmt, str::FromStr, sync::RwLock};
+
+use crate::xray::config::XrayConfig;
+
+/// Уровень логов Xray (log.loglevel)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum XrayLogLevel {
+    None,
+    Error,
+    #[default]
+    Warning,
+    Info,
+    Debug,
+}
+
+impl FromStr for XrayLogLevel {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "none" => Ok(XrayLogLevel::None),
+            "error" => Ok(XrayLogLevel::Error),
+            "warning" => Ok(XrayLogLevel::Warning),
+            "info" => Ok(XrayLogLevel::Info),
+            "debug" => Ok(XrayLogLevel::Debug),
+            other => Err(format!(
+                "unknown Xray log level '{}', expected none, error, warning, info or debug",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for XrayLogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            XrayLogLevel::None => "none",
+            XrayLogLevel::Error => "error",
+            XrayLogLevel::Warning => "warning",
+            XrayLogLevel::Info => "info",
+            XrayLogLevel::Debug => "debug",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Текущие настройки фильтра логов Xray
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LogLevelState {
+    /// Уровень строк, которые получают подписчики
+    pub xray_level: XrayLogLevel,
+    /// Передаются ли строки access лога
+    pub access_log: bool,
+    /// Уровень, с которым запущен процесс Xray (верхняя граница xray_level)
+    pub launch_level: XrayLogLevel,
+    /// Пишет ли запущенный Xray access лог в stdout
+    pub access_available: bool,
+}
+
+/// Вид строки из stdout Xray
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineKind {
+    Level(XrayLogLevel),
+    Access,
+    Other,
+}
+
+/// Фильтр строк Xray по уровню и access логу, меняется без перезапуска ядра
+///
+/// LoggerService в Xray умеет только RestartLogger, поэтому уровень нельзя поменять
+/// через API inbound. Вместо этого Xray запускается с уровнем не ниже
+/// XRAY_RUNTIME_LOG_LEVEL, а лишние строки отбрасываются на стороне ноды.
+#[derive(Debug)]
+pub struct LogLevelControl {
+    /// Минимальный уровень запуска Xray (XRAY_RUNTIME_LOG_LEVEL)
+    runtime_level: XrayLogLevel,
+    state: RwLock<LogLevelState>,
+    /// "2024/01/01 12:00:00.123456 [Warning] ..."
+    level_tag: Regex,
+    /// "2024/01/01 12:00:00 from 1.2.3.4:5 accepted tcp:..."
+    access_line: Regex,
+    /// Баннер запуска нужен для определения старта и проходит всегда
+    banner: Regex,
+}
+
+impl LogLevelControl {
+    pub fn new(runtime_level: XrayLogLevel) -> Self {
+        Self {
+            runtime_level,
+            state: RwLock::new(LogLevelState {
+                xray_level: XrayLogLevel::Warning,
+                access_log: true,
+                launch_level: XrayLogLevel::Warning,
+                access_available: true,
+            }),
+            level_tag: Regex::new(r"^\S+ \S+ \[(Debug|Info|Warning|Error)\]").unwrap(),
+            access_line: Regex::new(r"^\S+ \S+ (?:from )?\S+ (?:accepted|rejected) ").unwrap(),
+            banner: Regex::new(r"core: Xray \S+ started").unwrap(),
+        }
+    }
+
+    /// Подготавливает конфигурацию к запуску и сбрасывает фильтр
+    ///
+    /// Запрошенный панелью уровень становится уровнем фильтра, а Xray запускается
+    /// с уровнем не ниже warning (для баннера запуска, как в Python) и не ниже
+    /// XRAY_RUNTIME_LOG_LEVEL, чтобы уровень можно было поднять позже.
+    pub fn prepare(&self, config: &mut XrayConfig) {
+        let Some(config_obj) = config.as_object_mut() else {
+            return;
+        };
+        let log = config_obj.entry("log").or_insert_with(|| json!({}));
+        let Some(log_obj) = log.as_object_mut() else {
+            return;
+        };
+
+        // Xray сравнивает ключи без учета регистра и берет последний дубликат,
+        // поэтому все варианты написания заменяются одним ключом loglevel
+        let keys: Vec<String> = log_obj
+            .keys()
+            .filter(|key| key.eq_ignore_ascii_case("loglevel"))
+            .cloned()
+            .collect();
+        let levels: Vec<Value> = keys.iter().filter_map(|key| log_obj.remove(key)).collect();
+
+        // Xray по умолчанию пишет warning, неизвестный уровень оставляем ему
+        let requested = match levels.iter().rev().find_map(Value::as_str) {
+            Some(level) => level.parse().unwrap_or(XrayLogLevel::Warning),
+            None => XrayLogLevel::Warning,
+        };
+        let launch_level = requested.max(XrayLogLevel::Warning).max(self.runtime_level);
+        log_obj.insert("loglevel".to_string(), Value::String(launch_level.to_string()));
+
+        // Пустой путь означает stdout, "none" выключает access лог, иначе это файл
+        let access_available = log_obj
+            .get("access")
+            .and_then(Value::as_str)
+            .is_none_or(str::is_empty);
+
+        *self.state.write().unwrap() = LogLevelState {
+            xray_level: requested,
+            access_log: access_available,
+            launch_level,
+            access_available,
+        };
+    }
+
+    /// Текущие настройки фильтра
+    pub fn state(&self) -> LogLevelState {
+        *self.state.read().unwrap()
+    }
+
+    /// Меняет уровень и access лог; ничего не меняет, если запрос невыполним без перезапуска
+    ///
+    /// Уровень выше уровня запуска отклоняется явно: LoggerService его не поднимет,
+    /// а фильтр ноды не может показать строки, которые Xray не пишет
+    pub fn set(&self, xray_level: Option<XrayLogLevel>, access_log: Option<bool>) -> Result<LogLevelState, String> {
+        let mut state = self.state.write().unwrap();
+
+        if let Some(level) = xray_level {
+            if level > state.launch_level {
+                return Err(format!(
+                    "Xray was started with log level {}, and its LoggerService cannot raise it to {}; \
+                     restart Xray with log.loglevel {} or set XRAY_RUNTIME_LOG_LEVEL before starting it",
+                    state.launch_level, level, level
+                ));
+            }
+        }
+        if access_log == Some(true) && !state.access_available {
+            return Err("Xray was started without access log on stdout, enabling it requires a restart".to_string());
+        }
+
+        if let Some(level) = xray_level {
+            state.xray_level = level;
+        }
+        if let Some(access_log) = access_log {
+            state.access_log = access_log;
+        }
+        Ok(*state)
+    }
+
+    /// Проходит ли строка через фильтр
+    pub fn allows(&self, line: &str) -> bool {
+        let state = self.state();
+        match self.classify(line) {
+            LineKind::Level(_) if self.banner.is_match(line) => true,
+            LineKind::Level(level) => level <= state.xray_level,
+            LineKind::Access => state.access_log,
+            LineKind::Other => true,
+        }
+    }
+
+    fn classify(&self, line: &str) -> LineKind {
+        if let Some(caps) = self.level_tag.captures(line) {
+            return caps[1].parse().map_or(LineKind::Other, LineKind::Level);
+        }
+        if self.access_line.is_match(line) {
+            return LineKind::Access;
+        }
+        LineKind::Other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config as AppConfig;
+
+    const WARNING_LINE: &str = "2024/01/01 12:00:00.123456 [Warning] app/dispatcher: default route";
+    const INFO_LINE: &str = "2024/01/01 12:00:00.123456 [Info] transport/internet/tcp: listening TCP";
+    const BANNER_LINE: &str = "2024/01/01 12:00:00.123456 [Warning] core: Xray 1.8.4 started";
+    const ACCESS_LINE: &str =
+        "2024/01/01 12:00:00.123456 from 203.0.113.45:51234 accepted tcp:www.example.com:443 [vless-in >> direct]";
+
+    fn xray_config(log: Value) -> XrayConfig {
+        let config_json = json!({"log": log, "inbounds": []}).to_string();
+        XrayConfig::new(&config_json, "127.0.0.1".to_string(), &AppConfig::default()).unwrap()
+    }
+
+    fn launched_log_level(config: &XrayConfig) -> String {
+        let log = config.as_object().unwrap()["log"].as_object().unwrap();
+        // Остается единственный ключ уровня
+        assert_eq!(log.keys().filter(|key| key.eq_ignore_ascii_case("loglevel")).count(), 1);
+        log["loglevel"].as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_prepare_keeps_requested_level_in_filter() {
+        let control = LogLevelControl::new(XrayLogLevel::Warning);
+        let mut config = xray_config(json!({"logLevel": "error"}));
+        control.prepare(&mut config);
+
+        // Xray запускается с warning ради баннера, но подписчики видят только error
+        assert_eq!(launched_log_level(&config), "warning");
+        let state = control.state();
+        assert_eq!(state.xray_level, XrayLogLevel::Error);
+        assert!(!control.allows(WARNING_LINE));
+        assert!(control.allows(BANNER_LINE));
+        assert!(control.allows(ACCESS_LINE));
+        assert!(control.allows("Failed to start: main: failed to load config files"));
+    }
+
+    #[test]
+    fn test_prepare_handles_both_spellings() {
+        // Документированное написание Xray и шаблона Marzban
+        let control = LogLevelControl::new(XrayLogLevel::Warning);
+        let mut config = xray_config(json!({"loglevel": "error"}));
+        control.prepare(&mut config);
+        assert_eq!(launched_log_level(&config), "warning");
+        assert_eq!(control.state().xray_level, XrayLogLevel::Error);
+
+        let mut config = xray_config(json!({"logLevel": "debug"}));
+        control.prepare(&mut config);
+        assert_eq!(launched_log_level(&config), "debug");
+        assert_eq!(control.state().xray_level, XrayLogLevel::Debug);
+    }
+
+    #[test]
+    fn test_runtime_level_allows_raising() {
+        let control = LogLevelControl::new(XrayLogLevel::Debug);
+        let mut config = xray_config(json!({"loglevel": "warning", "access": "none"}));
+        control.prepare(&mut config);
+        assert_eq!(launched_log_level(&config), "debug");
+        assert!(!control.allows(INFO_LINE));
+
+        let state = control.set(Some(XrayLogLevel::Info), None).unwrap();
+        assert_eq!(state.xray_level, XrayLogLevel::Info);
+        assert!(control.allows(INFO_LINE));
+
+        // access лог выключен в конфигурации, включить его без перезапуска нельзя
+        assert!(control.set(None, Some(true)).is_err());
+    }
+
+    #[test]
+    fn test_set_rejects_level_above_launch_level() {
+        let control = LogLevelControl::new(XrayLogLevel::Warning);
+        let mut config = xray_config(json!({}));
+        control.prepare(&mut config);
+
+        assert!(control.set(Some(XrayLogLevel::Debug), Some(false)).is_err());
+        // Неудачный запрос ничего не меняет
+        assert!(control.state().access_log);
+
+        control.set(None, Some(false)).unwrap();
+        assert!(!control.allows(ACCESS_LINE));
+        assert!(control.allows(WARNING_LINE));
+    }
+
+    #[test]
+    fn test_level_parsing() {
+        assert_eq!("Warning".parse::<XrayLogLevel>().unwrap(), XrayLogLevel::Warning);
+        assert!("verbose".parse::<XrayLogLevel>().is_err());
+        assert!(XrayLogLevel::Debug > XrayLogLevel::Info);
+    }
+}
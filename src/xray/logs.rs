@@ -1,234 +1,460 @@
 use std::collections::VecDeque;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+use tokio::sync::{broadcast, RwLock};
+
+use crate::xray::redact::{LogRedactor, RedactionOptions};
+
+/// Настройки конвейера логов Xray
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogsPipelineOptions {
+    /// Максимальное количество строк в кольцевом буфере (LOGS_BUFFER_LINES)
+    pub buffer_lines: usize,
+    /// Максимальный суммарный размер строк в буфере в байтах (LOGS_BUFFER_BYTES)
+    pub buffer_bytes: usize,
+    /// Длина очереди каждого подписчика (LOGS_SUBSCRIBER_QUEUE)
+    pub subscriber_queue: usize,
+    /// Политика редактирования строк до их попадания к подписчикам
+    pub redaction: RedactionOptions,
+}
+
+impl Default for LogsPipelineOptions {
+    fn default() -> Self {
+        Self {
+            buffer_lines: 100,
+            buffer_bytes: 1024 * 1024,
+            subscriber_queue: 1000,
+            redaction: RedactionOptions::default(),
+        }
+    }
+}
+
+/// Строка лога с порядковым номером в конвейере
+/// Номер используется как курсор для SSE и long-poll клиентов
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    pub seq: u64,
+    pub line: Arc<str>,
+}
 
 /// Buffer for Xray logs (аналог _logs_buffer из Python версии)
-/// Использует VecDeque с ограниченным размером как deque(maxlen=100) в Python
+/// Кольцевой буфер, ограниченный и по количеству строк, и по размеру в байтах
 #[derive(Debug)]
 pub struct LogsBuffer {
     /// Внутренний буфер с ограниченным размером
-    buffer: VecDeque<String>,
-    /// Максимальный размер буфера
-    max_size: usize,
+    buffer: VecDeque<LogEntry>,
+    /// Максимальное количество строк
+    max_lines: usize,
+    /// Максимальный суммарный размер строк в байтах
+    max_bytes: usize,
+    /// Текущий суммарный размер строк в байтах
+    bytes: usize,
 }
 
 impl LogsBuffer {
-    /// Создает новый буфер с указанным максимальным размером
-    /// Аналогично deque(maxlen=max_size) в Python
-    pub fn new(max_size: usize) -> Self {
+    /// Создает новый буфер с указанными ограничениями
+    /// Аналогично deque(maxlen=max_lines) в Python, плюс лимит по байтам
+    pub fn new(max_lines: usize, max_bytes: usize) -> Self {
         Self {
-            buffer: VecDeque::with_capacity(max_size),
-            max_size,
+            buffer: VecDeque::with_capacity(max_lines.min(1024)),
+            max_lines,
+            max_bytes,
+            bytes: 0,
         }
     }
-    
+
     /// Добавляет новую запись в буфер
-    /// Автоматически удаляет старые записи если превышен размер
-    pub fn push(&mut self, line: String) {
-        if self.buffer.len() >= self.max_size {
-            self.buffer.pop_front();
-        }
-        self.buffer.push_back(line);
-    }
-    
-    /// Возвращает все логи в буфере
-    pub fn get_all(&self) -> Vec<String> {
-        self.buffer.iter().cloned().collect()
-    }
-    
-    /// Возвращает количество записей в буфере
-    pub fn len(&self) -> usize {
-        self.buffer.len()
-    }
-    
-    /// Проверяет пуст ли буфер
-    pub fn is_empty(&self) -> bool {
-        self.buffer.is_empty()
-    }
-    
-    /// Очищает буфер
-    pub fn clear(&mut self) {
-        self.buffer.clear();
-    }
-    
-    /// Создает копию буфера (для временных буферов как в Python)
-    pub fn clone_buffer(&self) -> VecDeque<String> {
-        self.buffer.clone()
+    /// Автоматически удаляет старые записи если превышен любой из лимитов
+    pub fn push(&mut self, entry: LogEntry) {
+        self.bytes += entry.line.len();
+        self.buffer.push_back(entry);
+
+        while self.buffer.len() > self.max_lines
+            || (self.bytes > self.max_bytes && self.buffer.len() > 1)
+        {
+            if let Some(old) = self.buffer.pop_front() {
+                self.bytes -= old.line.len();
+            }
+        }
     }
+
+    /// Итератор по строкам буфера от старых к новым
+    pub fn iter(&self) -> impl Iterator<Item = &LogEntry> {
+        self.buffer.iter()
+    }
+}
+
+/// Событие, получаемое подписчиком логов
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogEvent {
+    /// Очередная строка лога
+    Line(String),
+    /// Подписчик не успевал читать, и часть строк была пропущена
+    Dropped(u64),
+}
+
+impl LogEvent {
+    /// Текстовое представление события для отправки клиенту
+    pub fn into_text(self) -> String {
+        match self {
+            LogEvent::Line(line) => line,
+            LogEvent::Dropped(count) => format!("[rustzban-node] {} lines dropped", count),
+        }
+    }
+}
+
+/// История логов и номер следующей строки, меняются вместе под одной блокировкой
+#[derive(Debug)]
+struct History {
+    buffer: LogsBuffer,
+    next_seq: u64,
 }
 
-/// Temporary logs buffer manager
-/// Аналог _temp_log_buffers из Python версии
+/// Конвейер логов Xray: кольцевой буфер истории и широковещательная рассылка
+/// Заменяет _logs_buffer и _temp_log_buffers из Python версии
 #[derive(Debug)]
-pub struct TempLogsManager {
-    /// Временные буферы с уникальными ID
-    temp_buffers: Arc<Mutex<std::collections::HashMap<usize, Arc<Mutex<VecDeque<String>>>>>>,
-    /// Счетчик для генерации уникальных ID
-    next_id: Arc<Mutex<usize>>,
+pub struct LogsPipeline {
+    /// История последних строк для новых подписчиков
+    history: RwLock<History>,
+    /// Канал рассылки, у каждого подписчика своя позиция в очереди
+    sender: broadcast::Sender<LogEntry>,
+    /// Редактирование IP, email и назначений (None - выключено)
+    redactor: Option<LogRedactor>,
 }
 
-impl TempLogsManager {
-    /// Create new temporary buffer manager
-    pub fn new() -> Self {
+impl LogsPipeline {
+    pub fn new(options: LogsPipelineOptions) -> Self {
+        let (sender, _) = broadcast::channel(options.subscriber_queue.max(1));
         Self {
-            temp_buffers: Arc::new(Mutex::new(std::collections::HashMap::new())),
-            next_id: Arc::new(Mutex::new(0)),
-        }
-    }
-    
-    /// Создает новый временный буфер (аналог get_logs context manager в Python)
-    pub fn create_temp_buffer(&self) -> TempLogBuffer {
-        let id = {
-            let mut next_id = self.next_id.lock().unwrap();
-            let id = *next_id;
-            *next_id += 1;
-            id
+            history: RwLock::new(History {
+                buffer: LogsBuffer::new(options.buffer_lines, options.buffer_bytes),
+                next_seq: 1,
+            }),
+            sender,
+            redactor: options.redaction.is_enabled().then(|| LogRedactor::new(options.redaction)),
+        }
+    }
+
+    /// Публикует строку лога всем подписчикам и возвращает ее после редактирования
+    /// Никогда не ждет медленных подписчиков: они получат LogEvent::Dropped
+    pub async fn publish(&self, line: String) -> Arc<str> {
+        // Редактируем до истории и рассылки, чтобы исходная строка не попала ни к одному подписчику
+        let line = match self.redactor {
+            Some(ref redactor) => redactor.redact(&line),
+            None => line,
         };
-        
-        let buffer = Arc::new(Mutex::new(VecDeque::with_capacity(100)));
-        
-        {
-            let mut temp_buffers = self.temp_buffers.lock().unwrap();
-            temp_buffers.insert(id, Arc::clone(&buffer));
-        }
-        
-        TempLogBuffer {
-            id,
-            buffer,
-            manager: Arc::clone(&self.temp_buffers),
-        }
-    }
-    
-    /// Добавляет строку во все временные буферы
-    pub fn push_to_all(&self, line: String) {
-        let temp_buffers = self.temp_buffers.lock().unwrap();
-        for buffer in temp_buffers.values() {
-            let mut buf = buffer.lock().unwrap();
-            if buf.len() >= 100 {
-                buf.pop_front();
-            }
-            buf.push_back(line.clone());
+        let mut history = self.history.write().await;
+        let line: Arc<str> = Arc::from(line);
+        let entry = LogEntry {
+            seq: history.next_seq,
+            line: Arc::clone(&line),
+        };
+        history.next_seq += 1;
+        history.buffer.push(entry.clone());
+        // Ошибка означает только отсутствие подписчиков
+        let _ = self.sender.send(entry);
+        line
+    }
+
+    /// Подписка с копией текущего буфера (аналог get_logs в Python)
+    pub async fn subscribe(&self) -> LogSubscription {
+        self.subscribe_after(0).await
+    }
+
+    /// Подписка со строки, следующей за курсором
+    /// Строки, уже вытесненные из истории, учитываются как пропущенные
+    pub async fn subscribe_after(&self, cursor: u64) -> LogSubscription {
+        // Держим блокировку, чтобы между копией истории и подпиской не потерялись строки
+        let history = self.history.read().await;
+        let backlog: VecDeque<LogEntry> = history.buffer.iter()
+            .filter(|entry| entry.seq > cursor)
+            .cloned()
+            .collect();
+
+        // Курсор из будущего означает перезапуск ноды: продолжаем с текущей позиции
+        let cursor = cursor.min(history.next_seq - 1);
+        let first_available = backlog.front().map_or(history.next_seq, |entry| entry.seq);
+        let dropped = if cursor == 0 { 0 } else { first_available - cursor - 1 };
+
+        LogSubscription {
+            dropped,
+            backlog,
+            receiver: self.sender.subscribe(),
+            cursor,
+        }
+    }
+
+    /// Номер последней опубликованной строки
+    pub async fn cursor(&self) -> u64 {
+        self.history.read().await.next_seq - 1
+    }
+
+    /// Последние строки истории после курсора (не больше limit)
+    pub async fn tail_after(&self, cursor: u64, limit: usize) -> Vec<String> {
+        let history = self.history.read().await;
+        let lines: Vec<String> = history.buffer.iter()
+            .filter(|entry| entry.seq > cursor)
+            .map(|entry| entry.line.to_string())
+            .collect();
+        lines[lines.len().saturating_sub(limit)..].to_vec()
+    }
+
+    /// Подписка только на новые строки, без истории
+    pub async fn subscribe_live(&self) -> LogSubscription {
+        let history = self.history.read().await;
+        LogSubscription {
+            dropped: 0,
+            backlog: VecDeque::new(),
+            receiver: self.sender.subscribe(),
+            cursor: history.next_seq - 1,
         }
     }
 }
 
-/// Временный буфер логов (аналог контекстного менеджера get_logs в Python)
-pub struct TempLogBuffer {
-    id: usize,
-    buffer: Arc<Mutex<VecDeque<String>>>,
-    manager: Arc<Mutex<std::collections::HashMap<usize, Arc<Mutex<VecDeque<String>>>>>>,
+/// Подписка на логи с собственной ограниченной очередью
+#[derive(Debug)]
+pub struct LogSubscription {
+    /// Строки, пропущенные до начала подписки (вытеснены из истории)
+    dropped: u64,
+    /// Строки истории, которые еще не выданы подписчику
+    backlog: VecDeque<LogEntry>,
+    /// Позиция подписчика в канале рассылки
+    receiver: broadcast::Receiver<LogEntry>,
+    /// Номер последней выданной или пропущенной строки
+    cursor: u64,
 }
 
-impl TempLogBuffer {
-    /// Получает все логи из временного буфера
-    pub fn get_logs(&self) -> Vec<String> {
-        let buffer = self.buffer.lock().unwrap();
-        buffer.iter().cloned().collect()
-    }
-    
-    /// Проверяет есть ли новые логи
-    pub fn has_logs(&self) -> bool {
-        let buffer = self.buffer.lock().unwrap();
-        !buffer.is_empty()
-    }
-    
-    /// Извлекает один лог из буфера (аналог popleft в Python)
-    pub fn pop_log(&self) -> Option<String> {
-        let mut buffer = self.buffer.lock().unwrap();
-        buffer.pop_front()
-    }
-    
-    /// Возвращает количество логов в буфере
-    pub fn len(&self) -> usize {
-        let buffer = self.buffer.lock().unwrap();
-        buffer.len()
-    }
-    
-    /// Проверяет пуст ли буфер
-    pub fn is_empty(&self) -> bool {
-        let buffer = self.buffer.lock().unwrap();
-        buffer.is_empty()
+impl LogSubscription {
+    /// Ждет следующее событие. Возвращает None, если конвейер закрыт
+    pub async fn recv(&mut self) -> Option<LogEvent> {
+        if let Some(event) = self.take_pending() {
+            return Some(event);
+        }
+
+        loop {
+            let step = match self.receiver.recv().await {
+                Ok(entry) => self.deliver(entry),
+                Err(broadcast::error::RecvError::Lagged(count)) => self.lagged(count),
+                Err(broadcast::error::RecvError::Closed) => return None,
+            };
+            if step.is_some() {
+                return step;
+            }
+        }
+    }
+
+    /// Возвращает событие, если оно уже доступно, не ожидая новых строк
+    pub fn try_recv(&mut self) -> Option<LogEvent> {
+        if let Some(event) = self.take_pending() {
+            return Some(event);
+        }
+
+        loop {
+            let step = match self.receiver.try_recv() {
+                Ok(entry) => self.deliver(entry),
+                Err(broadcast::error::TryRecvError::Lagged(count)) => self.lagged(count),
+                Err(_) => return None,
+            };
+            if step.is_some() {
+                return step;
+            }
+        }
+    }
+
+    /// Номер последней строки, выданной подписчику (курсор для возобновления)
+    pub fn cursor(&self) -> u64 {
+        self.cursor
+    }
+
+    /// Проверяет, что все доступные события уже выданы
+    pub fn is_drained(&self) -> bool {
+        self.dropped == 0 && self.backlog.is_empty() && self.receiver.is_empty()
+    }
+
+    /// Выдает пропуск до начала подписки или строку из истории
+    fn take_pending(&mut self) -> Option<LogEvent> {
+        if self.dropped > 0 {
+            self.cursor += self.dropped;
+            return Some(LogEvent::Dropped(std::mem::take(&mut self.dropped)));
+        }
+
+        let entry = self.backlog.pop_front()?;
+        self.cursor = entry.seq;
+        Some(LogEvent::Line(entry.line.to_string()))
     }
-}
 
-// Автоматическая очистка при удалении (аналог finally в Python context manager)
-impl Drop for TempLogBuffer {
-    fn drop(&mut self) {
-        let mut manager = self.manager.lock().unwrap();
-        manager.remove(&self.id);
+    /// Выдает строку из канала
+    /// Возвращает None, если строка уже была выдана из истории
+    fn deliver(&mut self, entry: LogEntry) -> Option<LogEvent> {
+        if entry.seq <= self.cursor {
+            return None;
+        }
+        self.cursor = entry.seq;
+        Some(LogEvent::Line(entry.line.to_string()))
+    }
+
+    /// Учитывает строки, вытесненные из очереди подписчика
+    fn lagged(&mut self, count: u64) -> Option<LogEvent> {
+        self.cursor += count;
+        Some(LogEvent::Dropped(count))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+
+    fn options(buffer_lines: usize, buffer_bytes: usize, subscriber_queue: usize) -> LogsPipelineOptions {
+        LogsPipelineOptions {
+            buffer_lines,
+            buffer_bytes,
+            subscriber_queue,
+            ..LogsPipelineOptions::default()
+        }
+    }
+
+    fn lines(buffer: &LogsBuffer) -> Vec<String> {
+        buffer.iter().map(|entry| entry.line.to_string()).collect()
+    }
+
+    fn entry(seq: u64, line: &str) -> LogEntry {
+        LogEntry {
+            seq,
+            line: line.into(),
+        }
+    }
+
     #[test]
     fn test_logs_buffer_basic() {
-        let mut buffer = LogsBuffer::new(3);
-        
-        assert!(buffer.is_empty());
-        assert_eq!(buffer.len(), 0);
-        
-        buffer.push("log1".to_string());
-        buffer.push("log2".to_string());
-        buffer.push("log3".to_string());
-        
-        assert_eq!(buffer.len(), 3);
-        assert!(!buffer.is_empty());
-        
-        let logs = buffer.get_all();
-        assert_eq!(logs, vec!["log1", "log2", "log3"]);
-    }
-    
+        let mut buffer = LogsBuffer::new(3, 1024);
+
+        assert!(lines(&buffer).is_empty());
+
+        buffer.push(entry(1, "log1"));
+        buffer.push(entry(2, "log2"));
+        buffer.push(entry(3, "log3"));
+
+        assert_eq!(lines(&buffer), vec!["log1", "log2", "log3"]);
+    }
+
     #[test]
     fn test_logs_buffer_overflow() {
-        let mut buffer = LogsBuffer::new(2);
-        
-        buffer.push("log1".to_string());
-        buffer.push("log2".to_string());
-        buffer.push("log3".to_string()); // Должен вытолкнуть log1
-        
-        assert_eq!(buffer.len(), 2);
-        
-        let logs = buffer.get_all();
-        assert_eq!(logs, vec!["log2", "log3"]);
-    }
-    
+        let mut buffer = LogsBuffer::new(2, 1024);
+
+        buffer.push(entry(1, "log1"));
+        buffer.push(entry(2, "log2"));
+        buffer.push(entry(3, "log3")); // Должен вытолкнуть log1
+
+        assert_eq!(lines(&buffer), vec!["log2", "log3"]);
+    }
+
     #[test]
-    fn test_temp_logs_manager() {
-        let manager = TempLogsManager::new();
-        
-        let temp_buffer1 = manager.create_temp_buffer();
-        let temp_buffer2 = manager.create_temp_buffer();
-        
-        assert!(temp_buffer1.is_empty());
-        assert!(temp_buffer2.is_empty());
-        
-        // Добавляем лог во все буферы
-        manager.push_to_all("test log".to_string());
-        
-        assert_eq!(temp_buffer1.len(), 1);
-        assert_eq!(temp_buffer2.len(), 1);
-        
-        let log1 = temp_buffer1.pop_log().unwrap();
-        let log2 = temp_buffer2.pop_log().unwrap();
-        
-        assert_eq!(log1, "test log");
-        assert_eq!(log2, "test log");
-    }
-    
+    fn test_logs_buffer_byte_limit() {
+        let mut buffer = LogsBuffer::new(100, 10);
+
+        buffer.push(entry(1, "aaaa"));
+        buffer.push(entry(2, "bbbb"));
+        assert_eq!(buffer.bytes, 8);
+
+        buffer.push(entry(3, "cccc")); // 12 байт > 10, выталкивает aaaa
+        assert_eq!(lines(&buffer), vec!["bbbb", "cccc"]);
+        assert_eq!(buffer.bytes, 8);
+
+        // Строка больше лимита все равно сохраняется как последняя
+        buffer.push(entry(4, &"x".repeat(20)));
+        assert_eq!(buffer.iter().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_subscribers_receive_history_and_new_lines() {
+        let pipeline = LogsPipeline::new(options(10, 1024, 16));
+        pipeline.publish("old".to_string()).await;
+
+        let mut first = pipeline.subscribe().await;
+        let mut second = pipeline.subscribe_live().await;
+        pipeline.publish("new".to_string()).await;
+
+        assert_eq!(first.recv().await, Some(LogEvent::Line("old".to_string())));
+        assert_eq!(first.recv().await, Some(LogEvent::Line("new".to_string())));
+        assert_eq!(second.recv().await, Some(LogEvent::Line("new".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_slow_subscriber_gets_dropped_marker() {
+        let pipeline = LogsPipeline::new(options(10, 1024, 2));
+        let mut slow = pipeline.subscribe_live().await;
+        let mut fast = pipeline.subscribe_live().await;
+
+        for i in 0..5 {
+            pipeline.publish(format!("line{}", i)).await;
+            // Быстрый подписчик читает сразу и ничего не теряет
+            assert_eq!(fast.recv().await, Some(LogEvent::Line(format!("line{}", i))));
+        }
+
+        assert_eq!(slow.recv().await, Some(LogEvent::Dropped(3)));
+        assert_eq!(slow.recv().await, Some(LogEvent::Line("line3".to_string())));
+        assert_eq!(slow.recv().await, Some(LogEvent::Line("line4".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_after_cursor_resumes_without_gaps() {
+        let pipeline = LogsPipeline::new(options(3, 1024, 16));
+        for i in 1..=5 {
+            pipeline.publish(format!("line{}", i)).await;
+        }
+
+        // История содержит 3..=5, курсор 3 - продолжаем с 4 без пропусков
+        let mut resumed = pipeline.subscribe_after(3).await;
+        assert_eq!(resumed.recv().await, Some(LogEvent::Line("line4".to_string())));
+        assert_eq!(resumed.cursor(), 4);
+
+        // Курсор 1 - строка 2 уже вытеснена из истории
+        let mut behind = pipeline.subscribe_after(1).await;
+        assert_eq!(behind.recv().await, Some(LogEvent::Dropped(1)));
+        assert_eq!(behind.recv().await, Some(LogEvent::Line("line3".to_string())));
+        assert_eq!(behind.cursor(), 3);
+
+        // Курсор из будущего (нода перезапущена) - только новые строки
+        let mut future = pipeline.subscribe_after(100).await;
+        assert!(future.try_recv().is_none());
+        pipeline.publish("line6".to_string()).await;
+        assert_eq!(future.recv().await, Some(LogEvent::Line("line6".to_string())));
+        assert_eq!(future.cursor(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_lagged_subscriber_cursor_skips_dropped_lines() {
+        let pipeline = LogsPipeline::new(options(10, 1024, 2));
+        let mut slow = pipeline.subscribe_live().await;
+        for i in 1..=5 {
+            pipeline.publish(format!("line{}", i)).await;
+        }
+
+        assert_eq!(slow.recv().await, Some(LogEvent::Dropped(3)));
+        assert_eq!(slow.cursor(), 3);
+        assert_eq!(slow.recv().await, Some(LogEvent::Line("line4".to_string())));
+        assert_eq!(slow.cursor(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_lines_are_redacted_before_subscribers() {
+        let pipeline = LogsPipeline::new(LogsPipelineOptions {
+            redaction: RedactionOptions {
+                ips: crate::xray::redact::IpRedaction::Mask,
+                ..RedactionOptions::default()
+            },
+            ..LogsPipelineOptions::default()
+        });
+        // Возвращенную строку нода пишет в свой лог, поэтому она тоже отредактирована
+        let published = pipeline.publish("from 203.0.113.45:5000 accepted".to_string()).await;
+        assert_eq!(&*published, "from 203.0.*.*:5000 accepted");
+
+        let mut logs = pipeline.subscribe().await;
+        assert_eq!(logs.recv().await, Some(LogEvent::Line("from 203.0.*.*:5000 accepted".to_string())));
+    }
+
     #[test]
-    fn test_temp_buffer_drop() {
-        let manager = TempLogsManager::new();
-        
-        {
-            let temp_buffer = manager.create_temp_buffer();
-            manager.push_to_all("test".to_string());
-            assert_eq!(temp_buffer.len(), 1);
-        } // temp_buffer должен удалиться здесь
-        
-        // Создаем новый буфер - старые логи не должны попасть в него
-        let new_buffer = manager.create_temp_buffer();
-        assert!(new_buffer.is_empty());
-    }
-} 
\ No newline at end of file
+    fn test_dropped_marker_text() {
+        assert_eq!(LogEvent::Dropped(7).into_text(), "[rustzban-node] 7 lines dropped");
+        assert_eq!(LogEvent::Line("x".to_string()).into_text(), "x");
+    }
+}
@@ -1,6 +1,26 @@
 pub mod process;
+pub mod api_client;
 pub mod config;
+pub mod crash;
+pub mod diff;
 pub mod logs;
+pub mod log_files;
+pub mod history;
+pub mod inbound_filter;
+pub mod launch;
+pub mod lint;
+pub mod log_level;
+pub mod model;
+pub mod overrides;
+pub mod pid_file;
+pub mod placeholders;
+pub mod redact;
+pub mod rewrite;
+pub mod startup;
+pub mod state;
+pub mod watchdog;
+#[cfg(test)]
+pub mod testing;
 
-pub use process::XrayCore;
+pub use process::{XrayCore, XrayCoreOptions};
 pub use config::XrayConfig; 
\ No newline at end of file
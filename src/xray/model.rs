@@ -0,0 +1,405 @@
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// Ошибка в присланной конфигурации: путь в JSON и причина
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigIssue {
+    /// Путь вида inbounds[2].port
+    pub path: String,
+    pub reason: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.reason)
+    }
+}
+
+/// Конфигурация Xray: известные секции типизированы, остальные поля сохраняются как есть
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct XrayConfigModel {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log: Option<Map<String, Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api: Option<ApiSection>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<Map<String, Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub policy: Option<Map<String, Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dns: Option<Map<String, Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub routing: Option<Routing>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inbounds: Option<Vec<Inbound>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outbounds: Option<Vec<Outbound>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ApiSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub services: Option<Vec<String>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Inbound {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<Port>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub settings: Option<Map<String, Value>>,
+    #[serde(rename = "streamSettings", skip_serializing_if = "Option::is_none")]
+    pub stream_settings: Option<Map<String, Value>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Outbound {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub settings: Option<Map<String, Value>>,
+    #[serde(rename = "streamSettings", skip_serializing_if = "Option::is_none")]
+    pub stream_settings: Option<Map<String, Value>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Routing {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rules: Option<Vec<RoutingRule>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balancers: Option<Vec<Balancer>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RoutingRule {
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub rule_type: Option<String>,
+    #[serde(rename = "inboundTag", skip_serializing_if = "Option::is_none")]
+    pub inbound_tag: Option<StringList>,
+    #[serde(rename = "outboundTag", skip_serializing_if = "Option::is_none")]
+    pub outbound_tag: Option<String>,
+    #[serde(rename = "balancerTag", skip_serializing_if = "Option::is_none")]
+    pub balancer_tag: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Balancer {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Порт inbound: число или строка ("1000-2000", "80,443", "env:PORT")
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Port {
+    Number(u16),
+    Text(String),
+}
+
+impl Serialize for Port {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Port::Number(port) => serializer.serialize_u16(*port),
+            Port::Text(port) => serializer.serialize_str(port),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Port {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Value::deserialize(deserializer)? {
+            Value::Number(number) => number
+                .as_u64()
+                .and_then(|port| u16::try_from(port).ok())
+                .map(Port::Number)
+                .ok_or_else(|| serde::de::Error::custom(format!("port {} is out of range 0..65535", number))),
+            Value::String(port) => Ok(Port::Text(port)),
+            other => Err(serde::de::Error::custom(format!("expected a port number or string, got {}", kind(&other)))),
+        }
+    }
+}
+
+/// Строка или список строк, как StringList в Xray; исходная форма сохраняется
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StringList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl StringList {
+    pub fn iter(&self) -> impl Iterator<Item = &String> {
+        match self {
+            StringList::One(value) => std::slice::from_ref(value).iter(),
+            StringList::Many(values) => values.iter(),
+        }
+    }
+}
+
+impl Serialize for StringList {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            StringList::One(value) => serializer.serialize_str(value),
+            StringList::Many(values) => values.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for StringList {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            One(String),
+            Many(Vec<String>),
+        }
+        match Raw::deserialize(deserializer) {
+            Ok(Raw::One(value)) => Ok(StringList::One(value)),
+            Ok(Raw::Many(values)) => Ok(StringList::Many(values)),
+            Err(_) => Err(serde::de::Error::custom("expected a string or an array of strings")),
+        }
+    }
+}
+
+impl XrayConfigModel {
+    /// Разбирает конфигурацию и собирает все ошибки в известных секциях
+    pub fn from_value(value: Value) -> Result<Self, Vec<ConfigIssue>> {
+        let mut parser = Parser::default();
+        let Some(mut map) = parser.object(value, "") else {
+            return Err(parser.issues);
+        };
+        let model = Self {
+            log: parser.field(&mut map, "", "log"),
+            api: parser.section(&mut map, "", "api", |parser, mut map, path| ApiSection {
+                tag: parser.field(&mut map, path, "tag"),
+                services: parser.field(&mut map, path, "services"),
+                extra: map,
+            }),
+            stats: parser.field(&mut map, "", "stats"),
+            policy: parser.field(&mut map, "", "policy"),
+            dns: parser.field(&mut map, "", "dns"),
+            routing: parser.section(&mut map, "", "routing", Routing::parse),
+            inbounds: parser.list(&mut map, "", "inbounds", Inbound::parse),
+            outbounds: parser.list(&mut map, "", "outbounds", Outbound::parse),
+            extra: map,
+        };
+        match parser.issues.is_empty() {
+            true => Ok(model),
+            false => Err(parser.issues),
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+}
+
+impl Inbound {
+    fn parse(parser: &mut Parser, mut map: Map<String, Value>, path: &str) -> Self {
+        Self {
+            tag: parser.field(&mut map, path, "tag"),
+            listen: parser.field(&mut map, path, "listen"),
+            port: parser.field(&mut map, path, "port"),
+            protocol: parser.field(&mut map, path, "protocol"),
+            settings: parser.field(&mut map, path, "settings"),
+            stream_settings: parser.field(&mut map, path, "streamSettings"),
+            extra: map,
+        }
+    }
+}
+
+impl Outbound {
+    fn parse(parser: &mut Parser, mut map: Map<String, Value>, path: &str) -> Self {
+        Self {
+            tag: parser.field(&mut map, path, "tag"),
+            protocol: parser.field(&mut map, path, "protocol"),
+            settings: parser.field(&mut map, path, "settings"),
+            stream_settings: parser.field(&mut map, path, "streamSettings"),
+            extra: map,
+        }
+    }
+}
+
+impl Routing {
+    fn parse(parser: &mut Parser, mut map: Map<String, Value>, path: &str) -> Self {
+        Self {
+            rules: parser.list(&mut map, path, "rules", |parser, mut map, path| RoutingRule {
+                rule_type: parser.field(&mut map, path, "type"),
+                inbound_tag: parser.field(&mut map, path, "inboundTag"),
+                outbound_tag: parser.field(&mut map, path, "outboundTag"),
+                balancer_tag: parser.field(&mut map, path, "balancerTag"),
+                extra: map,
+            }),
+            balancers: parser.list(&mut map, path, "balancers", |parser, mut map, path| Balancer {
+                tag: parser.field(&mut map, path, "tag"),
+                extra: map,
+            }),
+            extra: map,
+        }
+    }
+}
+
+/// Разбор по полям: ошибка в одном поле не мешает проверить остальные
+#[derive(Default)]
+struct Parser {
+    issues: Vec<ConfigIssue>,
+}
+
+impl Parser {
+    fn issue(&mut self, path: String, reason: impl Into<String>) {
+        self.issues.push(ConfigIssue {
+            path: if path.is_empty() { "$".to_string() } else { path },
+            reason: reason.into(),
+        });
+    }
+
+    fn object(&mut self, value: Value, path: &str) -> Option<Map<String, Value>> {
+        match value {
+            Value::Object(map) => Some(map),
+            other => {
+                self.issue(path.to_string(), format!("expected an object, got {}", kind(&other)));
+                None
+            }
+        }
+    }
+
+    /// Типизированное поле; null считается отсутствующим
+    fn field<T: DeserializeOwned>(&mut self, map: &mut Map<String, Value>, path: &str, key: &str) -> Option<T> {
+        let value = map.remove(key).filter(|value| !value.is_null())?;
+        let path = join(path, key);
+        match serde_path_to_error::deserialize(value) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                let inner = e.path().to_string();
+                let path = match inner.as_str() {
+                    "." => path,
+                    inner if inner.starts_with('[') => format!("{}{}", path, inner),
+                    inner => format!("{}.{}", path, inner),
+                };
+                self.issue(path, e.into_inner().to_string());
+                None
+            }
+        }
+    }
+
+    /// Секция-объект, поля которой разбирает parse
+    fn section<T>(
+        &mut self,
+        map: &mut Map<String, Value>,
+        path: &str,
+        key: &str,
+        parse: impl Fn(&mut Self, Map<String, Value>, &str) -> T,
+    ) -> Option<T> {
+        let value = map.remove(key).filter(|value| !value.is_null())?;
+        let path = join(path, key);
+        let map = self.object(value, &path)?;
+        Some(parse(self, map, &path))
+    }
+
+    /// Массив объектов; каждый элемент проверяется отдельно
+    fn list<T>(
+        &mut self,
+        map: &mut Map<String, Value>,
+        path: &str,
+        key: &str,
+        parse: impl Fn(&mut Self, Map<String, Value>, &str) -> T,
+    ) -> Option<Vec<T>> {
+        let value = map.remove(key).filter(|value| !value.is_null())?;
+        let path = join(path, key);
+        let Value::Array(items) = value else {
+            self.issue(path, format!("expected an array, got {}", kind(&value)));
+            return None;
+        };
+        let mut parsed = Vec::with_capacity(items.len());
+        for (index, item) in items.into_iter().enumerate() {
+            let path = format!("{}[{}]", path, index);
+            if let Some(map) = self.object(item, &path) {
+                parsed.push(parse(self, map, &path));
+            }
+        }
+        Some(parsed)
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    match path {
+        "" => key.to_string(),
+        path => format!("{}.{}", path, key),
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_round_trip_keeps_unknown_fields() {
+        let value = json!({
+            "log": {"loglevel": "warning"},
+            "inbounds": [{"tag": "in", "port": "1000-2000", "protocol": "vless", "sniffing": {"enabled": true}}],
+            "outbounds": [{"protocol": "freedom", "mux": {"enabled": false}}],
+            "routing": {"domainStrategy": "AsIs", "rules": [{"type": "field", "inboundTag": "in", "outboundTag": "direct"}]},
+            "transport": {}
+        });
+        let model = XrayConfigModel::from_value(value.clone()).unwrap();
+        assert_eq!(model.inbounds.as_ref().unwrap()[0].port, Some(Port::Text("1000-2000".to_string())));
+        assert_eq!(model.to_value(), value);
+    }
+
+    #[test]
+    fn test_issues_have_paths() {
+        let value = json!({
+            "inbounds": [
+                {"tag": "ok", "port": 443},
+                {"tag": 5, "port": 70000},
+                "vless"
+            ],
+            "outbounds": {"protocol": "freedom"},
+            "routing": {"rules": [{"inboundTag": [1]}]}
+        });
+        let issues = XrayConfigModel::from_value(value).unwrap_err();
+        let paths: Vec<&str> = issues.iter().map(|issue| issue.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec!["routing.rules[0].inboundTag", "inbounds[1].tag", "inbounds[1].port", "inbounds[2]", "outbounds"]
+        );
+        assert_eq!(issues[2].reason, "port 70000 is out of range 0..65535");
+        assert_eq!(issues[4].reason, "expected an array, got an object");
+        assert!(XrayConfigModel::from_value(json!([])).is_err());
+    }
+}
@@ -0,0 +1,311 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{fs, path::Path};
+
+use crate::xray::model::ConfigIssue;
+
+/// Применение локальных патчей ноды (CONFIG_OVERRIDES_DIR)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OverridesReport {
+    /// Примененные файлы в порядке применения
+    pub applied: Vec<String>,
+    /// Пути, которые меняли несколько патчей; действует последний
+    pub conflicts: Vec<OverrideConflict>,
+}
+
+/// Путь, который изменили два патча
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OverrideConflict {
+    /// JSON Pointer пути, измененного позже
+    pub path: String,
+    /// Патч, изменение которого перекрыто
+    pub overridden: String,
+    /// Патч, изменение которого действует
+    pub by: String,
+}
+
+/// Операция JSON Patch (RFC 6902)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum PatchOperation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+impl PatchOperation {
+    /// Пути, которые операция меняет
+    fn touched(&self) -> Vec<&str> {
+        match self {
+            PatchOperation::Add { path, .. }
+            | PatchOperation::Remove { path }
+            | PatchOperation::Replace { path, .. }
+            | PatchOperation::Copy { path, .. } => vec![path],
+            PatchOperation::Move { from, path } => vec![from, path],
+            PatchOperation::Test { .. } => Vec::new(),
+        }
+    }
+
+    fn apply(&self, config: &mut Value) -> Result<(), String> {
+        match self {
+            PatchOperation::Add { path, value } => add(config, path, value.clone()),
+            PatchOperation::Remove { path } => remove(config, path).map(|_| ()),
+            PatchOperation::Replace { path, value } => {
+                *pointer_mut(config, path)? = value.clone();
+                Ok(())
+            }
+            PatchOperation::Move { from, path } => {
+                if path.starts_with(&format!("{}/", from)) {
+                    return Err(format!("cannot move {} into itself", from));
+                }
+                let value = remove(config, from)?;
+                add(config, path, value)
+            }
+            PatchOperation::Copy { from, path } => {
+                let value = config.pointer(from).cloned().ok_or_else(|| missing(from))?;
+                add(config, path, value)
+            }
+            PatchOperation::Test { path, value } => match config.pointer(path) {
+                Some(actual) if actual == value => Ok(()),
+                Some(_) => Err(format!("test failed: {} has a different value", path)),
+                None => Err(missing(path)),
+            },
+        }
+    }
+}
+
+/// Документ из каталога: объект - JSON Merge Patch (RFC 7386), массив - JSON Patch (RFC 6902)
+enum PatchDocument {
+    Merge(Value),
+    Json(Vec<PatchOperation>),
+}
+
+/// Применяет файлы *.json из каталога в порядке имен
+pub fn apply_overrides(config: &mut Value, dir: &Path) -> Result<OverridesReport, Vec<ConfigIssue>> {
+    let entries = fs::read_dir(dir).map_err(|e| vec![issue(dir.display().to_string(), e.to_string())])?;
+    let mut names: Vec<String> = entries
+        .flatten()
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".json"))
+        .collect();
+    names.sort();
+
+    let mut report = OverridesReport::default();
+    // Измененные пути и патчи, которые их изменили
+    let mut touched: Vec<(String, String)> = Vec::new();
+    for name in names {
+        let path = format!("overrides/{}", name);
+        let document = read_document(&dir.join(&name)).map_err(|reason| vec![issue(path.clone(), reason)])?;
+
+        let paths = match document {
+            PatchDocument::Merge(patch) => {
+                let mut paths = Vec::new();
+                leaf_paths(&patch, String::new(), &mut paths);
+                merge_patch(config, &patch);
+                paths
+            }
+            PatchDocument::Json(operations) => {
+                for (index, operation) in operations.iter().enumerate() {
+                    operation
+                        .apply(config)
+                        .map_err(|reason| vec![issue(format!("{}[{}]", path, index), reason)])?;
+                }
+                operations
+                    .iter()
+                    .flat_map(PatchOperation::touched)
+                    // Добавление в конец массива не перекрывает другие добавления
+                    .filter(|path| !path.ends_with("/-"))
+                    .map(str::to_string)
+                    .collect()
+            }
+        };
+
+        for changed in paths {
+            for (earlier, patch) in &touched {
+                if *patch != name && overlaps(earlier, &changed) {
+                    report.conflicts.push(OverrideConflict {
+                        path: changed.clone(),
+                        overridden: patch.clone(),
+                        by: name.clone(),
+                    });
+                }
+            }
+            touched.push((changed, name.clone()));
+        }
+        report.applied.push(name);
+    }
+    Ok(report)
+}
+
+fn read_document(path: &Path) -> Result<PatchDocument, String> {
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    match serde_json::from_str(&content).map_err(|e| e.to_string())? {
+        Value::Array(operations) => serde_json::from_value(Value::Array(operations))
+            .map(PatchDocument::Json)
+            .map_err(|e| format!("invalid JSON Patch: {}", e)),
+        patch @ Value::Object(_) => Ok(PatchDocument::Merge(patch)),
+        _ => Err("expected a JSON Merge Patch object or a JSON Patch array".to_string()),
+    }
+}
+
+/// JSON Merge Patch: null удаляет ключ, объекты сливаются, остальное заменяется
+fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+/// Пути значений, которые заменяет merge patch
+fn leaf_paths(patch: &Value, prefix: String, paths: &mut Vec<String>) {
+    match patch {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                leaf_paths(value, format!("{}/{}", prefix, escape(key)), paths);
+            }
+        }
+        _ => paths.push(prefix),
+    }
+}
+
+/// Один путь вложен в другой или совпадает с ним
+fn overlaps(a: &str, b: &str) -> bool {
+    a == b || a.starts_with(&format!("{}/", b)) || b.starts_with(&format!("{}/", a))
+}
+
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+fn unescape(token: &str) -> String {
+    token.replace("~1", "/").replace("~0", "~")
+}
+
+fn missing(path: &str) -> String {
+    format!("path {} does not exist", path)
+}
+
+fn pointer_mut<'a>(config: &'a mut Value, path: &str) -> Result<&'a mut Value, String> {
+    config.pointer_mut(path).ok_or_else(|| missing(path))
+}
+
+/// Родитель пути и последний токен
+fn split(path: &str) -> Result<(&str, String), String> {
+    let (parent, last) = path
+        .rsplit_once('/')
+        .ok_or_else(|| format!("invalid JSON Pointer '{}'", path))?;
+    Ok((parent, unescape(last)))
+}
+
+fn add(config: &mut Value, path: &str, value: Value) -> Result<(), String> {
+    if path.is_empty() {
+        *config = value;
+        return Ok(());
+    }
+    let (parent, key) = split(path)?;
+    match pointer_mut(config, parent)? {
+        Value::Object(map) => {
+            map.insert(key, value);
+            Ok(())
+        }
+        Value::Array(items) if key == "-" => {
+            items.push(value);
+            Ok(())
+        }
+        Value::Array(items) => match key.parse::<usize>() {
+            Ok(index) if index <= items.len() => {
+                items.insert(index, value);
+                Ok(())
+            }
+            _ => Err(format!("invalid array index in {}", path)),
+        },
+        _ => Err(format!("parent of {} is not an object or array", path)),
+    }
+}
+
+fn remove(config: &mut Value, path: &str) -> Result<Value, String> {
+    let (parent, key) = split(path)?;
+    match pointer_mut(config, parent)? {
+        Value::Object(map) => map.remove(&key).ok_or_else(|| missing(path)),
+        Value::Array(items) => match key.parse::<usize>() {
+            Ok(index) if index < items.len() => Ok(items.remove(index)),
+            _ => Err(missing(path)),
+        },
+        _ => Err(missing(path)),
+    }
+}
+
+fn issue(path: String, reason: String) -> ConfigIssue {
+    ConfigIssue { path, reason }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn write(dir: &tempfile::TempDir, name: &str, value: Value) {
+        fs::write(dir.path().join(name), value.to_string()).unwrap();
+    }
+
+    #[test]
+    fn test_patches_are_applied_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        write(&dir, "20-block.json", json!([
+            {"op": "add", "path": "/outbounds/-", "value": {"tag": "blocked", "protocol": "blackhole"}},
+            {"op": "add", "path": "/routing/rules/0", "value": {"ip": ["10.0.0.0/8"], "outboundTag": "blocked"}}
+        ]));
+        write(&dir, "10-dns.json", json!({"dns": {"servers": ["1.1.1.1"]}, "log": null}));
+        write(&dir, "30-dns.json", json!({"dns": {"servers": ["8.8.8.8"]}}));
+        fs::write(dir.path().join("README"), "not a patch").unwrap();
+
+        let mut config = json!({
+            "log": {"loglevel": "warning"},
+            "outbounds": [{"tag": "direct"}],
+            "routing": {"rules": [{"outboundTag": "direct"}]}
+        });
+        let report = apply_overrides(&mut config, dir.path()).unwrap();
+
+        assert_eq!(report.applied, vec!["10-dns.json", "20-block.json", "30-dns.json"]);
+        assert_eq!(config["dns"]["servers"], json!(["8.8.8.8"]));
+        assert!(config.get("log").is_none());
+        assert_eq!(config["outbounds"][1]["tag"], "blocked");
+        assert_eq!(config["routing"]["rules"][0]["outboundTag"], "blocked");
+        assert_eq!(report.conflicts, vec![OverrideConflict {
+            path: "/dns/servers".to_string(),
+            overridden: "10-dns.json".to_string(),
+            by: "30-dns.json".to_string(),
+        }]);
+    }
+
+    #[test]
+    fn test_failed_operation_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        write(&dir, "10-remove.json", json!([
+            {"op": "test", "path": "/log/loglevel", "value": "warning"},
+            {"op": "remove", "path": "/dns/servers/3"}
+        ]));
+        let mut config = json!({"log": {"loglevel": "warning"}, "dns": {"servers": []}});
+        let issues = apply_overrides(&mut config, dir.path()).unwrap_err();
+        assert_eq!(issues[0].to_string(), "overrides/10-remove.json[1]: path /dns/servers/3 does not exist");
+
+        write(&dir, "10-remove.json", json!([{"op": "increment", "path": "/x"}]));
+        let issues = apply_overrides(&mut config, dir.path()).unwrap_err();
+        assert!(issues[0].reason.starts_with("invalid JSON Patch"), "{}", issues[0]);
+    }
+}
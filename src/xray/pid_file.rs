@@ -0,0 +1,180 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Что делать с процессом Xray, оставшимся от предыдущего запуска ноды (ORPHAN_POLICY)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OrphanPolicy {
+    /// Завершить процесс до приема команд панели
+    #[default]
+    Terminate,
+    /// Продолжить управлять процессом как запущенным ядром
+    Adopt,
+}
+
+impl FromStr for OrphanPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "terminate" => Ok(OrphanPolicy::Terminate),
+            "adopt" => Ok(OrphanPolicy::Adopt),
+            other => Err(format!("unknown orphan policy '{}', expected terminate or adopt", other)),
+        }
+    }
+}
+
+impl fmt::Display for OrphanPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OrphanPolicy::Terminate => write!(f, "terminate"),
+            OrphanPolicy::Adopt => write!(f, "adopt"),
+        }
+    }
+}
+
+/// Запись о запущенном процессе Xray
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PidRecord {
+    pub pid: u32,
+    /// Время старта процесса из /proc/<pid>/stat, защищает от повторного использования PID
+    pub start_time: u64,
+    /// Путь к исполняемому файлу Xray
+    pub executable: String,
+    /// XrayConfig::canonical_hash конфигурации, с которой запущен процесс
+    pub config_hash: String,
+    pub started_at: DateTime<Utc>,
+}
+
+impl PidRecord {
+    /// Запись для только что запущенного процесса
+    pub fn new(pid: u32, executable: &str, config_hash: String) -> Option<Self> {
+        Some(Self {
+            pid,
+            start_time: process_start_time(pid)?,
+            executable: executable.to_string(),
+            config_hash,
+            started_at: Utc::now(),
+        })
+    }
+
+    /// Тот же ли это процесс, что был записан: жив, не зомби, время старта и команда совпадают
+    pub fn is_alive(&self) -> bool {
+        let Some(stat) = read_stat(self.pid) else {
+            return false;
+        };
+        if stat.state == 'Z' || stat.start_time != self.start_time {
+            return false;
+        }
+        fs::read(format!("/proc/{}/cmdline", self.pid))
+            .map(|cmdline| {
+                cmdline
+                    .split(|byte| *byte == 0)
+                    .any(|arg| arg == self.executable.as_bytes())
+            })
+            .unwrap_or(false)
+    }
+}
+
+/// Файл с записью о запущенном процессе в STATE_DIR
+#[derive(Debug, Clone)]
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    pub fn new(state_dir: &Path) -> Self {
+        Self {
+            path: state_dir.join("xray.pid.json"),
+        }
+    }
+
+    pub fn write(&self, record: &PidRecord) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Пишем во временный файл и переименовываем, чтобы не оставить половину записи
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec(record)?)?;
+        fs::rename(tmp, &self.path)
+    }
+
+    pub fn read(&self) -> Option<PidRecord> {
+        let data = fs::read(&self.path).ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    pub fn remove(&self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Поля /proc/<pid>/stat, нужные для проверки процесса
+struct ProcStat {
+    state: char,
+    start_time: u64,
+}
+
+fn read_stat(pid: u32) -> Option<ProcStat> {
+    parse_stat(&fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?)
+}
+
+/// Имя процесса в скобках может содержать пробелы, поэтому поля считаются после последней ')'
+fn parse_stat(stat: &str) -> Option<ProcStat> {
+    let fields: Vec<&str> = stat.get(stat.rfind(')')? + 1..)?.split_whitespace().collect();
+    Some(ProcStat {
+        // Поле 3 - состояние, поле 22 - время старта
+        state: fields.first()?.chars().next()?,
+        start_time: fields.get(19)?.parse().ok()?,
+    })
+}
+
+/// Время старта процесса в тиках с загрузки системы
+pub fn process_start_time(pid: u32) -> Option<u64> {
+    read_stat(pid).map(|stat| stat.start_time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stat() {
+        let stat = "1234 (xray (main)) S 1 1234 1234 0 -1 4194560 1 0 0 0 0 0 0 0 20 0 8 0 987654 0 0";
+        let stat = parse_stat(stat).unwrap();
+        assert_eq!(stat.state, 'S');
+        assert_eq!(stat.start_time, 987654);
+        assert!(parse_stat("garbage").is_none());
+    }
+
+    #[test]
+    fn test_pid_file_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = PidFile::new(dir.path());
+        assert!(pid_file.read().is_none());
+
+        let pid = std::process::id();
+        let executable = std::env::args().next().unwrap();
+        let record = PidRecord::new(pid, &executable, "hash".to_string()).unwrap();
+        pid_file.write(&record).unwrap();
+        assert_eq!(pid_file.read(), Some(record.clone()));
+
+        // Текущий процесс жив, а запись с другим временем старта - уже не он
+        assert!(record.is_alive());
+        assert!(!PidRecord { start_time: record.start_time + 1, ..record.clone() }.is_alive());
+        assert!(!PidRecord { executable: "/usr/local/bin/xray".to_string(), ..record }.is_alive());
+
+        pid_file.remove();
+        assert!(pid_file.read().is_none());
+    }
+
+    #[test]
+    fn test_orphan_policy_parsing() {
+        assert_eq!("adopt".parse::<OrphanPolicy>().unwrap(), OrphanPolicy::Adopt);
+        assert!("ignore".parse::<OrphanPolicy>().is_err());
+    }
+}
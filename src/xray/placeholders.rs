@@ -0,0 +1,184 @@
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::config::Config as AppConfig;
+use crate::xray::model::ConfigIssue;
+
+/// Переменные ноды для плейсхолдеров `${NAME}` и `${env:NAME}` в конфигурации панели
+#[derive(Debug, Clone)]
+pub struct NodeVariables {
+    /// Встроенные переменные; None - переменная известна, но на этой ноде не задана
+    builtin: HashMap<&'static str, Option<String>>,
+    /// Переменные окружения, доступные через `${env:NAME}` (CONFIG_ENV_ALLOW)
+    env_allow: Vec<String>,
+}
+
+impl NodeVariables {
+    pub fn new(app_config: &AppConfig) -> Self {
+        let builtin = HashMap::from([
+            ("NODE_PUBLIC_IP", app_config.node_public_ip.clone()),
+            ("NODE_HOSTNAME", hostname()),
+        ]);
+        let env_allow = app_config
+            .config_env_allow
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect();
+        Self { builtin, env_allow }
+    }
+
+    /// Подставляет переменные во все строковые значения; `$${NAME}` остается литералом `${NAME}`.
+    /// `${` без имени вида `NAME` или `env:NAME` не считается плейсхолдером и не меняется
+    pub fn resolve(&self, config: &mut Value) -> Result<(), Vec<ConfigIssue>> {
+        let mut issues = Vec::new();
+        self.resolve_value(config, String::new(), &mut issues);
+        if issues.is_empty() { Ok(()) } else { Err(issues) }
+    }
+
+    fn resolve_value(&self, value: &mut Value, path: String, issues: &mut Vec<ConfigIssue>) {
+        match value {
+            Value::String(text) if text.contains("${") => match self.substitute(text) {
+                Ok(resolved) => *text = resolved,
+                Err(reason) => issues.push(ConfigIssue {
+                    path: if path.is_empty() { "$".to_string() } else { path },
+                    reason,
+                }),
+            },
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    let path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                    self.resolve_value(value, path, issues);
+                }
+            }
+            Value::Array(items) => {
+                for (index, item) in items.iter_mut().enumerate() {
+                    self.resolve_value(item, format!("{}[{}]", path, index), issues);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn substitute(&self, text: &str) -> Result<String, String> {
+        let mut resolved = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("${") {
+            let after = &rest[start + 2..];
+            let name = after.find('}').map(|end| &after[..end]).filter(|name| is_placeholder(name));
+            let Some(name) = name else {
+                // Не плейсхолдер, например `${` в пароле клиента
+                resolved.push_str(&rest[..start + 2]);
+                rest = after;
+                continue;
+            };
+            if rest[..start].ends_with('$') {
+                // `$${NAME}` экранирует плейсхолдер
+                resolved.push_str(&rest[..start - 1]);
+                resolved.push_str(&rest[start..start + name.len() + 3]);
+            } else {
+                resolved.push_str(&rest[..start]);
+                resolved.push_str(&self.lookup(name)?);
+            }
+            rest = &after[name.len() + 1..];
+        }
+        resolved.push_str(rest);
+        Ok(resolved)
+    }
+
+    fn lookup(&self, name: &str) -> Result<String, String> {
+        if let Some(env_name) = name.strip_prefix("env:") {
+            if !self.env_allow.iter().any(|allowed| allowed == env_name) {
+                return Err(format!("${{{}}}: {} is not in CONFIG_ENV_ALLOW", name, env_name));
+            }
+            return std::env::var(env_name).map_err(|_| format!("${{{}}}: {} is not set on the node", name, env_name));
+        }
+        match self.builtin.get(name) {
+            Some(Some(value)) => Ok(value.clone()),
+            Some(None) => Err(format!("${{{}}}: {} is not set on the node", name, name)),
+            None => Err(format!("${{{}}}: unknown variable", name)),
+        }
+    }
+}
+
+/// Имя плейсхолдера: `env:NAME` или идентификатор из `A-Z`, `0-9` и `_`
+fn is_placeholder(name: &str) -> bool {
+    name.starts_with("env:")
+        || (!name.is_empty() && name.chars().all(|ch| ch.is_ascii_uppercase() || ch.is_ascii_digit() || ch == '_'))
+}
+
+/// Имя хоста ноды
+fn hostname() -> Option<String> {
+    let mut buffer = [0u8; 256];
+    // gethostname пишет не больше buffer.len() байт в наш буфер
+    let result = unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) };
+    if result != 0 {
+        return None;
+    }
+    let len = buffer.iter().position(|&byte| byte == 0).unwrap_or(buffer.len());
+    String::from_utf8(buffer[..len].to_vec()).ok().filter(|name| !name.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn variables() -> NodeVariables {
+        NodeVariables::new(&AppConfig {
+            node_public_ip: Some("203.0.113.7".to_string()),
+            config_env_allow: "PATH, XRAY_NODE_UNSET_VARIABLE".to_string(),
+            ..AppConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_placeholders_are_resolved() {
+        let mut config = json!({
+            "inbounds": [{"listen": "${NODE_PUBLIC_IP}", "settings": {"fallbacks": [{"dest": "${NODE_PUBLIC_IP}:80"}]}}],
+            "log": {"access": "${env:PATH}", "error": "$${NODE_PUBLIC_IP}"},
+            "stats": {"count": 1}
+        });
+        variables().resolve(&mut config).unwrap();
+        assert_eq!(config["inbounds"][0]["listen"], "203.0.113.7");
+        assert_eq!(config["inbounds"][0]["settings"]["fallbacks"][0]["dest"], "203.0.113.7:80");
+        assert_eq!(config["log"]["access"], std::env::var("PATH").unwrap());
+        assert_eq!(config["log"]["error"], "${NODE_PUBLIC_IP}");
+    }
+
+    #[test]
+    fn test_other_placeholders_pass_through() {
+        let passwords = ["p@${ss", "a${b}c$${d}", "x${NODE-IP}y", "${NODE_PUBLIC_IP", "${}"];
+        let mut config = json!({
+            "inbounds": [{"listen": "${NODE_PUBLIC_IP}", "settings": {"clients": passwords.map(|password| json!({"password": password}))}}]
+        });
+        variables().resolve(&mut config).unwrap();
+        assert_eq!(config["inbounds"][0]["listen"], "203.0.113.7");
+        for (index, password) in passwords.iter().enumerate() {
+            assert_eq!(config["inbounds"][0]["settings"]["clients"][index]["password"], *password);
+        }
+    }
+
+    #[test]
+    fn test_unresolved_placeholders_are_reported() {
+        let mut config = json!({
+            "inbounds": [{"listen": "${NODE_IP}"}, {"tag": "${env:HOME}"}],
+            "log": {"access": "${env:XRAY_NODE_UNSET_VARIABLE}", "error": "/var/log/${NODE_PUBLC_IP}.log"}
+        });
+        let issues = variables().resolve(&mut config).unwrap_err();
+        let mut issues: Vec<String> = issues.iter().map(ToString::to_string).collect();
+        issues.sort();
+        assert_eq!(issues, vec![
+            "inbounds[0].listen: ${NODE_IP}: unknown variable",
+            "inbounds[1].tag: ${env:HOME}: HOME is not in CONFIG_ENV_ALLOW",
+            "log.access: ${env:XRAY_NODE_UNSET_VARIABLE}: XRAY_NODE_UNSET_VARIABLE is not set on the node",
+            "log.error: ${NODE_PUBLC_IP}: unknown variable",
+        ]);
+
+        // Встроенная переменная без значения тоже ошибка
+        let mut config = json!({"listen": "${NODE_PUBLIC_IP}"});
+        let issues = NodeVariables::new(&AppConfig::default()).resolve(&mut config).unwrap_err();
+        assert_eq!(issues[0].reason, "${NODE_PUBLIC_IP}: NODE_PUBLIC_IP is not set on the node");
+    }
+}
@@ -1,20 +1,178 @@
 use std::{
     collections::HashMap,
+    path::PathBuf,
     process::Stdio,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
 };
 use tokio::{
     io::{AsyncBufReadExt, BufReader},
     process::{Child as TokioChild, Command as TokioCommand},
-    sync::RwLock,
+    sync::{watch, RwLock},
     task::JoinHandle,
 };
+use chrono::{DateTime, Utc};
 use tracing::{debug, warn};
+use crate::config::Config as AppConfig;
+use crate::xray::api_client::XrayApiClient;
 use crate::xray::config::XrayConfig;
-use crate::xray::logs::LogsBuffer;
+use crate::xray::crash::{CrashRecord, CrashStore};
+use crate::xray::diff::{plan, ApplyPlan, ChangeSummary, InboundChanges};
+use crate::xray::history::{ConfigHistory, RollbackReport};
+use crate::xray::log_files::{spawn_log_file_sink, LogFilesOptions};
+use crate::xray::log_level::{LogLevelControl, XrayLogLevel};
+use crate::xray::logs::{LogsPipeline, LogsPipelineOptions};
+use crate::xray::redact::RedactionOptions;
+use crate::xray::startup::{StartupFailure, STARTUP_LOG_TAIL_LINES};
+use crate::xray::state::CoreState;
+use crate::xray::launch::LaunchOptions;
+use crate::xray::pid_file::{OrphanPolicy, PidFile, PidRecord};
+use crate::xray::watchdog::{run_watchdog, ProcessResources, WatchdogOptions};
+use crate::xray::logs::LogEvent;
+
+/// Баннер, по которому определяется успешный запуск (как в Python)
+pub const DEFAULT_READY_PATTERN: &str = r"Xray \S+ started";
+
+/// Сколько ждать одну команду `xray api` при инкрементальном применении
+const API_COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// Callback функция для событий start/stop (аналог Python версии)
-pub type EventCallback = Box<dyn Fn() + Send + Sync>;
+pub type EventCallback = Arc<dyn Fn() + Send + Sync>;
+
+/// Настройки XrayCore, не относящиеся к конкретному запуску
+#[derive(Debug, Clone)]
+pub struct XrayCoreOptions {
+    /// Настройки конвейера логов
+    pub logs: LogsPipelineOptions,
+    /// Настройки хранения логов на диске (None - хранение выключено)
+    pub log_files: Option<LogFilesOptions>,
+    /// Минимальный уровень запуска Xray, до которого уровень можно поднять без перезапуска
+    pub runtime_log_level: XrayLogLevel,
+    /// Сколько ждать баннера запуска
+    pub startup_timeout: Duration,
+    /// Регулярное выражение строки, после которой ядро считается запущенным
+    pub ready_pattern: String,
+    /// Сколько ждать завершения после SIGTERM перед SIGKILL
+    pub stop_timeout: Duration,
+    /// Каталог для PID файла запущенного ядра (None - не записывать)
+    pub state_dir: Option<PathBuf>,
+    /// Окружение, аргументы и ограничения процесса
+    pub launch: LaunchOptions,
+    /// Сторож памяти, файловых дескрипторов и потоков
+    pub watchdog: WatchdogOptions,
+    /// Сколько записей о падениях хранить в STATE_DIR
+    pub crash_reports_max: usize,
+    /// Сколько строк лога сохранять в записи о падении
+    pub crash_log_lines: usize,
+    /// Сколько успешно примененных конфигураций хранить для отката
+    pub config_history_size: usize,
+    /// Локальный порт API для применения изменений без перезапуска (0 - выключено)
+    pub control_api_port: u16,
+}
+
+impl Default for XrayCoreOptions {
+    fn default() -> Self {
+        Self {
+            logs: LogsPipelineOptions::default(),
+            log_files: None,
+            runtime_log_level: XrayLogLevel::default(),
+            startup_timeout: Duration::from_secs(3),
+            ready_pattern: DEFAULT_READY_PATTERN.to_string(),
+            stop_timeout: Duration::from_secs(5),
+            state_dir: None,
+            launch: LaunchOptions::default(),
+            watchdog: WatchdogOptions::default(),
+            crash_reports_max: 20,
+            crash_log_lines: 100,
+            config_history_size: 5,
+            control_api_port: 0,
+        }
+    }
+}
+
+impl From<&AppConfig> for XrayCoreOptions {
+    fn from(config: &AppConfig) -> Self {
+        Self {
+            logs: LogsPipelineOptions {
+                buffer_lines: config.logs_buffer_lines,
+                buffer_bytes: config.logs_buffer_bytes,
+                subscriber_queue: config.logs_subscriber_queue,
+                redaction: RedactionOptions {
+                    // Значение проверено в Config::validate
+                    ips: config.logs_redact_ips.parse().unwrap_or_default(),
+                    ipv4_prefix: config.logs_redact_ipv4_prefix,
+                    ipv6_prefix: config.logs_redact_ipv6_prefix,
+                    emails: config.logs_redact_emails,
+                    destinations: config.logs_redact_destinations,
+                    // Без явной соли хеши стабильны только в пределах процесса
+                    salt: config.logs_redact_salt.clone()
+                        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+                },
+            },
+            log_files: config.logs_dir.as_ref().map(|dir| LogFilesOptions {
+                dir: PathBuf::from(dir),
+                max_file_bytes: config.logs_file_max_bytes,
+                rotate_interval: Duration::from_secs(config.logs_file_rotate_secs),
+                compress: config.logs_file_compress,
+                max_files: config.logs_file_max_count,
+                max_age: Duration::from_secs(config.logs_file_max_age_secs),
+            }),
+            // Значение проверено в Config::validate
+            runtime_log_level: config.xray_runtime_log_level.parse().unwrap_or_default(),
+            startup_timeout: Duration::from_secs(config.xray_startup_timeout_secs),
+            ready_pattern: config.xray_ready_pattern.clone(),
+            stop_timeout: Duration::from_secs(config.xray_stop_timeout_secs),
+            state_dir: config.state_dir.as_ref().map(PathBuf::from),
+            launch: LaunchOptions::from(config),
+            watchdog: WatchdogOptions::from(config),
+            crash_reports_max: config.crash_reports_max,
+            crash_log_lines: config.crash_log_lines,
+            config_history_size: config.config_history_size,
+            control_api_port: config.xray_control_api_port,
+        }
+    }
+}
+
+/// Ошибки запуска Xray
+#[derive(Debug, thiserror::Error)]
+pub enum XrayStartError {
+    /// Процесс не удалось запустить или остановить
+    #[error("{0}")]
+    Core(String),
+    
+    /// Процесс запущен, но Xray не поднялся
+    #[error("{0}")]
+    Startup(Box<StartupFailure>),
+}
+
+impl From<Box<dyn std::error::Error>> for XrayStartError {
+    fn from(error: Box<dyn std::error::Error>) -> Self {
+        XrayStartError::Core(error.to_string())
+    }
+}
+
+/// Как новая конфигурация была применена к ядру
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApplyOutcome {
+    /// Ядро уже работает с этой конфигурацией
+    Unchanged,
+    /// Изменения inbounds и пользователей применены без перезапуска
+    Updated(ChangeSummary),
+    /// Ядро перезапущено, в сводке причина
+    Restarted(ChangeSummary),
+}
+
+/// Конфигурация, которую получил работающий Xray (после преобразований ноды)
+#[derive(Debug, Clone)]
+pub struct EffectiveConfig {
+    pub config: XrayConfig,
+    /// Хеш конфигурации до подготовки уровня логов, как в PID файле и истории
+    pub config_hash: String,
+    pub applied_at: DateTime<Utc>,
+}
 
 /// Основная структура для управления Xray процессом
 /// Идентична классу XRayCore из xray.py Python версии
@@ -31,11 +189,63 @@ pub struct XrayCore {
     /// Xray process (process в Python)
     process: Arc<RwLock<Option<TokioChild>>>,
     
-    /// Флаг перезапуска (restarting в Python)
-    restarting: Arc<RwLock<bool>>,
+    /// Запущенный процесс: свой или принятый от предыдущего запуска ноды
+    running: RwLock<Option<PidRecord>>,
+    
+    /// PID файл запущенного ядра в STATE_DIR
+    pid_file: Option<PidFile>,
+    
+    /// Записи о неожиданных завершениях Xray в STATE_DIR
+    crashes: Option<Arc<CrashStore>>,
+    
+    /// Сколько строк лога сохранять в записи о падении
+    crash_log_lines: usize,
+    
+    /// Последние конфигурации, с которыми Xray запустился
+    history: ConfigHistory,
+    
+    /// Конфигурация последнего запуска, с ней сторож перезапускает ядро
+    last_config: RwLock<Option<XrayConfig>>,
+    
+    /// Конфигурация, переданная работающему Xray
+    effective: RwLock<Option<EffectiveConfig>>,
+    
+    /// Клиент `xray api` для изменений без перезапуска (None - XRAY_CONTROL_API_PORT не задан)
+    api: Option<XrayApiClient>,
+    
+    /// Последний замер ресурсов процесса
+    resources: Mutex<Option<ProcessResources>>,
+    
+    /// Количество перезапусков сторожем
+    watchdog_restarts: AtomicU64,
+    
+    /// Настройки сторожа ресурсов
+    watchdog: WatchdogOptions,
+    
+    /// Сериализует start/stop/restart: проверка started() и запуск процесса
+    /// выполняются под одной блокировкой (вместо флага restarting из Python)
+    lifecycle: tokio::sync::Mutex<()>,
+    
+    /// Logs pipeline (аналог _logs_buffer и _temp_log_buffers в Python)
+    logs: Arc<LogsPipeline>,
+    
+    /// Настройки хранения логов на диске
+    log_files: Option<LogFilesOptions>,
+    
+    /// Фильтр уровня и access лога Xray
+    log_level: Arc<LogLevelControl>,
+    
+    /// Состояние ядра, меняется при старте/остановке и задачей захвата логов
+    state: Arc<watch::Sender<CoreState>>,
+    
+    /// Сколько ждать баннера запуска
+    startup_timeout: Duration,
     
-    /// Logs buffer (аналог _logs_buffer в Python)
-    logs_buffer: Arc<Mutex<LogsBuffer>>,
+    /// Сколько ждать завершения после SIGTERM перед SIGKILL
+    stop_timeout: Duration,
+    
+    /// Строка, после которой ядро считается запущенным
+    ready_pattern: regex::Regex,
     
     /// Start callbacks (аналог _on_start_funcs в Python)
     on_start_callbacks: Arc<Mutex<Vec<EventCallback>>>,
@@ -46,6 +256,9 @@ pub struct XrayCore {
     /// Переменные окружения (аналог _env в Python)
     env_vars: HashMap<String, String>,
     
+    /// Дополнительные окружение, аргументы и ограничения процесса
+    launch: LaunchOptions,
+    
     /// Handle задачи захвата логов
     log_capture_handle: Arc<RwLock<Option<JoinHandle<()>>>>,
 }
@@ -53,7 +266,7 @@ pub struct XrayCore {
 impl XrayCore {
     /// Создает новый экземпляр XrayCore
     /// Идентично __init__ из Python версии
-    pub async fn new(executable_path: String, assets_path: String) -> Result<Self, Box<dyn std::error::Error>> {
+    pub async fn new(executable_path: String, assets_path: String, options: XrayCoreOptions) -> Result<Self, Box<dyn std::error::Error>> {
         let mut env_vars = HashMap::new();
         env_vars.insert("XRAY_LOCATION_ASSET".to_string(), assets_path.clone());
         
@@ -62,11 +275,31 @@ impl XrayCore {
             assets_path,
             version: None,
             process: Arc::new(RwLock::new(None)),
-            restarting: Arc::new(RwLock::new(false)),
-            logs_buffer: Arc::new(Mutex::new(LogsBuffer::new(100))),
+            running: RwLock::new(None),
+            pid_file: options.state_dir.as_deref().map(PidFile::new),
+            crashes: options.state_dir.as_deref()
+                .map(|dir| Arc::new(CrashStore::new(dir, options.crash_reports_max))),
+            crash_log_lines: options.crash_log_lines,
+            history: ConfigHistory::new(options.config_history_size),
+            last_config: RwLock::new(None),
+            effective: RwLock::new(None),
+            api: (options.control_api_port != 0)
+                .then(|| XrayApiClient::new(&executable_path, options.control_api_port, API_COMMAND_TIMEOUT)),
+            resources: Mutex::new(None),
+            watchdog_restarts: AtomicU64::new(0),
+            watchdog: options.watchdog,
+            lifecycle: tokio::sync::Mutex::new(()),
+            logs: Arc::new(LogsPipeline::new(options.logs)),
+            log_files: options.log_files,
+            log_level: Arc::new(LogLevelControl::new(options.runtime_log_level)),
+            state: Arc::new(watch::Sender::new(CoreState::Stopped)),
+            startup_timeout: options.startup_timeout,
+            stop_timeout: options.stop_timeout,
+            ready_pattern: regex::Regex::new(&options.ready_pattern)?,
             on_start_callbacks: Arc::new(Mutex::new(Vec::new())),
             on_stop_callbacks: Arc::new(Mutex::new(Vec::new())),
             env_vars,
+            launch: options.launch,
             log_capture_handle: Arc::new(RwLock::new(None)),
         };
         
@@ -75,6 +308,11 @@ impl XrayCore {
         let mut core = core;
         core.version = Some(version);
         
+        // Запускаем запись логов на диск, если она включена
+        if let Some(ref log_files) = core.log_files {
+            spawn_log_file_sink(log_files.clone(), core.logs.subscribe_live().await);
+        }
+        
         Ok(core)
     }
     
@@ -108,27 +346,242 @@ impl XrayCore {
                 Err(_) => false,      // Ошибка - считаем что не работает
             }
         } else {
-            false
+            // Принятый процесс не является дочерним, проверяем его через /proc
+            self.running.read().await.as_ref().is_some_and(PidRecord::is_alive)
+        }
+    }
+    
+    /// Запись о запущенном процессе (PID и хеш конфигурации)
+    pub async fn running(&self) -> Option<PidRecord> {
+        self.running.read().await.clone()
+    }
+    
+    /// Находит процесс Xray, оставшийся от предыдущего запуска ноды, и
+    /// завершает или принимает его согласно политике. Вызывается до приема команд панели
+    pub async fn recover_orphan(&self, policy: OrphanPolicy) {
+        let Some(ref pid_file) = self.pid_file else {
+            return;
+        };
+        let Some(record) = pid_file.read() else {
+            return;
+        };
+        if !record.is_alive() {
+            pid_file.remove();
+            return;
+        }
+        
+        let _lifecycle = self.lifecycle.lock().await;
+        match policy {
+            OrphanPolicy::Terminate => {
+                warn!("Terminating Xray process {} left from a previous run", record.pid);
+                self.terminate_orphan(&record).await;
+                pid_file.remove();
+            }
+            OrphanPolicy::Adopt => {
+                warn!(
+                    "Adopted Xray process {} left from a previous run (config {})",
+                    record.pid, record.config_hash
+                );
+                // stdout процесса был подключен к прошлой ноде, его логи недоступны
+                self.logs
+                    .publish(format!(
+                        "[rustzban-node] Adopted Xray process {} from a previous run, its logs are not available",
+                        record.pid
+                    ))
+                    .await;
+                *self.running.write().await = Some(record);
+                self.state.send_replace(CoreState::Running);
+            }
+        }
+    }
+    
+    /// Завершает процесс, который не является дочерним: SIGTERM, ожидание, SIGKILL группе
+    async fn terminate_orphan(&self, record: &PidRecord) {
+        signal_group(record.pid, libc::SIGTERM);
+        let exited = async {
+            while record.is_alive() {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        };
+        if tokio::time::timeout(self.stop_timeout, exited).await.is_err() {
+            warn!("Xray process {} did not exit after SIGTERM, killing it", record.pid);
+        }
+        signal_group(record.pid, libc::SIGKILL);
+    }
+    
+    /// Запускает сторожа ресурсов, если он включен
+    pub fn start_watchdog(self: &Arc<Self>) {
+        if !self.watchdog.interval.is_zero() {
+            tokio::spawn(run_watchdog(Arc::downgrade(self), self.watchdog.clone()));
+        }
+    }
+    
+    /// Последний замер ресурсов запущенного процесса
+    pub fn resources(&self) -> Option<ProcessResources> {
+        *self.resources.lock().unwrap()
+    }
+    
+    /// Снимает и сохраняет замер ресурсов работающего процесса
+    /// Запись о процессе удерживается до сохранения, чтобы stop не получил устаревший замер
+    pub(crate) async fn sample_resources(&self) -> Option<ProcessResources> {
+        let running = self.running.read().await;
+        let resources = match *running {
+            Some(ref record) if self.state().is_running() => ProcessResources::sample(record.pid),
+            _ => None,
+        };
+        *self.resources.lock().unwrap() = resources;
+        resources
+    }
+    
+    /// Записи о падениях (None - STATE_DIR не задан)
+    pub fn crashes(&self) -> Option<Arc<CrashStore>> {
+        self.crashes.clone()
+    }
+    
+    /// Количество перезапусков сторожем
+    pub fn watchdog_restarts(&self) -> u64 {
+        self.watchdog_restarts.load(Ordering::Relaxed)
+    }
+    
+    /// Перезапускает работающее ядро с конфигурацией последнего запуска
+    /// false - перезапускать нечем (ядро принято от предыдущего запуска ноды)
+    #[tracing::instrument(name = "xray_watchdog_restart", skip(self))]
+    pub async fn restart_with_last_config(&self, reason: &str) -> Result<bool, XrayStartError> {
+        let _lifecycle = self.lifecycle.lock().await;
+        // Пока сторож ждал блокировку, панель могла остановить или перезапустить ядро
+        if !self.state().is_running() {
+            return Ok(true);
         }
+        let Some(config) = self.last_config.read().await.clone() else {
+            return Ok(false);
+        };
+        
+        warn!("Restarting Xray: {}", reason);
+        self.logs
+            .publish(format!("[rustzban-node] Restarting Xray: {}", reason))
+            .await;
+        self.watchdog_restarts.fetch_add(1, Ordering::Relaxed);
+        self.stop_locked().await?;
+        self.start_locked(config).await?;
+        Ok(true)
+    }
+    
+    /// Текущее состояние ядра
+    pub fn state(&self) -> CoreState {
+        self.state.borrow().clone()
     }
     
     /// Запускает Xray с конфигурацией (аналог start из Python)
-    pub async fn start(&self, config: XrayConfig) -> Result<(), Box<dyn std::error::Error>> {
+    /// Ждет баннера запуска не дольше startup_timeout; если Xray не поднялся,
+    /// процесс останавливается, а ошибка содержит диагностику этой попытки
+    #[tracing::instrument(name = "xray_start", skip_all, fields(pid = tracing::field::Empty))]
+    pub async fn start(&self, config: XrayConfig) -> Result<(), XrayStartError> {
+        let _lifecycle = self.lifecycle.lock().await;
+        self.start_locked(config).await
+    }
+    
+    async fn start_locked(&self, config: XrayConfig) -> Result<(), XrayStartError> {
         if self.started().await {
-            return Err("Xray is started already".into());
+            return Err(XrayStartError::Core("Xray is started already".to_string()));
         }
         
-        // Модифицируем конфигурацию как в Python версии
-        let mut config = config;
-        if let Some(log) = config.as_object_mut().and_then(|obj| obj.get_mut("log")) {
-            if let Some(log_obj) = log.as_object_mut() {
-                if let Some(log_level) = log_obj.get("logLevel").and_then(|v| v.as_str()) {
-                    if matches!(log_level, "none" | "error") {
-                        log_obj.insert("logLevel".to_string(), serde_json::Value::String("warning".to_string()));
-                    }
+        // Подписка до запуска, чтобы в диагностику попали все строки этой попытки
+        let mut logs = self.logs.subscribe_live().await;
+        *self.last_config.write().await = Some(config.clone());
+        
+        // Ошибка запуска не Send, поэтому превращается в сообщение до очистки
+        let spawned = self.spawn(config).await.map_err(|e| (e.to_string(), XrayStartError::from(e)));
+        if let Err((message, error)) = spawned {
+            self.discard_failed_start().await;
+            self.state.send_replace(CoreState::Failed(message));
+            return Err(error);
+        }
+        
+        // Ждем, пока задача захвата логов не увидит баннер или завершение процесса
+        let mut state = self.state.subscribe();
+        let _ = tokio::time::timeout(
+            self.startup_timeout,
+            state.wait_for(|state| !state.is_starting()),
+        ).await;
+        
+        let state = self.state();
+        if state.is_running() {
+            if let Some(ref config) = *self.last_config.read().await {
+                self.history.record(config);
+            }
+            return Ok(());
+        }
+        
+        let mut log_tail = std::collections::VecDeque::new();
+        while let Some(event) = logs.try_recv() {
+            if let LogEvent::Line(line) = event {
+                if log_tail.len() == STARTUP_LOG_TAIL_LINES {
+                    log_tail.pop_front();
+                }
+                log_tail.push_back(line);
+            }
+        }
+        
+        let failure = if state.is_starting() {
+            // Процесс жив, но не сообщил о запуске: не оставляем его наполовину запущенным
+            self.stop_locked().await?;
+            StartupFailure::diagnose(log_tail.into(), None, Some(self.startup_timeout))
+        } else {
+            let failure = StartupFailure::diagnose(log_tail.into(), self.exit_status().await, None);
+            self.discard_failed_start().await;
+            failure
+        };
+        
+        warn!("Xray failed to start ({:?}): {}", failure.code, failure.message);
+        self.state.send_replace(CoreState::Failed(failure.message.clone()));
+        Err(XrayStartError::Startup(Box::new(failure)))
+    }
+    
+    /// Статус завершившегося процесса
+    async fn exit_status(&self) -> Option<std::process::ExitStatus> {
+        let mut process_lock = self.process.write().await;
+        let process = process_lock.as_mut()?;
+        // stdout уже закрыт, процесс завершается или завершился
+        tokio::time::timeout(Duration::from_secs(1), process.wait())
+            .await
+            .ok()?
+            .ok()
+    }
+    
+    /// Убирает следы неудачного запуска: процесс, PID файл и примененную конфигурацию
+    async fn discard_failed_start(&self) {
+        let process = self.process.write().await.take();
+        if let Some(mut process) = process {
+            match process.id() {
+                Some(pid) => signal_group(pid, libc::SIGKILL),
+                None => {
+                    let _ = process.kill().await;
                 }
             }
+            let _ = process.wait().await;
         }
+        *self.running.write().await = None;
+        *self.effective.write().await = None;
+        *self.resources.lock().unwrap() = None;
+        if let Some(ref pid_file) = self.pid_file {
+            pid_file.remove();
+        }
+    }
+    
+    /// Запускает процесс Xray и захват его логов
+    async fn spawn(&self, config: XrayConfig) -> Result<(), Box<dyn std::error::Error>> {
+        self.state.send_replace(CoreState::Starting);
+        
+        // Python поднимал none/error до warning; теперь запрошенный уровень
+        // соблюдается фильтром ноды, а Xray запускается с уровнем не ниже warning
+        let config_hash = config.canonical_hash();
+        let mut config = config;
+        self.log_level.prepare(&mut config);
+        *self.effective.write().await = Some(EffectiveConfig {
+            config: config.clone(),
+            config_hash: config_hash.clone(),
+            applied_at: Utc::now(),
+        });
         
         // Создаем команду как в Python версии
         let mut cmd = TokioCommand::new(&self.executable_path);
@@ -144,26 +597,78 @@ impl XrayCore {
             cmd.env(key, value);
         }
         
+        // Настройки запуска из конфигурации ноды, дополняют и переопределяют значения выше
+        self.launch.apply(&mut cmd);
+        
+        // Своя группа процессов: stop() завершает Xray вместе с его дочерними процессами
+        cmd.process_group(0);
+        
+        // Xray завершается вместе с нодой, даже если ее убили SIGKILL
+        // Выставляется после смены пользователя, которая сбрасывает этот флаг.
+        // Сигнал приходит при завершении потока, вызвавшего spawn, а не всего процесса,
+        // поэтому spawn должен выполняться только на долгоживущем рабочем потоке runtime,
+        // а не в spawn_blocking или временном потоке
+        #[cfg(target_os = "linux")]
+        unsafe {
+            let parent = libc::getpid();
+            cmd.pre_exec(move || {
+                if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                // Нода могла завершиться между fork и prctl, тогда сигнала уже не будет
+                if libc::getppid() != parent {
+                    return Err(std::io::Error::other("node exited before Xray started"));
+                }
+                Ok(())
+            });
+        }
+        
+        // Строки этого запуска для записи о падении идут после курсора
+        let log_cursor = self.logs.cursor().await;
         let mut process = cmd.spawn()?;
+        let config_json = config.to_json();
+        if let Some(pid) = process.id() {
+            tracing::Span::current().record("pid", pid);
+            
+            // Запоминаем процесс, чтобы после падения ноды найти его по PID файлу
+            let record = PidRecord::new(pid, &self.executable_path, config_hash);
+            if let (Some(pid_file), Some(record)) = (&self.pid_file, &record) {
+                if let Err(e) = pid_file.write(record) {
+                    warn!("Failed to write Xray PID file: {}", e);
+                }
+            }
+            *self.running.write().await = record;
+        }
+        debug!("Xray process spawned");
+        
+        // Сохраняем процесс до записи в stdin, чтобы при ошибке записи его можно было завершить
+        let stdin = process.stdin.take();
+        {
+            let mut process_lock = self.process.write().await;
+            *process_lock = Some(process);
+        }
         
         // Отправляем конфигурацию в stdin (как в Python версии)
-        if let Some(stdin) = process.stdin.take() {
+        if let Some(stdin) = stdin {
             use tokio::io::AsyncWriteExt;
-            let config_json = config.to_json();
             let mut stdin = stdin;
             stdin.write_all(config_json.as_bytes()).await?;
             stdin.flush().await?;
             drop(stdin); // Закрываем stdin как в Python
         }
         
-        // Сохраняем процесс
-        {
-            let mut process_lock = self.process.write().await;
-            *process_lock = Some(process);
-        }
-        
         // Запускаем захват логов (аналог __capture_process_logs из Python)
-        self.start_log_capture().await;
+        let crash = match (&self.crashes, self.running.read().await.clone()) {
+            (Some(store), Some(record)) => Some(CrashContext {
+                store: Arc::clone(store),
+                record,
+                log_cursor,
+                log_lines: self.crash_log_lines,
+                xray_version: self.version.clone(),
+            }),
+            _ => None,
+        };
+        self.start_log_capture(crash).await;
         
         // Выполняем колбэки на старт (как в Python версии)
         self.execute_start_callbacks().await;
@@ -172,25 +677,58 @@ impl XrayCore {
     }
     
     /// Останавливает Xray (аналог stop из Python)
+    #[tracing::instrument(name = "xray_stop", skip_all)]
     pub async fn stop(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let _lifecycle = self.lifecycle.lock().await;
+        self.stop_locked().await
+    }
+    
+    async fn stop_locked(&self) -> Result<(), Box<dyn std::error::Error>> {
         if !self.started().await {
+            // Процесс уже завершился сам: Failed сохраняется до следующего запуска
             return Ok(());
         }
         
-        // Останавливаем захват логов
-        if let Some(handle) = self.log_capture_handle.write().await.take() {
-            handle.abort();
+        self.state.send_replace(CoreState::Stopping);
+        
+        // Завершаем процесс: SIGTERM, ожидание stop_timeout, затем SIGKILL всей группе
+        let process = self.process.write().await.take();
+        if let Some(mut process) = process {
+            match process.id() {
+                Some(pid) => {
+                    signal_group(pid, libc::SIGTERM);
+                    if tokio::time::timeout(self.stop_timeout, process.wait()).await.is_err() {
+                        warn!("Xray did not exit within {:?} after SIGTERM, killing it", self.stop_timeout);
+                    }
+                    // Добиваем оставшиеся процессы группы, если они есть
+                    signal_group(pid, libc::SIGKILL);
+                }
+                None => {
+                    let _ = process.kill().await;
+                }
+            }
+            let _ = process.wait().await;
+        } else if let Some(ref record) = *self.running.read().await {
+            // Процесс принят от предыдущего запуска ноды
+            self.terminate_orphan(record).await;
         }
         
-        // Завершаем процесс
-        {
-            let mut process_lock = self.process.write().await;
-            if let Some(mut process) = process_lock.take() {
-                let _ = process.kill().await;
-                let _ = process.wait().await;
+        *self.running.write().await = None;
+        *self.effective.write().await = None;
+        *self.resources.lock().unwrap() = None;
+        if let Some(ref pid_file) = self.pid_file {
+            pid_file.remove();
+        }
+        
+        // Захват логов дочитывает строки, которые Xray вывел при завершении
+        if let Some(handle) = self.log_capture_handle.write().await.take() {
+            let abort = handle.abort_handle();
+            if tokio::time::timeout(Duration::from_secs(1), handle).await.is_err() {
+                abort.abort();
             }
         }
         
+        self.state.send_replace(CoreState::Stopped);
         warn!("Xray core stopped");
         
         // Выполняем колбэки на стоп (как в Python версии)
@@ -200,74 +738,252 @@ impl XrayCore {
     }
     
     /// Перезапускает Xray с новой конфигурацией (аналог restart из Python)
-    pub async fn restart(&self, config: XrayConfig) -> Result<(), Box<dyn std::error::Error>> {
-        let mut restarting_lock = self.restarting.write().await;
-        if *restarting_lock {
-            return Ok(()); // Уже перезапускается
-        }
+    #[tracing::instrument(name = "xray_restart", skip_all)]
+    pub async fn restart(&self, config: XrayConfig) -> Result<(), XrayStartError> {
+        // Параллельный перезапуск ждет текущий, а не пропускает свою конфигурацию
+        let _lifecycle = self.lifecycle.lock().await;
+        
+        self.restart_locked(config).await
+    }
+    
+    /// Перезапуск с возвратом к последней рабочей конфигурации, если новая не запустилась
+    async fn restart_locked(&self, config: XrayConfig) -> Result<(), XrayStartError> {
+        // Пока ядро работает, последняя ревизия истории - его текущая конфигурация
+        let previous = match self.state().is_running() {
+            true => self.history.latest(),
+            false => None,
+        };
+        
+        warn!("Restarting Xray core...");
+        self.stop_locked().await?;
+        let failure = match self.start_locked(config).await {
+            Err(XrayStartError::Startup(failure)) => failure,
+            result => return result,
+        };
+        let Some(previous) = previous else {
+            return Err(XrayStartError::Startup(failure));
+        };
         
-        *restarting_lock = true;
+        warn!("New config failed to start, rolling back to revision {}", previous.revision);
+        self.logs
+            .publish(format!(
+                "[rustzban-node] New config failed to start, rolling back to revision {}",
+                previous.revision
+            ))
+            .await;
+        let restored = self.start_locked(previous.config).await;
+        let mut failure = failure;
+        failure.rollback = Some(RollbackReport {
+            revision: previous.revision,
+            config_hash: previous.config_hash,
+            restored: restored.is_ok(),
+            error: restored.err().map(|e| e.to_string()),
+        });
+        Err(XrayStartError::Startup(failure))
+    }
+    
+    /// Применяет конфигурацию к работающему ядру: изменения inbounds и пользователей
+    /// отправляются через `xray api`, остальное и любые ошибки API ведут к полному перезапуску
+    #[tracing::instrument(name = "xray_apply", skip_all)]
+    pub async fn apply(&self, config: XrayConfig) -> Result<ApplyOutcome, XrayStartError> {
+        let _lifecycle = self.lifecycle.lock().await;
         
-        let result = async {
-            warn!("Restarting Xray core...");
-            self.stop().await?;
-            self.start(config).await?;
-            Ok::<(), Box<dyn std::error::Error>>(())
-        }.await;
+        let reason = match self.plan_locked(&config).await {
+            ApplyPlan::Unchanged => return Ok(ApplyOutcome::Unchanged),
+            ApplyPlan::Incremental(changes) => match self.apply_changes(&config, &changes).await {
+                Ok(()) => return Ok(ApplyOutcome::Updated(changes.summary())),
+                Err(e) => {
+                    // Часть команд могла выполниться: перезапуск приводит ядро к новой конфигурации целиком
+                    warn!("Incremental update failed, restarting Xray: {}", e);
+                    format!("incremental update failed: {}", e)
+                }
+            },
+            ApplyPlan::Restart(reason) => reason,
+        };
         
-        *restarting_lock = false;
-        result
+        self.restart_locked(config).await?;
+        Ok(ApplyOutcome::Restarted(ChangeSummary::restart(reason)))
+    }
+    
+    /// План применения относительно конфигурации работающего ядра
+    async fn plan_locked(&self, config: &XrayConfig) -> ApplyPlan {
+        if self.api.is_none() {
+            return ApplyPlan::Restart("XRAY_CONTROL_API_PORT is not set".to_string());
+        }
+        if !self.state().is_running() {
+            return ApplyPlan::Restart("Xray is not running".to_string());
+        }
+        match *self.last_config.read().await {
+            Some(ref running) => plan(running, config),
+            // Ядро принято от предыдущего запуска ноды
+            None => ApplyPlan::Restart("running config is unknown".to_string()),
+        }
+    }
+    
+    /// Отправляет изменения в Xray и запоминает новую конфигурацию как текущую
+    async fn apply_changes(&self, config: &XrayConfig, changes: &InboundChanges) -> Result<(), String> {
+        let Some(ref api) = self.api else {
+            return Err("XRAY_CONTROL_API_PORT is not set".to_string());
+        };
+        api.apply(changes).await?;
+        
+        let summary = changes.summary();
+        tracing::info!(
+            "Applied config without restart: -{} +{} inbounds, -{} +{} users",
+            summary.inbounds_removed, summary.inbounds_added, summary.users_removed, summary.users_added
+        );
+        *self.last_config.write().await = Some(config.clone());
+        let config_hash = config.canonical_hash();
+        if let Some(ref mut effective) = *self.effective.write().await {
+            // Секция log не менялась (иначе был бы перезапуск), в ней остается подготовленный уровень
+            let log = effective.config.as_object().and_then(|obj| obj.get("log")).cloned();
+            let mut prepared = config.clone();
+            if let (Some(obj), Some(log)) = (prepared.as_object_mut(), log) {
+                obj.insert("log".to_string(), log);
+            }
+            *effective = EffectiveConfig {
+                config: prepared,
+                config_hash: config_hash.clone(),
+                applied_at: Utc::now(),
+            };
+        }
+        if let Some(ref mut record) = *self.running.write().await {
+            record.config_hash = config_hash;
+            if let Some(ref pid_file) = self.pid_file {
+                if let Err(e) = pid_file.write(record) {
+                    warn!("Failed to write Xray PID file: {}", e);
+                }
+            }
+        }
+        self.history.record(config);
+        Ok(())
+    }
+    
+    /// Конфигурация, с которой работает Xray (None - ядро не работает
+    /// или принято от предыдущего запуска ноды)
+    pub async fn effective_config(&self) -> Option<EffectiveConfig> {
+        if !self.state().is_running() {
+            return None;
+        }
+        self.effective.read().await.clone()
+    }
+    
+    /// Успешно примененные конфигурации
+    pub fn config_history(&self) -> &ConfigHistory {
+        &self.history
     }
     
     /// Add start callback (аналог on_start из Python)
+    #[allow(dead_code)]
     pub async fn on_start<F>(&self, callback: F) 
     where
         F: Fn() + Send + Sync + 'static,
     {
         let mut callbacks = self.on_start_callbacks.lock().unwrap();
-        callbacks.push(Box::new(callback));
+        callbacks.push(Arc::new(callback));
     }
     
     /// Add stop callback (аналог on_stop из Python)
+    #[allow(dead_code)]
     pub async fn on_stop<F>(&self, callback: F)
     where
         F: Fn() + Send + Sync + 'static,
     {
         let mut callbacks = self.on_stop_callbacks.lock().unwrap();
-        callbacks.push(Box::new(callback));
+        callbacks.push(Arc::new(callback));
+    }
+    
+    /// Получает конвейер логов (аналог get_logs из Python)
+    pub fn get_logs(&self) -> Arc<LogsPipeline> {
+        Arc::clone(&self.logs)
+    }
+    
+    /// Настройки хранения логов на диске, если оно включено
+    pub fn log_files(&self) -> Option<&LogFilesOptions> {
+        self.log_files.as_ref()
     }
     
-    /// Получает буфер логов (аналог get_logs из Python)
-    pub fn get_logs_buffer(&self) -> Arc<Mutex<LogsBuffer>> {
-        Arc::clone(&self.logs_buffer)
+    /// Фильтр уровня логов Xray
+    pub fn log_level(&self) -> &LogLevelControl {
+        &self.log_level
     }
     
     /// Запускает захват логов (аналог __capture_process_logs из Python)
-    async fn start_log_capture(&self) {
-        let process_arc: Arc<RwLock<Option<TokioChild>>> = Arc::clone(&self.process);
-        let logs_buffer_arc: Arc<Mutex<LogsBuffer>> = Arc::clone(&self.logs_buffer);
+    async fn start_log_capture(&self, crash: Option<CrashContext>) {
+        // Забираем stdout под блокировкой и сразу ее отпускаем,
+        // чтобы started()/stop() не ждали завершения процесса
+        let stdout = {
+            let mut process_lock = self.process.write().await;
+            process_lock.as_mut().and_then(|process| process.stdout.take())
+        };
+        let Some(stdout) = stdout else {
+            return;
+        };
+        let logs = Arc::clone(&self.logs);
+        let log_level = Arc::clone(&self.log_level);
+        let state = Arc::clone(&self.state);
+        let process = Arc::clone(&self.process);
+        let ready_pattern = self.ready_pattern.clone();
         
         let handle = tokio::spawn(async move {
-            let mut process_lock = process_arc.write().await;
-            if let Some(ref mut process) = *process_lock {
-                if let Some(stdout) = process.stdout.take() {
-                    let reader = BufReader::new(stdout);
-                    let mut lines = reader.lines();
-                    
-                    while let Ok(Some(line)) = lines.next_line().await {
-                        let line = line.trim().to_string();
-                        if !line.is_empty() {
-                            // Добавляем в основной буфер
-                            {
-                                let mut buffer = logs_buffer_arc.lock().unwrap();
-                                buffer.push(line.clone());
-                            }
-                            
-                            // Логируем в debug режиме (как в Python версии)
-                            debug!("{}", line);
+            let reader = BufReader::new(stdout);
+            let mut lines = reader.lines();
+            let mut last_line = String::new();
+            
+            while let Ok(Some(line)) = lines.next_line().await {
+                let line = line.trim().to_string();
+                if line.is_empty() {
+                    continue;
+                }
+                
+                // Баннер запуска переводит Starting в Running
+                if ready_pattern.is_match(&line) {
+                    state.send_if_modified(|state| {
+                        let ready = state.is_starting();
+                        if ready {
+                            *state = CoreState::Running;
                         }
+                        ready
+                    });
+                }
+                
+                if log_level.allows(&line) {
+                    last_line.clone_from(&line);
+                    let published = logs.publish(line).await;
+                    // Логируем в debug режиме (как в Python версии), только после редактирования
+                    debug!("{}", published);
+                }
+            }
+            
+            // stdout закрыт без stop(): процесс завершился сам
+            let mut crashed = None;
+            state.send_if_modified(|state| {
+                let unexpected = matches!(state, CoreState::Starting | CoreState::Running);
+                if unexpected {
+                    let reason = if last_line.is_empty() {
+                        "Xray process exited".to_string()
+                    } else {
+                        last_line
+                    };
+                    // Неудачный запуск описывается в ответе /start, падением считается только running
+                    if state.is_running() {
+                        crashed = Some(reason.clone());
                     }
+                    warn!("Xray core exited unexpectedly: {}", reason);
+                    *state = CoreState::Failed(reason);
                 }
+                unexpected
+            });
+            
+            if let (Some(reason), Some(crash)) = (crashed, crash) {
+                let exit_status = match process.write().await.as_mut() {
+                    Some(process) => tokio::time::timeout(Duration::from_secs(1), process.wait())
+                        .await
+                        .ok()
+                        .and_then(Result::ok),
+                    None => None,
+                };
+                crash.report(&logs, reason, exit_status).await;
             }
         });
         
@@ -277,31 +993,72 @@ impl XrayCore {
     
     /// Execute start callbacks
     async fn execute_start_callbacks(&self) {
-        let callbacks_lock = self.on_start_callbacks.lock().unwrap();
-        for _ in 0..callbacks_lock.len() {
-            // Выполняем колбэки без клонирования
-            if let Some(_callback) = callbacks_lock.get(0) {
-                tokio::spawn(async move {
-                    // _callback(); // Временно отключаем пока не решим с клонированием
-                });
-            }
+        let callbacks: Vec<EventCallback> = self.on_start_callbacks.lock().unwrap().clone();
+        for callback in callbacks {
+            // Каждый колбэк в отдельной задаче, как threading.Thread в Python
+            tokio::spawn(async move { callback() });
         }
     }
     
     /// Execute stop callbacks
     async fn execute_stop_callbacks(&self) {
-        let callbacks_lock = self.on_stop_callbacks.lock().unwrap();
-        for _ in 0..callbacks_lock.len() {
-            // Выполняем колбэки без клонирования
-            if let Some(_callback) = callbacks_lock.get(0) {
-                tokio::spawn(async move {
-                    // _callback(); // Временно отключаем пока не решим с клонированием
-                });
-            }
+        let callbacks: Vec<EventCallback> = self.on_stop_callbacks.lock().unwrap().clone();
+        for callback in callbacks {
+            tokio::spawn(async move { callback() });
+        }
+    }
+}
+
+/// Данные запуска, нужные для записи о падении
+struct CrashContext {
+    store: Arc<CrashStore>,
+    record: PidRecord,
+    /// Курсор логов перед запуском процесса
+    log_cursor: u64,
+    log_lines: usize,
+    xray_version: Option<String>,
+}
+
+impl CrashContext {
+    async fn report(self, logs: &LogsPipeline, reason: String, exit_status: Option<std::process::ExitStatus>) {
+        use std::os::unix::process::ExitStatusExt;
+        
+        let crashed_at = chrono::Utc::now();
+        let record = CrashRecord {
+            id: CrashStore::new_id(crashed_at, self.record.pid),
+            crashed_at,
+            reason,
+            pid: self.record.pid,
+            exit_code: exit_status.and_then(|status| status.code()),
+            exit_signal: exit_status.and_then(|status| status.signal()),
+            uptime_secs: u64::try_from((crashed_at - self.record.started_at).num_seconds()).ok(),
+            config_hash: Some(self.record.config_hash),
+            xray_version: self.xray_version,
+            node_version: env!("CARGO_PKG_VERSION").to_string(),
+            log_tail: logs.tail_after(self.log_cursor, self.log_lines).await,
+        };
+        
+        let store = self.store;
+        let id = record.id.clone();
+        match tokio::task::spawn_blocking(move || store.save(&record)).await {
+            Ok(Ok(())) => warn!("Xray crash recorded as {}", id),
+            Ok(Err(e)) => warn!("Failed to save Xray crash record: {}", e),
+            Err(e) => warn!("Failed to save Xray crash record: {}", e),
         }
     }
 }
 
+/// Отправляет сигнал группе процессов Xray (pgid = pid, см. process_group(0))
+fn signal_group(pid: u32, signal: libc::c_int) {
+    let Ok(pgid) = libc::pid_t::try_from(pid) else {
+        return;
+    };
+    // ESRCH (группа уже завершилась) не ошибка
+    unsafe {
+        libc::killpg(pgid, signal);
+    }
+}
+
 // Реализуем Drop для автоматической остановки процесса (аналог atexit.register в Python)
 // Реализуем Debug вручную (колбэки не могут быть Debug)
 impl std::fmt::Debug for XrayCore {
@@ -317,10 +1074,12 @@ impl std::fmt::Debug for XrayCore {
 
 impl Drop for XrayCore {
     fn drop(&mut self) {
-        // В Drop нельзя использовать async, поэтому используем блокирующий вариант
+        // В Drop нельзя использовать async: корректная остановка невозможна, убиваем группу сразу.
+        // Если нода убита без Drop, Xray завершит PR_SET_PDEATHSIG
         if let Ok(process_lock) = self.process.try_read() {
-            if process_lock.is_some() {
-                warn!("XrayCore dropped while process is still running - this may leave zombie processes");
+            if let Some(pid) = process_lock.as_ref().and_then(|process| process.id()) {
+                warn!("XrayCore dropped while process is still running, killing it");
+                signal_group(pid, libc::SIGKILL);
             }
         }
     }
@@ -329,13 +1088,375 @@ impl Drop for XrayCore {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tempfile::tempdir;
+    use crate::xray::startup::StartupFailureCode;
+    use crate::xray::state::CoreStateName;
+    use crate::xray::testing::{fake_xray, fake_xray_rejecting_broken, fake_xray_started_after, fake_xray_with_api};
+    use crate::xray::watchdog::ResourceLimits;
+    use std::os::unix::fs::PermissionsExt;
+    
+    async fn fake_core(dir: &tempfile::TempDir, body: &str, startup_timeout: Duration) -> XrayCore {
+        XrayCore::new(
+            fake_xray(dir, body),
+            dir.path().to_string_lossy().into_owned(),
+            XrayCoreOptions {
+                startup_timeout,
+                ..XrayCoreOptions::default()
+            },
+        ).await.unwrap()
+    }
+    
+    fn empty_config() -> XrayConfig {
+        XrayConfig::new(r#"{"inbounds": []}"#, "127.0.0.1".to_string(), &AppConfig::default()).unwrap()
+    }
+    
+    #[tokio::test]
+    async fn test_lifecycle_states() {
+        let dir = tempfile::tempdir().unwrap();
+        let core = XrayCore::new(
+            fake_xray_started_after(&dir, "0"),
+            dir.path().to_string_lossy().into_owned(),
+            XrayCoreOptions::default(),
+        ).await.unwrap();
+        assert_eq!(core.state(), CoreState::Stopped);
+        
+        core.start(empty_config()).await.unwrap();
+        assert_eq!(core.state(), CoreState::Running);
+        assert!(core.start(empty_config()).await.is_err());
+        
+        core.stop().await.unwrap();
+        assert_eq!(core.state(), CoreState::Stopped);
+    }
+    
+    async fn fake_core_with_stop_timeout(dir: &tempfile::TempDir, body: &str, stop_timeout: Duration) -> XrayCore {
+        XrayCore::new(
+            fake_xray(dir, body),
+            dir.path().to_string_lossy().into_owned(),
+            XrayCoreOptions {
+                stop_timeout,
+                ..XrayCoreOptions::default()
+            },
+        ).await.unwrap()
+    }
+    
+    const BANNER: &str = "echo '2024/01/01 00:00:00 [Warning] core: Xray 1.8.4 started'";
+    
+    /// Процесс существует и не является зомби
+    fn process_alive(pid: &str) -> bool {
+        std::fs::read_to_string(format!("/proc/{}/stat", pid.trim()))
+            .map(|stat| !stat.contains(") Z "))
+            .unwrap_or(false)
+    }
+    
+    #[tokio::test]
+    async fn test_stop_is_graceful() {
+        let dir = tempfile::tempdir().unwrap();
+        let body = format!("trap 'echo graceful shutdown; exit 0' TERM\n{}\nwhile true; do sleep 0.1; done", BANNER);
+        let core = fake_core_with_stop_timeout(&dir, &body, Duration::from_secs(5)).await;
+        core.start(empty_config()).await.unwrap();
+        
+        let mut logs = core.get_logs().subscribe_live().await;
+        let started = std::time::Instant::now();
+        core.stop().await.unwrap();
+        
+        // Xray успел обработать SIGTERM, а его последние строки попали в логи
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(logs.try_recv(), Some(LogEvent::Line("graceful shutdown".to_string())));
+    }
+    
+    #[tokio::test]
+    async fn test_stop_kills_process_group_after_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let helper_pid = dir.path().join("helper.pid");
+        let body = format!(
+            "trap '' TERM\nsleep 30 &\necho $! > {}\n{}\nwhile true; do sleep 0.1; done",
+            helper_pid.display(),
+            BANNER
+        );
+        let core = fake_core_with_stop_timeout(&dir, &body, Duration::from_millis(300)).await;
+        core.start(empty_config()).await.unwrap();
+        
+        let helper_pid = std::fs::read_to_string(&helper_pid).unwrap();
+        assert!(process_alive(&helper_pid));
+        
+        core.stop().await.unwrap();
+        assert!(!core.started().await);
+        // Дочерний процесс Xray тоже завершен
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!process_alive(&helper_pid));
+    }
+    
+    #[tokio::test]
+    async fn test_startup_without_banner_times_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let core = fake_core(&dir, "echo 'still loading'\nexec sleep 30", Duration::from_millis(300)).await;
+        
+        let Err(XrayStartError::Startup(failure)) = core.start(empty_config()).await else {
+            panic!("startup must fail without banner");
+        };
+        assert_eq!(failure.code, StartupFailureCode::StartupTimeout);
+        assert_eq!(failure.log_tail, vec!["still loading"]);
+        // Наполовину запущенный процесс остановлен
+        assert!(!core.started().await);
+        assert_eq!(core.state().name(), CoreStateName::Failed);
+    }
+    
+    #[tokio::test]
+    async fn test_exit_before_banner_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let core = fake_core(&dir, "echo 'Failed to start: main: failed to load config'\nexit 1", Duration::from_secs(5)).await;
+        
+        let Err(XrayStartError::Startup(failure)) = core.start(empty_config()).await else {
+            panic!("startup must fail when process exits");
+        };
+        assert_eq!(failure.code, StartupFailureCode::ConfigLoadFailed);
+        assert_eq!(failure.exit_code, Some(1));
+        assert_eq!(core.state(), CoreState::Failed("Failed to start: main: failed to load config".to_string()));
+    }
+    
+    #[tokio::test]
+    async fn test_failed_start_leaves_no_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let core = fake_core_with_state_dir(&dir, fake_xray(&dir, "echo 'Failed to start: main: failed to load config'\nexit 1")).await;
+        assert!(core.start(empty_config()).await.is_err());
+        assert!(core.running().await.is_none());
+        assert!(core.effective.read().await.is_none());
+        assert!(PidFile::new(&dir.path().join("state")).read().is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_stdin_write_error_kills_process() {
+        let dir = tempfile::tempdir().unwrap();
+        // Процесс закрывает stdin и остается жить: запись конфигурации получает EPIPE
+        let path = dir.path().join("xray");
+        let script = format!(
+            "#!/bin/sh\nif [ \"$1\" = version ]; then echo 'Xray 1.8.4'; exit 0; fi\necho $$ > '{}/xray.pid'\nexec 0<&-\nexec sleep 30\n",
+            dir.path().display()
+        );
+        std::fs::write(&path, script).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let core = fake_core_with_state_dir(&dir, path.to_string_lossy().into_owned()).await;
+        
+        // Конфигурация больше буфера канала, чтобы запись дождалась закрытия stdin
+        let padding = "x".repeat(256 * 1024);
+        let config = XrayConfig::new(
+            &format!(r#"{{"inbounds": [], "outbounds": [{{"tag": "{}"}}]}}"#, padding),
+            "127.0.0.1".to_string(),
+            &AppConfig::default(),
+        ).unwrap();
+        assert!(core.start(config).await.is_err());
+        
+        let pid = std::fs::read_to_string(dir.path().join("xray.pid")).unwrap();
+        assert!(!process_alive(&pid));
+        assert!(!core.started().await);
+        assert!(core.running().await.is_none());
+        assert!(core.effective.read().await.is_none());
+        assert!(PidFile::new(&dir.path().join("state")).read().is_none());
+    }
+    
+    /// Ядро с PID файлом в dir/state
+    async fn fake_core_with_state_dir(dir: &tempfile::TempDir, executable_path: String) -> XrayCore {
+        XrayCore::new(
+            executable_path,
+            dir.path().to_string_lossy().into_owned(),
+            XrayCoreOptions {
+                state_dir: Some(dir.path().join("state")),
+                stop_timeout: Duration::from_secs(1),
+                ..XrayCoreOptions::default()
+            },
+        ).await.unwrap()
+    }
+    
+    /// Запускает ядро и "роняет" ноду: XrayCore забывается без остановки процесса
+    async fn orphaned_xray(dir: &tempfile::TempDir) -> PidRecord {
+        let body = format!("{}\nwhile true; do sleep 0.1; done", BANNER);
+        let core = fake_core_with_state_dir(dir, fake_xray(dir, &body)).await;
+        core.start(empty_config()).await.unwrap();
+        let record = core.running().await.unwrap();
+        std::mem::forget(core);
+        record
+    }
+    
+    #[tokio::test]
+    async fn test_orphan_is_terminated_on_recovery() {
+        let dir = tempfile::tempdir().unwrap();
+        let record = orphaned_xray(&dir).await;
+        assert!(record.is_alive());
+        
+        let core = fake_core_with_state_dir(&dir, record.executable.clone()).await;
+        core.recover_orphan(OrphanPolicy::Terminate).await;
+        
+        assert!(!record.is_alive());
+        assert!(!core.started().await);
+        assert_eq!(core.state(), CoreState::Stopped);
+        assert!(PidFile::new(&dir.path().join("state")).read().is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_orphan_is_adopted_and_stopped() {
+        let dir = tempfile::tempdir().unwrap();
+        let record = orphaned_xray(&dir).await;
+        
+        let core = fake_core_with_state_dir(&dir, record.executable.clone()).await;
+        core.recover_orphan(OrphanPolicy::Adopt).await;
+        
+        assert!(core.started().await);
+        assert_eq!(core.state(), CoreState::Running);
+        assert_eq!(core.running().await.unwrap().config_hash, record.config_hash);
+        
+        core.stop().await.unwrap();
+        assert!(!record.is_alive());
+        assert_eq!(core.state(), CoreState::Stopped);
+        assert!(PidFile::new(&dir.path().join("state")).read().is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_stale_pid_file_is_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = PidFile::new(&dir.path().join("state"));
+        let mut record = PidRecord::new(std::process::id(), "/usr/local/bin/xray", "hash".to_string()).unwrap();
+        record.start_time += 1;
+        pid_file.write(&record).unwrap();
+        
+        let core = fake_core_with_state_dir(&dir, fake_xray(&dir, BANNER)).await;
+        core.recover_orphan(OrphanPolicy::Terminate).await;
+        
+        // Чужой процесс с тем же PID не тронут, а устаревшая запись удалена
+        assert!(pid_file.read().is_none());
+        assert_eq!(core.state(), CoreState::Stopped);
+    }
+    
+    #[tokio::test]
+    async fn test_watchdog_restarts_on_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let body = format!("{}\nwhile true; do sleep 0.1; done", BANNER);
+        let core = Arc::new(XrayCore::new(
+            fake_xray(&dir, &body),
+            dir.path().to_string_lossy().into_owned(),
+            XrayCoreOptions {
+                watchdog: WatchdogOptions {
+                    interval: Duration::from_millis(100),
+                    restart: ResourceLimits { threads: 1, rss_bytes: 1, fds: 0 },
+                    restart_cooldown: Duration::from_secs(60),
+                    ..WatchdogOptions::default()
+                },
+                ..XrayCoreOptions::default()
+            },
+        ).await.unwrap());
+        core.start(empty_config()).await.unwrap();
+        let first_pid = core.running().await.unwrap().pid;
+        core.start_watchdog();
+        
+        let restarted = async {
+            while core.watchdog_restarts() == 0 || !core.state().is_running() {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), restarted).await.unwrap();
+        
+        // Ядро перезапущено с той же конфигурацией, пауза не дает перезапускать его снова
+        assert_ne!(core.running().await.unwrap().pid, first_pid);
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(core.watchdog_restarts(), 1);
+        assert!(core.resources().is_some());
+        
+        core.stop().await.unwrap();
+        assert!(core.resources().is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_unexpected_exit_is_recorded() {
+        let dir = tempfile::tempdir().unwrap();
+        let body = format!("{}\necho 'panic: runtime error: index out of range'\nsleep 0.3\nexit 2", BANNER);
+        let core = fake_core_with_state_dir(&dir, fake_xray(&dir, &body)).await;
+        core.start(empty_config()).await.unwrap();
+        
+        let crashes = core.crashes().unwrap();
+        let recorded = async {
+            while crashes.list().is_empty() {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), recorded).await.unwrap();
+        
+        let crash = crashes.get(&crashes.list()[0].id).unwrap();
+        assert_eq!(crash.exit_code, Some(2));
+        assert_eq!(crash.reason, "panic: runtime error: index out of range");
+        assert_eq!(crash.log_tail.len(), 2);
+        assert!(crash.config_hash.is_some());
+        assert_eq!(core.state().name(), CoreStateName::Failed);
+    }
+    
+    fn config_with_outbound(tag: &str) -> XrayConfig {
+        let json = format!(r#"{{"inbounds": [], "outbounds": [{{"tag": "{}", "protocol": "freedom"}}]}}"#, tag);
+        XrayConfig::new(&json, "127.0.0.1".to_string(), &AppConfig::default()).unwrap()
+    }
+    
+    #[tokio::test]
+    async fn test_failed_restart_rolls_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let core = XrayCore::new(
+            fake_xray_rejecting_broken(&dir),
+            dir.path().to_string_lossy().into_owned(),
+            XrayCoreOptions::default(),
+        ).await.unwrap();
+        core.start(config_with_outbound("direct")).await.unwrap();
+        
+        let Err(XrayStartError::Startup(failure)) = core.restart(config_with_outbound("broken")).await else {
+            panic!("restart with a broken config must fail");
+        };
+        assert_eq!(failure.code, StartupFailureCode::UnknownProtocol);
+        let rollback = failure.rollback.unwrap();
+        assert_eq!(rollback.revision, 1);
+        assert!(rollback.restored);
+        
+        // Ядро снова работает с прежней конфигурацией, неудачная в историю не попала
+        assert_eq!(core.state(), CoreState::Running);
+        assert_eq!(core.config_history().list().len(), 1);
+        
+        // Без работающего ядра возвращаться не к чему
+        core.stop().await.unwrap();
+        let Err(XrayStartError::Startup(failure)) = core.restart(config_with_outbound("broken")).await else {
+            panic!("restart with a broken config must fail");
+        };
+        assert!(failure.rollback.is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_failed_incremental_update_falls_back_to_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let core = XrayCore::new(
+            fake_xray_with_api(&dir),
+            dir.path().to_string_lossy().into_owned(),
+            XrayCoreOptions {
+                control_api_port: 62052,
+                ..XrayCoreOptions::default()
+            },
+        ).await.unwrap();
+        let inbound = |port: u16| {
+            let json = format!(r#"{{"inbounds": [{{"tag": "socks-in", "port": {}, "protocol": "socks"}}]}}"#, port);
+            XrayConfig::new(&json, "127.0.0.1".to_string(), &AppConfig::default()).unwrap()
+        };
+        core.start(inbound(1080)).await.unwrap();
+        let first_pid = core.running().await.unwrap().pid;
+        
+        std::fs::write(dir.path().join("api.fail"), "").unwrap();
+        let ApplyOutcome::Restarted(summary) = core.apply(inbound(1081)).await.unwrap() else {
+            panic!("failed API call must lead to a restart");
+        };
+        assert!(summary.restart_reason.unwrap().starts_with("incremental update failed: xray api rmi failed"));
+        let record = core.running().await.unwrap();
+        assert_ne!(record.pid, first_pid);
+        assert_eq!(record.config_hash, inbound(1081).canonical_hash());
+        
+        core.stop().await.unwrap();
+    }
     
     #[tokio::test]
     async fn test_xray_core_creation() {
         let core = XrayCore::new(
             "/usr/local/bin/xray".to_string(),
-            "/usr/local/share/xray".to_string()
+            "/usr/local/share/xray".to_string(),
+            XrayCoreOptions::default(),
         ).await;
         
         // Тест может упасть если xray не установлен, это нормально для CI
@@ -354,7 +1475,8 @@ mod tests {
     async fn test_started_property() {
         let core = XrayCore::new(
             "/usr/local/bin/xray".to_string(),
-            "/usr/local/share/xray".to_string()
+            "/usr/local/share/xray".to_string(),
+            XrayCoreOptions::default(),
         ).await;
         
         if core.is_err() {
@@ -0,0 +1,310 @@
+use regex::{Captures, Regex};
+use std::{
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+/// Режим обработки IP адресов клиентов в логах
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IpRedaction {
+    /// Адреса передаются как есть
+    #[default]
+    Off,
+    /// Хвост адреса заменяется на *, префикс сохраняется
+    Mask,
+    /// Хвост адреса заменяется на хеш, префикс сохраняется
+    Hash,
+}
+
+impl FromStr for IpRedaction {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "" | "off" | "none" => Ok(IpRedaction::Off),
+            "mask" => Ok(IpRedaction::Mask),
+            "hash" => Ok(IpRedaction::Hash),
+            other => Err(format!("unknown IP redaction mode '{}', expected off, mask or hash", other)),
+        }
+    }
+}
+
+impl fmt::Display for IpRedaction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IpRedaction::Off => write!(f, "off"),
+            IpRedaction::Mask => write!(f, "mask"),
+            IpRedaction::Hash => write!(f, "hash"),
+        }
+    }
+}
+
+/// Политика редактирования логов Xray
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedactionOptions {
+    /// Обработка IP адресов (LOGS_REDACT_IPS)
+    pub ips: IpRedaction,
+    /// Сколько октетов IPv4 сохранять (LOGS_REDACT_IPV4_PREFIX)
+    pub ipv4_prefix: u8,
+    /// Сколько групп IPv6 сохранять (LOGS_REDACT_IPV6_PREFIX)
+    pub ipv6_prefix: u8,
+    /// Хешировать email пользователей (LOGS_REDACT_EMAILS)
+    pub emails: bool,
+    /// Удалять домены и адреса назначения (LOGS_REDACT_DESTINATIONS)
+    pub destinations: bool,
+    /// Соль для хешей (LOGS_REDACT_SALT)
+    pub salt: String,
+}
+
+impl Default for RedactionOptions {
+    fn default() -> Self {
+        Self {
+            ips: IpRedaction::Off,
+            ipv4_prefix: 2,
+            ipv6_prefix: 3,
+            emails: false,
+            destinations: false,
+            salt: String::new(),
+        }
+    }
+}
+
+impl RedactionOptions {
+    /// Включено ли хотя бы одно правило
+    pub fn is_enabled(&self) -> bool {
+        self.ips != IpRedaction::Off || self.emails || self.destinations
+    }
+}
+
+/// Применяет политику редактирования к строкам логов
+#[derive(Debug)]
+pub struct LogRedactor {
+    options: RedactionOptions,
+    /// Назначение вида "tcp:example.com:443" (access лог, dispatcher, freedom);
+    /// после "from " это адрес клиента, а не назначение
+    destination: Regex,
+    /// Домены и адреса назначения в остальных строках и шаблон замены
+    destination_forms: Vec<(Regex, &'static str)>,
+    /// Пользователь в access логе: "email: 1.user"
+    user_email: Regex,
+    /// Обычный email адрес где угодно в строке
+    email: Regex,
+    /// Кандидат в IPv4 адрес
+    ipv4: Regex,
+    /// Кандидат в IPv6 адрес (проверяется парсером)
+    ipv6: Regex,
+}
+
+impl LogRedactor {
+    pub fn new(options: RedactionOptions) -> Self {
+        Self {
+            options,
+            destination: Regex::new(r"(from )?\b((?:tcp|udp):)(\[[^\]]*\]|[^\s:,\[\]]+)(:\d+)?").unwrap(),
+            destination_forms: vec![
+                // app/dispatcher: sniffed domain: example.com
+                (Regex::new(r"(sniffed domain: )[^\s,]+").unwrap(), "${1}[redacted]"),
+                // proxy/freedom: connection opened to ..., remote endpoint 93.184.216.34:443
+                (Regex::new(r"(remote endpoint )[^\s,]+").unwrap(), "${1}[redacted]"),
+                // app/dns: UDP:8.8.8.8:53 got answer: example.com. TypeA -> [93.184.216.34] 25ms
+                (Regex::new(r"(got answer: )\S+( \S+ -> )\[[^\]]*\]").unwrap(), "${1}[redacted]${2}[redacted]"),
+                // app/dns: UDP:8.8.8.8:53 querying DNS for: example.com.
+                (Regex::new(r"(querying DNS for: )\S+").unwrap(), "${1}[redacted]"),
+                // app/dns: domain example.com will use DNS in order: ...
+                (Regex::new(r"(domain )\S+( will use DNS)").unwrap(), "${1}[redacted]${2}"),
+                // app/dns: failed to lookup ip for domain example.com at server ...
+                (Regex::new(r"(lookup ip for domain )\S+").unwrap(), "${1}[redacted]"),
+            ],
+            user_email: Regex::new(r"(email: )(\S+)").unwrap(),
+            email: Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap(),
+            ipv4: Regex::new(r"\b\d{1,3}(?:\.\d{1,3}){3}\b").unwrap(),
+            ipv6: Regex::new(r"[0-9A-Fa-f]{0,4}(?::[0-9A-Fa-f]{0,4}){2,7}").unwrap(),
+        }
+    }
+
+    /// Возвращает строку с примененной политикой
+    pub fn redact(&self, line: &str) -> String {
+        let mut line = line.to_string();
+
+        // Назначение удаляется первым, чтобы его IP не попал в маскирование
+        if self.options.destinations {
+            line = self.destination
+                .replace_all(&line, |caps: &Captures| match caps.get(1) {
+                    Some(_) => caps[0].to_string(),
+                    None => format!("{}[redacted]{}", &caps[2], caps.get(4).map_or("", |port| port.as_str())),
+                })
+                .into_owned();
+            for (form, replacement) in &self.destination_forms {
+                line = form.replace_all(&line, *replacement).into_owned();
+            }
+        }
+
+        if self.options.emails {
+            line = self.user_email
+                .replace_all(&line, |caps: &Captures| format!("{}{}", &caps[1], self.hash(&caps[2])))
+                .into_owned();
+            line = self.email
+                .replace_all(&line, |caps: &Captures| self.hash(&caps[0]))
+                .into_owned();
+        }
+
+        if self.options.ips != IpRedaction::Off {
+            line = self.ipv4
+                .replace_all(&line, |caps: &Captures| match Ipv4Addr::from_str(&caps[0]) {
+                    Ok(ip) => self.redact_ipv4(ip),
+                    Err(_) => caps[0].to_string(),
+                })
+                .into_owned();
+            line = self.ipv6
+                .replace_all(&line, |caps: &Captures| match Ipv6Addr::from_str(&caps[0]) {
+                    Ok(ip) => self.redact_ipv6(ip),
+                    // Например время 12:00:00 - не адрес
+                    Err(_) => caps[0].to_string(),
+                })
+                .into_owned();
+        }
+
+        line
+    }
+
+    fn redact_ipv4(&self, ip: Ipv4Addr) -> String {
+        let keep = usize::from(self.options.ipv4_prefix.min(4));
+        let octets = ip.octets();
+        let prefix: Vec<String> = octets[..keep].iter().map(|octet| octet.to_string()).collect();
+        self.redact_tail(prefix, 4 - keep, '.', &ip.to_string())
+    }
+
+    fn redact_ipv6(&self, ip: Ipv6Addr) -> String {
+        let keep = usize::from(self.options.ipv6_prefix.min(8));
+        let segments = ip.segments();
+        let prefix: Vec<String> = segments[..keep].iter().map(|segment| format!("{:x}", segment)).collect();
+        self.redact_tail(prefix, 8 - keep, ':', &ip.to_string())
+    }
+
+    /// Собирает адрес из сохраненного префикса и замаскированного или хешированного хвоста
+    fn redact_tail(&self, mut parts: Vec<String>, hidden: usize, separator: char, original: &str) -> String {
+        if hidden == 0 {
+            return original.to_string();
+        }
+        match self.options.ips {
+            IpRedaction::Hash => parts.push(self.hash(original)),
+            _ => parts.extend(std::iter::repeat_n("*".to_string(), hidden)),
+        }
+        parts.join(&separator.to_string())
+    }
+
+    /// Короткий соленый хеш значения
+    fn hash(&self, value: &str) -> String {
+        let digest = openssl::sha::sha256(format!("{}{}", self.options.salt, value).as_bytes());
+        let hex: String = digest[..6].iter().map(|byte| format!("{:02x}", byte)).collect();
+        format!("#{}", hex)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACCESS_LINE: &str =
+        "2024/01/01 12:00:00.123456 from 203.0.113.45:51234 accepted tcp:www.example.com:443 [vless-in >> direct] email: 12.alice";
+
+    fn redactor(options: RedactionOptions) -> LogRedactor {
+        LogRedactor::new(RedactionOptions {
+            salt: "salt".to_string(),
+            ..options
+        })
+    }
+
+    #[test]
+    fn test_disabled_policy_keeps_line() {
+        let redactor = redactor(RedactionOptions::default());
+        assert_eq!(redactor.redact(ACCESS_LINE), ACCESS_LINE);
+    }
+
+    #[test]
+    fn test_mask_ips_keeps_prefix() {
+        let redactor = redactor(RedactionOptions {
+            ips: IpRedaction::Mask,
+            ..RedactionOptions::default()
+        });
+        let line = redactor.redact(ACCESS_LINE);
+        assert!(line.contains("from 203.0.*.*:51234"));
+        // Время и версия не похожи на адреса и не меняются
+        assert!(line.starts_with("2024/01/01 12:00:00.123456"));
+
+        let line = redactor.redact("from [2001:db8:85a3::8a2e:370:7334]:443 accepted");
+        assert!(line.contains("[2001:db8:85a3:*:*:*:*:*]:443"));
+    }
+
+    #[test]
+    fn test_hash_ips_and_emails_are_stable() {
+        let redactor = redactor(RedactionOptions {
+            ips: IpRedaction::Hash,
+            emails: true,
+            ..RedactionOptions::default()
+        });
+        let first = redactor.redact(ACCESS_LINE);
+        assert_eq!(first, redactor.redact(ACCESS_LINE));
+        assert!(!first.contains("203.0.113.45"));
+        assert!(first.contains("from 203.0.#"));
+        assert!(!first.contains("12.alice"));
+        assert!(first.contains("email: #"));
+
+        let line = redactor.redact("user bob@example.com rejected");
+        assert!(!line.contains("bob@example.com"));
+    }
+
+    #[test]
+    fn test_drop_destinations() {
+        let redactor = redactor(RedactionOptions {
+            destinations: true,
+            ..RedactionOptions::default()
+        });
+        let line = redactor.redact(ACCESS_LINE);
+        assert!(line.contains("accepted tcp:[redacted]:443"));
+        assert!(!line.contains("www.example.com"));
+
+        let line = redactor.redact("from 1.2.3.4:5 accepted udp:[2001:db8::1]:53 [a >> b]");
+        assert!(line.contains("accepted udp:[redacted]:53"));
+
+        // Адрес клиента с сетью не назначение
+        let line = redactor.redact("from tcp:203.0.113.45:51234 accepted tcp:www.example.com:443 [vless-in >> direct]");
+        assert!(line.starts_with("from tcp:203.0.113.45:51234 accepted tcp:[redacted]:443"));
+    }
+
+    #[test]
+    fn test_drop_destinations_outside_access_lines() {
+        let redactor = redactor(RedactionOptions {
+            destinations: true,
+            ..RedactionOptions::default()
+        });
+        let lines = [
+            "2024/01/01 12:00:00.123456 from 203.0.113.45:51234 rejected tcp:www.example.com:443 [vless-in >> blocked] email: 12.alice",
+            "2024/01/01 12:00:00.123456 [Info] [3571256871] app/dispatcher: sniffed domain: www.example.com",
+            "2024/01/01 12:00:00.123456 [Info] [3571256871] app/dispatcher: taking detour [direct] for [tcp:www.example.com:443]",
+            "2024/01/01 12:00:00.123456 [Info] [3571256871] proxy/freedom: dialing to tcp:www.example.com:443",
+            "2024/01/01 12:00:00.123456 [Info] [3571256871] proxy/freedom: connection opened to tcp:www.example.com:443, local endpoint 10.0.0.2:40000, remote endpoint 93.184.216.34:443",
+            "2024/01/01 12:00:00.123456 [Debug] app/dns: domain www.example.com will use DNS in order: [UDP:8.8.8.8:53] [A AAAA]",
+            "2024/01/01 12:00:00.123456 [Debug] app/dns: UDP:8.8.8.8:53 querying DNS for: www.example.com.",
+            "2024/01/01 12:00:00.123456 [Info] app/dns: UDP:8.8.8.8:53 got answer: www.example.com. TypeA -> [93.184.216.34] 25.1ms",
+            "2024/01/01 12:00:00.123456 [Info] app/dns: failed to lookup ip for domain www.example.com at server UDP:8.8.8.8:53",
+        ];
+        for line in lines {
+            let redacted = redactor.redact(line);
+            assert!(!redacted.contains("example.com"), "{}", redacted);
+            assert!(!redacted.contains("93.184.216.34"), "{}", redacted);
+            assert!(redacted.contains("[redacted]"), "{}", redacted);
+        }
+        assert!(redactor.redact(lines[0]).contains("from 203.0.113.45:51234 rejected tcp:[redacted]:443"));
+        assert!(redactor.redact(lines[4]).contains("local endpoint 10.0.0.2:40000"));
+        assert!(redactor.redact(lines[7]).contains("UDP:8.8.8.8:53 got answer: [redacted] TypeA -> [redacted] 25.1ms"));
+    }
+
+    #[test]
+    fn test_ip_mode_parsing() {
+        assert_eq!("mask".parse::<IpRedaction>().unwrap(), IpRedaction::Mask);
+        assert_eq!("HASH".parse::<IpRedaction>().unwrap(), IpRedaction::Hash);
+        assert_eq!("".parse::<IpRedaction>().unwrap(), IpRedaction::Off);
+        assert!("scramble".parse::<IpRedaction>().is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::xray::model::{Inbound, Port};
+
+/// Таблица перезаписи inbounds ноды (INBOUND_REWRITES_FILE)
+///
+/// ```json
+/// {
+///   "inbounds": {"vless-in": {"port": 8443, "listen": "::"}},
+///   "certificates": {"/var/lib/marzban/certs/": "/etc/ssl/node/"}
+/// }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RewriteTable {
+    /// Порт и адрес прослушивания по тегу inbound
+    #[serde(default)]
+    pub inbounds: HashMap<String, InboundRewrite>,
+    /// Пути certificateFile и keyFile: ключ с `/` на конце заменяет префикс каталога
+    #[serde(default)]
+    pub certificates: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InboundRewrite {
+    pub port: Option<Port>,
+    pub listen: Option<String>,
+}
+
+impl RewriteTable {
+    /// Читает таблицу; ошибки разбора содержат путь внутри файла
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        serde_path_to_error::deserialize(&mut serde_json::Deserializer::from_str(&content)).map_err(|e| {
+            match e.path().to_string().as_str() {
+                "." => e.into_inner().to_string(),
+                inner => format!("{}: {}", inner, e.into_inner()),
+            }
+        })
+    }
+
+    /// Применяет таблицу к inbounds панели
+    pub fn apply(&self, inbounds: &mut [Inbound]) {
+        for inbound in inbounds {
+            if let Some(rewrite) = inbound.tag.as_deref().and_then(|tag| self.inbounds.get(tag)) {
+                if let Some(port) = &rewrite.port {
+                    inbound.port = Some(port.clone());
+                }
+                if let Some(listen) = &rewrite.listen {
+                    inbound.listen = Some(listen.clone());
+                }
+            }
+            if !self.certificates.is_empty() {
+                if let Some(stream_settings) = inbound.stream_settings.as_mut() {
+                    self.remap_certificates(stream_settings);
+                }
+            }
+        }
+    }
+
+    /// Заменяет certificateFile и keyFile на любой глубине streamSettings
+    fn remap_certificates(&self, map: &mut Map<String, Value>) {
+        for (key, value) in map.iter_mut() {
+            match value {
+                Value::String(path) if matches!(key.as_str(), "certificateFile" | "keyFile") => {
+                    if let Some(remapped) = self.remap_path(path) {
+                        *path = remapped;
+                    }
+                }
+                Value::Object(inner) => self.remap_certificates(inner),
+                Value::Array(items) => {
+                    for item in items {
+                        if let Value::Object(inner) = item {
+                            self.remap_certificates(inner);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Точное совпадение важнее префикса, из префиксов выбирается самый длинный
+    fn remap_path(&self, path: &str) -> Option<String> {
+        if let Some(target) = self.certificates.get(path) {
+            return Some(target.clone());
+        }
+        self.certificates
+            .iter()
+            .filter(|(prefix, _)| prefix.ends_with('/') && path.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(prefix, target)| format!("{}{}", target, &path[prefix.len()..]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_certificate_paths() {
+        let table: RewriteTable = serde_json::from_str(r#"{"certificates": {
+            "/certs/": "/etc/ssl/node/",
+            "/certs/special/": "/srv/special/",
+            "/certs/old.pem": "/etc/ssl/new.pem"
+        }}"#).unwrap();
+        assert_eq!(table.remap_path("/certs/a.pem").as_deref(), Some("/etc/ssl/node/a.pem"));
+        assert_eq!(table.remap_path("/certs/special/b.key").as_deref(), Some("/srv/special/b.key"));
+        assert_eq!(table.remap_path("/certs/old.pem").as_deref(), Some("/etc/ssl/new.pem"));
+        assert_eq!(table.remap_path("/other/c.pem"), None);
+    }
+
+    #[test]
+    fn test_load_reports_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rewrites.json");
+        fs::write(&path, r#"{"inbounds": {"vless-in": {"port": true}}}"#).unwrap();
+        let error = RewriteTable::load(&path).unwrap_err();
+        assert!(error.starts_with("inbounds.vless-in.port: "), "{}", error);
+
+        fs::write(&path, r#"{"inbound": {}}"#).unwrap();
+        assert!(RewriteTable::load(&path).unwrap_err().contains("unknown field `inbound`"));
+    }
+}
@@ -0,0 +1,189 @@
+use regex::Regex;
+use serde::Serialize;
+use std::{os::unix::process::ExitStatusExt, process::ExitStatus, sync::LazyLock, time::Duration};
+
+use crate::xray::history::RollbackReport;
+
+/// Сколько последних строк попытки запуска сохраняется в диагностике
+pub const STARTUP_LOG_TAIL_LINES: usize = 100;
+
+/// Код причины неудачного запуска
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StartupFailureCode {
+    /// Порт inbound уже занят
+    AddressInUse,
+    /// Xray не смог разобрать или загрузить конфигурацию
+    ConfigLoadFailed,
+    /// Ошибка сертификата или ключа TLS
+    InvalidCertificate,
+    /// Неизвестный протокол или тип настроек
+    UnknownProtocol,
+    /// Процесс завершился по другой причине
+    ProcessExited,
+    /// Процесс жив, но не сообщил о запуске вовремя
+    StartupTimeout,
+}
+
+/// Известные ошибки запуска в порядке приоритета: более конкретные раньше,
+/// потому что Xray заворачивает их в "failed to load config"
+static FAILURE_PATTERNS: LazyLock<Vec<(StartupFailureCode, Regex)>> = LazyLock::new(|| {
+    [
+        (StartupFailureCode::AddressInUse, r"(?i)address already in use"),
+        (StartupFailureCode::InvalidCertificate, r"(?i)failed to (?:parse|load) (?:certificate|(?:private )?key)|x509:"),
+        (StartupFailureCode::UnknownProtocol, r"(?i)unknown (?:config id|protocol)|not (?:registered|supported)"),
+        (StartupFailureCode::ConfigLoadFailed, r"(?i)failed to (?:load|read|parse|decode) config|infra/conf"),
+    ]
+    .into_iter()
+    .map(|(code, pattern)| (code, Regex::new(pattern).unwrap()))
+    .collect()
+});
+
+/// Диагностика неудачного запуска Xray
+#[derive(Debug, Clone, Serialize, thiserror::Error)]
+#[error("{message}")]
+pub struct StartupFailure {
+    pub code: StartupFailureCode,
+    /// Строка лога с причиной или описание ошибки
+    pub message: String,
+    /// Код выхода процесса, если он завершился сам
+    pub exit_code: Option<i32>,
+    /// Сигнал, которым завершен процесс
+    pub exit_signal: Option<i32>,
+    /// Строки лога этой попытки запуска
+    pub log_tail: Vec<String>,
+    /// Возврат к предыдущей конфигурации после неудачного перезапуска
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rollback: Option<RollbackReport>,
+}
+
+impl StartupFailure {
+    /// Определяет причину по логу попытки запуска и статусу процесса
+    /// timed_out задан, если процесс жив, но не сообщил о запуске за это время
+    pub fn diagnose(
+        log_tail: Vec<String>,
+        exit_status: Option<ExitStatus>,
+        timed_out: Option<Duration>,
+    ) -> Self {
+        // Сначала итоговая строка: "Failed to start" или последняя строка завершившегося процесса,
+        // потому что более ранние предупреждения могут упоминать другие причины
+        let final_line = log_tail
+            .iter()
+            .rev()
+            .find(|line| line.contains("Failed to start"))
+            .or_else(|| if timed_out.is_none() { log_tail.last() } else { None });
+        let known = final_line
+            .and_then(|line| {
+                FAILURE_PATTERNS
+                    .iter()
+                    .find(|(_, pattern)| pattern.is_match(line))
+                    .map(|(code, _)| (*code, line.clone()))
+            })
+            .or_else(|| {
+                FAILURE_PATTERNS.iter().find_map(|(code, pattern)| {
+                    log_tail
+                        .iter()
+                        .rev()
+                        .find(|line| pattern.is_match(line))
+                        .map(|line| (*code, line.clone()))
+                })
+            });
+
+        let (code, message) = match (known, timed_out) {
+            (Some(known), _) => known,
+            (None, Some(timeout)) => (
+                StartupFailureCode::StartupTimeout,
+                format!("Xray did not report startup within {:?}", timeout),
+            ),
+            (None, None) => (
+                StartupFailureCode::ProcessExited,
+                log_tail.last().cloned().unwrap_or_else(|| match exit_status {
+                    Some(status) => format!("Xray process exited with {}", status),
+                    None => "Xray process exited".to_string(),
+                }),
+            ),
+        };
+
+        Self {
+            code,
+            message,
+            exit_code: exit_status.and_then(|status| status.code()),
+            exit_signal: exit_status.and_then(|status| status.signal()),
+            log_tail,
+            rollback: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn test_known_failures() {
+        let cases = [
+            (
+                "Failed to start: main: failed to create server > app/proxyman/inbound: failed to listen TCP on 443 > transport/internet: failed to listen on address: 0.0.0.0:443 > listen tcp 0.0.0.0:443: bind: address already in use",
+                StartupFailureCode::AddressInUse,
+            ),
+            (
+                "Failed to start: main: failed to load config files: [stdin:] > infra/conf: failed to build TLS config > transport/internet/tls: failed to parse certificate",
+                StartupFailureCode::InvalidCertificate,
+            ),
+            (
+                "Failed to start: main: failed to load config files: [stdin:] > infra/conf: unknown config id: vmesss",
+                StartupFailureCode::UnknownProtocol,
+            ),
+            (
+                "Failed to start: main: failed to load config files: [stdin:] > infra/conf/serial: failed to decode config",
+                StartupFailureCode::ConfigLoadFailed,
+            ),
+        ];
+
+        for (line, code) in cases {
+            let failure = StartupFailure::diagnose(lines(&["Xray 1.8.4 (Xray, Penetrates Everything.)", line]), None, None);
+            assert_eq!(failure.code, code, "{}", line);
+            assert_eq!(failure.message, line);
+        }
+    }
+
+    #[test]
+    fn test_final_line_wins_over_earlier_warnings() {
+        let failed = "Failed to start: main: failed to load config files: [stdin:] > infra/conf: unknown config id: vmesss";
+        let failure = StartupFailure::diagnose(
+            lines(&["[Warning] transport/internet/tls: certificate for example.com expires soon", failed]),
+            None,
+            None,
+        );
+        assert_eq!(failure.code, StartupFailureCode::UnknownProtocol);
+        assert_eq!(failure.message, failed);
+
+        // Итоговая строка без известной причины: причина ищется в остальном логе
+        let expired = "[Error] transport/internet/tls: x509: certificate has expired";
+        let failure = StartupFailure::diagnose(lines(&[expired, "Failed to start: main: something went wrong"]), None, None);
+        assert_eq!(failure.code, StartupFailureCode::InvalidCertificate);
+        assert_eq!(failure.message, expired);
+
+        // Упоминание сертификата само по себе не ошибка TLS
+        let failure = StartupFailure::diagnose(lines(&["[Info] loading certificate from /etc/ssl/a.pem"]), None, None);
+        assert_eq!(failure.code, StartupFailureCode::ProcessExited);
+    }
+
+    #[test]
+    fn test_unknown_failure_uses_exit_status_or_timeout() {
+        let status = ExitStatus::from_raw(2 << 8);
+        let failure = StartupFailure::diagnose(Vec::new(), Some(status), None);
+        assert_eq!(failure.code, StartupFailureCode::ProcessExited);
+        assert_eq!(failure.exit_code, Some(2));
+        assert!(failure.message.contains("exit status: 2"));
+
+        let failure = StartupFailure::diagnose(lines(&["[Info] still loading"]), None, Some(Duration::from_secs(3)));
+        assert_eq!(failure.code, StartupFailureCode::StartupTimeout);
+        assert_eq!(failure.log_tail.len(), 1);
+        assert!(failure.exit_code.is_none());
+    }
+}
@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Состояние ядра Xray
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum CoreState {
+    /// Процесс не запущен
+    #[default]
+    Stopped,
+    /// Процесс запущен, баннер запуска еще не появился в логах
+    Starting,
+    /// Xray сообщил об успешном запуске
+    Running,
+    /// Процесс останавливается
+    Stopping,
+    /// Запуск не удался или процесс неожиданно завершился
+    Failed(String),
+}
+
+/// Имя состояния для API
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CoreStateName {
+    Stopped,
+    Starting,
+    Running,
+    Stopping,
+    Failed,
+}
+
+impl CoreState {
+    pub fn name(&self) -> CoreStateName {
+        match self {
+            CoreState::Stopped => CoreStateName::Stopped,
+            CoreState::Starting => CoreStateName::Starting,
+            CoreState::Running => CoreStateName::Running,
+            CoreState::Stopping => CoreStateName::Stopping,
+            CoreState::Failed(_) => CoreStateName::Failed,
+        }
+    }
+
+    /// Причина ошибки для состояния Failed
+    pub fn failure_reason(&self) -> Option<&str> {
+        match self {
+            CoreState::Failed(reason) => Some(reason),
+            _ => None,
+        }
+    }
+
+    /// Запуск еще не завершился
+    pub fn is_starting(&self) -> bool {
+        matches!(self, CoreState::Starting)
+    }
+
+    /// Ядро запущено и готово
+    pub fn is_running(&self) -> bool {
+        matches!(self, CoreState::Running)
+    }
+}
+
+impl fmt::Display for CoreStateName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            CoreStateName::Stopped => "stopped",
+            CoreStateName::Starting => "starting",
+            CoreStateName::Running => "running",
+            CoreStateName::Stopping => "stopping",
+            CoreStateName::Failed => "failed",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for CoreState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CoreState::Failed(reason) => write!(f, "failed: {}", reason),
+            state => write!(f, "{}", state.name()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_names() {
+        assert_eq!(serde_json::to_string(&CoreState::Starting.name()).unwrap(), "\"starting\"");
+        assert_eq!(CoreState::Failed("boom".to_string()).to_string(), "failed: boom");
+        assert_eq!(CoreState::Running.to_string(), "running");
+        assert_eq!(CoreState::Failed("boom".to_string()).failure_reason(), Some("boom"));
+        assert!(CoreState::default().failure_reason().is_none());
+    }
+}
@@ -0,0 +1,63 @@
+use std::os::unix::fs::PermissionsExt;
+
+/// Создает скрипт, который отвечает на "xray version" и выполняет body при "xray run"
+pub fn fake_xray(dir: &tempfile::TempDir, body: &str) -> String {
+    let path = dir.path().join("xray");
+    let script = format!(
+        "#!/bin/sh\nif [ \"$1\" = version ]; then echo 'Xray 1.8.4 (Xray, Penetrates Everything.)'; exit 0; fi\ncat > /dev/null\n{}\n",
+        body
+    );
+    std::fs::write(&path, script).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+/// Скрипт, который сообщает о запуске после паузы и работает до остановки
+pub fn fake_xray_started_after(dir: &tempfile::TempDir, delay: &str) -> String {
+    fake_xray(
+        dir,
+        &format!("sleep {}\necho '2024/01/01 00:00:00 [Warning] core: Xray 1.8.4 started'\nexec sleep 30", delay),
+    )
+}
+
+/// Скрипт, который не запускается с конфигурацией, содержащей "broken", и работает с любой другой
+pub fn fake_xray_rejecting_broken(dir: &tempfile::TempDir) -> String {
+    let path = dir.path().join("xray");
+    let script = "#!/bin/sh\n\
+        if [ \"$1\" = version ]; then echo 'Xray 1.8.4 (Xray, Penetrates Everything.)'; exit 0; fi\n\
+        case \"$(cat)\" in *broken*)\n\
+        echo 'Failed to start: main: failed to load config files: [stdin:] > infra/conf: unknown config id: broken'; exit 1;;\n\
+        esac\n\
+        echo '2024/01/01 00:00:00 [Warning] core: Xray 1.8.4 started'\n\
+        while true; do sleep 0.1; done\n";
+    std::fs::write(&path, script).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+/// Скрипт, который работает как fake_xray_started_after без паузы и записывает вызовы
+/// `xray api` (и содержимое переданных файлов) в dir/api.log
+/// Если есть dir/api.fail, команды api завершаются ошибкой; `rmi` без `-tags`
+/// читает аргументы как файлы конфигурации и тоже завершается ошибкой, как настоящий Xray
+pub fn fake_xray_with_api(dir: &tempfile::TempDir) -> String {
+    let path = dir.path().join("xray");
+    let dir = dir.path().display();
+    let script = format!(
+        "#!/bin/sh\n\
+        if [ \"$1\" = version ]; then echo 'Xray 1.8.4 (Xray, Penetrates Everything.)'; exit 0; fi\n\
+        if [ \"$1\" = api ]; then\n\
+        if [ -e '{dir}/api.fail' ]; then echo 'failed to call service: unknown service xray.app.proxyman.command.HandlerService' >&2; exit 1; fi\n\
+        if [ \"$2\" = rmi ]; then case \" $* \" in *' -tags '*) ;; *) echo \"failed to read config: open $4: no such file or directory\" >&2; exit 1;; esac; fi\n\
+        echo \"$*\" >> '{dir}/api.log'\n\
+        for last; do :; done\n\
+        if [ -f \"$last\" ]; then cat \"$last\" >> '{dir}/api.log'; echo >> '{dir}/api.log'; fi\n\
+        exit 0\n\
+        fi\n\
+        cat > /dev/null\n\
+        echo '2024/01/01 00:00:00 [Warning] core: Xray 1.8.4 started'\n\
+        while true; do sleep 0.1; done\n"
+    );
+    std::fs::write(&path, script).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path.to_string_lossy().into_owned()
+}
@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    sync::Weak,
+    time::{Duration, Instant},
+};
+use tokio::time::MissedTickBehavior;
+use tracing::{error, warn};
+
+use crate::config::Config as AppConfig;
+use crate::xray::process::XrayCore;
+
+/// Потребление ресурсов процессом Xray по данным /proc
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessResources {
+    pub rss_bytes: u64,
+    pub fds: u64,
+    pub threads: u64,
+}
+
+impl ProcessResources {
+    /// Снимает показатели процесса; None, если процесса нет или /proc недоступен
+    pub fn sample(pid: u32) -> Option<Self> {
+        let status = fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+        let (rss_kb, threads) = parse_status(&status)?;
+        let fds = fs::read_dir(format!("/proc/{}/fd", pid)).ok()?.count() as u64;
+        Some(Self {
+            rss_bytes: rss_kb * 1024,
+            fds,
+            threads,
+        })
+    }
+}
+
+/// VmRSS (kB) и Threads из /proc/<pid>/status
+fn parse_status(status: &str) -> Option<(u64, u64)> {
+    let field = |name: &str| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .and_then(|value| value.split_whitespace().next())
+            .and_then(|value| value.parse::<u64>().ok())
+    };
+    // У зомби и ядерных потоков VmRSS нет
+    Some((field("VmRSS:").unwrap_or(0), field("Threads:")?))
+}
+
+/// Пороги потребления ресурсов, 0 - порог выключен
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    pub rss_bytes: u64,
+    pub fds: u64,
+    pub threads: u64,
+}
+
+impl ResourceLimits {
+    /// Описания превышенных порогов
+    pub fn exceeded(&self, resources: &ProcessResources) -> Vec<String> {
+        let mut exceeded = Vec::new();
+        if self.rss_bytes > 0 && resources.rss_bytes > self.rss_bytes {
+            exceeded.push(format!(
+                "RSS {} MiB > {} MiB",
+                resources.rss_bytes / MIB,
+                self.rss_bytes / MIB
+            ));
+        }
+        if self.fds > 0 && resources.fds > self.fds {
+            exceeded.push(format!("{} open files > {}", resources.fds, self.fds));
+        }
+        if self.threads > 0 && resources.threads > self.threads {
+            exceeded.push(format!("{} threads > {}", resources.threads, self.threads));
+        }
+        exceeded
+    }
+}
+
+const MIB: u64 = 1024 * 1024;
+
+/// Настройки сторожа ресурсов Xray
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchdogOptions {
+    /// Период опроса /proc (ноль - сторож выключен)
+    pub interval: Duration,
+    /// Пороги, после которых пишется предупреждение
+    pub warn: ResourceLimits,
+    /// Пороги, после которых Xray перезапускается с последней конфигурацией
+    pub restart: ResourceLimits,
+    /// Минимальный интервал между перезапусками сторожем
+    pub restart_cooldown: Duration,
+}
+
+impl Default for WatchdogOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(10),
+            warn: ResourceLimits::default(),
+            restart: ResourceLimits::default(),
+            restart_cooldown: Duration::from_secs(300),
+        }
+    }
+}
+
+impl From<&AppConfig> for WatchdogOptions {
+    fn from(config: &AppConfig) -> Self {
+        Self {
+            interval: Duration::from_secs(config.xray_watchdog_interval_secs),
+            warn: ResourceLimits {
+                rss_bytes: config.xray_watchdog_rss_warn_mb * MIB,
+                fds: config.xray_watchdog_fds_warn,
+                threads: config.xray_watchdog_threads_warn,
+            },
+            restart: ResourceLimits {
+                rss_bytes: config.xray_watchdog_rss_restart_mb * MIB,
+                fds: config.xray_watchdog_fds_restart,
+                threads: config.xray_watchdog_threads_restart,
+            },
+            restart_cooldown: Duration::from_secs(config.xray_watchdog_restart_cooldown_secs),
+        }
+    }
+}
+
+/// Опрашивает ресурсы запущенного Xray, предупреждает о превышении порогов и
+/// перезапускает ядро. Завершается вместе с XrayCore
+pub async fn run_watchdog(core: Weak<XrayCore>, options: WatchdogOptions) {
+    let mut interval = tokio::time::interval(options.interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // Предупреждение пишется один раз, пока показатели не вернутся в норму
+    let mut warned = false;
+    let mut last_restart: Option<Instant> = None;
+
+    loop {
+        interval.tick().await;
+        let Some(core) = core.upgrade() else {
+            return;
+        };
+
+        let Some(resources) = core.sample_resources().await else {
+            warned = false;
+            continue;
+        };
+
+        let over_restart = options.restart.exceeded(&resources);
+        let cooled_down = last_restart.is_none_or(|at| at.elapsed() >= options.restart_cooldown);
+        if !over_restart.is_empty() && cooled_down {
+            last_restart = Some(Instant::now());
+            warned = false;
+            let reason = over_restart.join(", ");
+            match core.restart_with_last_config(&reason).await {
+                Ok(true) => {}
+                Ok(false) => warn!("Xray exceeded resource limits ({}), but there is no config to restart it with", reason),
+                Err(e) => error!("Watchdog failed to restart Xray: {}", e),
+            }
+            continue;
+        }
+
+        // Во время паузы между перезапусками превышение порога перезапуска - тоже предупреждение
+        let mut exceeded = options.warn.exceeded(&resources);
+        exceeded.extend(over_restart);
+        if exceeded.is_empty() {
+            warned = false;
+        } else if !warned {
+            warned = true;
+            warn!("Xray resource usage is high: {}", exceeded.join(", "));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_status() {
+        let status = "Name:\txray\nVmPeak:\t  900000 kB\nVmRSS:\t  524288 kB\nThreads:\t12\n";
+        assert_eq!(parse_status(status), Some((524288, 12)));
+        assert_eq!(parse_status("Name:\tkworker\nThreads:\t1\n"), Some((0, 1)));
+        assert_eq!(parse_status(""), None);
+    }
+
+    #[test]
+    fn test_sample_current_process() {
+        let resources = ProcessResources::sample(std::process::id()).unwrap();
+        assert!(resources.rss_bytes > 0);
+        assert!(resources.fds > 0);
+        assert!(resources.threads > 0);
+    }
+
+    #[test]
+    fn test_exceeded_limits() {
+        let resources = ProcessResources {
+            rss_bytes: 600 * MIB,
+            fds: 100,
+            threads: 8,
+        };
+        let limits = ResourceLimits {
+            rss_bytes: 512 * MIB,
+            fds: 1000,
+            threads: 0,
+        };
+        assert_eq!(limits.exceeded(&resources), vec!["RSS 600 MiB > 512 MiB".to_string()]);
+        assert!(ResourceLimits::default().exceeded(&resources).is_empty());
+    }
+}